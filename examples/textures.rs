@@ -55,7 +55,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     //
     //     for y in 0..12 {
     //         for x in 0..16 {
-    //             let mut sprite = Sprite::with_size(&graphics_device, x * 64, y * 64, 64, 64);
+    //             let mut sprite = Sprite::with_size(&graphics_device, x * 64, y * 64, 64, 64)?;
     //             sprite.set_texture(tex_rc.clone());
     //             sprites.push(sprite);
     //         }
@@ -64,7 +64,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Sprite Batch
     let mut sprites = vec![];
-    let mut sprite_batch = SpriteBatch::new(&graphics_device);
+    let mut sprite_batch = SpriteBatch::new(&graphics_device)?;
 
     {
         let img = image::open("src/test_pattern_2.png")?.to_rgba8();
@@ -105,6 +105,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut last_time = Instant::now();
     let mut dt = Duration::from_millis(16); // Avoid divide by 0.
     let mut fps = utils::FpsCounter::new();
+    // Vsync is off, so cap the frame rate instead of spinning.
+    let mut frame_limiter = utils::FrameLimiter::new(120.0);
 
     event_loop.run(move |event, _, control_flow| {
         // *control_flow = ControlFlow::Wait;
@@ -135,13 +137,15 @@ fn main() -> Result<(), Box<dyn Error>> {
                     sprite_batch.add(sprite);
                 }
 
-                graphics_device.maintain().unwrap();
+                graphics_device.begin_frame().unwrap();
                 graphics_device.clear_screen([0.1, 0.2, 0.3, 1.0]);
                 // graphics_device.draw(&sprites, shader.as_ref().unwrap());
                 sprite_batch.draw(&graphics_device, shader.as_ref().unwrap());
+                graphics_device.end_frame().unwrap();
 
                 // Important! Remember to swap the buffers else no drawing will show.
                 windowed_context.swap_buffers().unwrap();
+                frame_limiter.wait();
             }
             Event::WindowEvent { ref event, .. } => match event {
                 WindowEvent::Resized(physical_size) => {
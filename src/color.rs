@@ -0,0 +1,75 @@
+//! Colour representation.
+
+/// Linear RGBA colour, with each channel in the range 0.0 to 1.0.
+pub type Color = [f32; 4];
+
+pub const WHITE: Color = [1.0, 1.0, 1.0, 1.0];
+pub const BLACK: Color = [0.0, 0.0, 0.0, 1.0];
+pub const TRANSPARENT: Color = [0.0, 0.0, 0.0, 0.0];
+
+/// Converts to 8-bit channels, clamping each channel to the range 0.0 to 1.0.
+pub fn to_rgba8(color: Color) -> [u8; 4] {
+    let mut rgba = [0; 4];
+    for (byte, channel) in rgba.iter_mut().zip(color.iter()) {
+        *byte = (channel.clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+    rgba
+}
+
+/// Packs into a single integer of 8-bit channels, with red
+/// in the first byte in memory.
+pub fn pack_rgba8(color: Color) -> u32 {
+    u32::from_ne_bytes(to_rgba8(color))
+}
+
+/// Decodes an sRGB encoded channel to linear intensity.
+pub fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encodes a linear channel as sRGB, as done by an sRGB framebuffer.
+pub fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_rgba8() {
+        assert_eq!(to_rgba8(WHITE), [255, 255, 255, 255]);
+        assert_eq!(to_rgba8(TRANSPARENT), [0, 0, 0, 0]);
+        assert_eq!(to_rgba8([0.5, -1.0, 2.0, 1.0]), [128, 0, 255, 255]);
+        assert_eq!(pack_rgba8(BLACK).to_ne_bytes(), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_linear_interpolation() {
+        for channel in [0.0, 0.02, 0.25, 0.5, 1.0].iter() {
+            let round_trip = linear_to_srgb(srgb_to_linear(*channel));
+            assert!((round_trip - channel).abs() < 1e-5);
+        }
+
+        // Midpoint of a red to green gradient, as written to an sRGB framebuffer.
+        let lerp = |a: f32, b: f32| (a + b) * 0.5;
+        let red = [1.0, 0.0];
+        let green = [0.0, 1.0];
+
+        // Interpolated as is, the encoded midpoint is too dark.
+        let srgb_mid = lerp(red[0], green[0]);
+        assert_eq!(to_rgba8([srgb_mid; 4])[0], 128);
+
+        // Interpolated in linear space, then encoded by the framebuffer.
+        let linear_mid = lerp(srgb_to_linear(red[0]), srgb_to_linear(green[0]));
+        assert_eq!(to_rgba8([linear_to_srgb(linear_mid); 4])[0], 188);
+    }
+}
@@ -1,106 +1,670 @@
 //! Graphics device context.
-use crate::{errors::debug_assert_gl, marker::Invariant};
+use crate::{
+    color::{self, Color},
+    dpi::PhysicalSize,
+    errors::{self, debug_assert_gl, gl_error, gl_error_at},
+    marker::Invariant,
+    primitives,
+    rect::Rect,
+    shader::Shader,
+    texture::{self, PixelFormat, Texture},
+    utils,
+    vertex::{Vertex, VertexBuffer},
+};
 use glow::HasContext;
-use glutin::{dpi::PhysicalSize, PossiblyCurrent};
-use std::collections::HashSet;
-use std::{cell::Cell, fmt, marker::PhantomData, sync::mpsc};
+#[cfg(feature = "window")]
+use glutin::PossiblyCurrent;
+use std::collections::{HashMap, HashSet};
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    ffi::c_void,
+    fmt,
+    marker::PhantomData,
+    mem,
+    rc::{Rc, Weak},
+};
 
 pub struct GraphicDevice {
     pub(crate) gl: glow::Context,
     extensions: HashSet<String>,
-    tx: mpsc::Sender<Destroy>,
-    rx: mpsc::Receiver<Destroy>,
+    /// Version and driver strings, queried once at construction.
+    info: OpenGlInfo,
+    /// Major and minor version parsed from `info`.
+    gl_version: (u32, u32),
+    /// Lazily created 1x1 textures of a single colour.
+    ///
+    /// Declared before the destroy queue so they're dropped while
+    /// the queue is still alive.
+    solid_textures: RefCell<HashMap<[u8; 4], Texture>>,
+    /// Lazily created placeholder for textures that failed to load.
+    missing_texture: RefCell<Option<Texture>>,
+    /// Lazily created quad covering clip space, for `draw_fullscreen`.
+    fullscreen_quad: RefCell<Option<VertexBuffer>>,
+    tx: DestroySender,
+    rx: DestroyQueue,
+    /// Viewport size, at least 1x1.
     size: Cell<PhysicalSize<u32>>,
+    /// Whether the viewport was given a zero dimension, like when
+    /// the window is minimized.
+    viewport_empty: Cell<bool>,
+    /// Whether the y-axis of the current render target must be flipped
+    /// to put the origin in the top-left.
+    flip_y: Cell<bool>,
+    /// Constant colour used by `BlendMode::Constant`.
+    blend_color: Cell<Color>,
+    line_width: Cell<f32>,
+    /// Scissor rectangle set by `set_scissor`, while the test is enabled.
+    scissor: Cell<Option<Rect<u32>>>,
+    /// `GL_MAX_TEXTURE_IMAGE_UNITS`, queried on first use.
+    max_texture_units: Cell<Option<u32>>,
+    /// Minimum and maximum supported width of aliased lines.
+    line_width_range: [f32; 2],
     shutting_down: Cell<bool>,
+    /// Set when the OpenGL context was lost, and all handles are invalid.
+    context_lost: Cell<bool>,
+    /// Set between `begin_frame` and `end_frame`.
+    in_frame: Cell<bool>,
+    /// Bytes uploaded to textures since `begin_frame`.
+    upload_bytes: Cell<usize>,
+    /// Soft limit on `upload_bytes`, set by `set_upload_budget`.
+    upload_budget: Cell<Option<usize>>,
+    /// Primitive restart index, and the capability enabled for it.
+    primitive_restart: Cell<Option<(u32, u32)>>,
+    /// Entry points not wrapped by glow, loaded when the device is
+    /// created with a function loader.
+    pub(crate) fns: GlFunctions,
     /// Inner OpenGL context has inner mutability, and is not thread safe.
     _invariant: Invariant,
 }
 
 impl GraphicDevice {
+    /// Maximum number of colours kept by `solid_texture`.
+    const SOLID_TEXTURE_CACHE_SIZE: usize = 16;
+
+    /// Create a device drawing with the given context.
+    ///
+    /// Entry points glow doesn't wrap can't be looked up from the context,
+    /// so features relying on them, like compressed textures, are
+    /// unsupported. See `from_loader_function`.
     pub fn new(gl: glow::Context) -> Self {
-        let mut extensions = HashSet::new();
+        Self::builder().build(gl)
+    }
 
-        // This implementation is taken from glow::Context::from_loader_function.
-        let num_extensions = unsafe { gl.get_parameter_i32(glow::NUM_EXTENSIONS) };
-        for i in 0..num_extensions {
-            let extension_name =
-                unsafe { gl.get_parameter_indexed_string(glow::EXTENSIONS, i as u32) };
-            extensions.insert(extension_name);
-        }
+    /// Create a device and its context from a function that looks up
+    /// OpenGL entry points by name, like `eglGetProcAddress`.
+    ///
+    /// Entry points glow doesn't wrap are loaded with the same function.
+    ///
+    /// # Safety
+    ///
+    /// The context the functions belong to must be current on this thread.
+    pub unsafe fn from_loader_function<F>(loader: F) -> Self
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        Self::builder().build_with_loader(loader)
+    }
 
-        println!("Extensions:");
-        for ext in extensions.iter() {
-            println!("  {}", ext);
+    /// Configure the device before creating it.
+    pub fn builder() -> DeviceBuilder {
+        DeviceBuilder {
+            log_extensions: false,
         }
+    }
 
-        // Ensure our preferred settings.
-        unsafe {
-            gl.front_face(glow::CCW); // Counter-clockwise winding.
-                                      // gl.enable(glow::CULL_FACE);
-                                      // gl.cull_face(glow::BACK);
-        }
+    fn with_builder(gl: glow::Context, fns: GlFunctions, builder: DeviceBuilder) -> Self {
+        let extensions = Self::query_extensions(&gl, builder.log_extensions);
+        let info = Self::query_info(&gl);
+        let line_width_range = Self::query_line_width_range(&fns);
+        Self::apply_preferred_settings(&gl);
 
         // Dropped resources need to be deallocated via the OpenGL context.
-        let (tx, rx) = mpsc::channel();
+        let (tx, rx) = DestroySender::channel();
 
         Self {
             gl,
             extensions,
+            gl_version: parse_gl_version(&info.version).unwrap_or((0, 0)),
+            info,
+            solid_textures: RefCell::new(HashMap::new()),
+            missing_texture: RefCell::new(None),
+            fullscreen_quad: RefCell::new(None),
             tx,
             rx,
             size: Cell::new(PhysicalSize::new(640, 480)),
+            viewport_empty: Cell::new(false),
+            flip_y: Cell::new(true),
+            blend_color: Cell::new(crate::color::TRANSPARENT),
             shutting_down: Cell::new(false),
+            context_lost: Cell::new(false),
+            in_frame: Cell::new(false),
+            upload_bytes: Cell::new(0),
+            upload_budget: Cell::new(None),
+            primitive_restart: Cell::new(None),
+            line_width: Cell::new(1.0),
+            scissor: Cell::new(None),
+            max_texture_units: Cell::new(None),
+            line_width_range,
+            fns,
             _invariant: PhantomData,
         }
     }
 
+    fn query_extensions(gl: &glow::Context, log_extensions: bool) -> HashSet<String> {
+        // This implementation is taken from glow::Context::from_loader_function.
+        let num_extensions = unsafe { gl.get_parameter_i32(glow::NUM_EXTENSIONS) };
+        let names = (0..num_extensions)
+            .map(|i| unsafe { gl.get_parameter_indexed_string(glow::EXTENSIONS, i as u32) });
+
+        collect_extensions(names, log_extensions, |line| debug!("{}", line))
+    }
+
+    fn query_info(gl: &glow::Context) -> OpenGlInfo {
+        unsafe {
+            let version = gl.get_parameter_string(glow::VERSION);
+            let vendor = gl.get_parameter_string(glow::VENDOR);
+            let renderer = gl.get_parameter_string(glow::RENDERER);
+            debug_assert_gl(gl, ());
+
+            OpenGlInfo {
+                version,
+                vendor,
+                renderer,
+            }
+        }
+    }
+
+    /// Range of aliased line widths, or 1.0 to 1.0 when `glGetFloatv`
+    /// is not loaded.
+    fn query_line_width_range(fns: &GlFunctions) -> [f32; 2] {
+        let mut range = [1.0; 2];
+        // A pair of floats, which glow can't query.
+        if let Some(get_float_v) = fns.get_float_v {
+            unsafe { get_float_v(glow::ALIASED_LINE_WIDTH_RANGE, range.as_mut_ptr()) };
+        }
+        range
+    }
+
+    fn apply_preferred_settings(gl: &glow::Context) {
+        // Ensure our preferred settings.
+        unsafe {
+            gl.front_face(glow::CCW); // Counter-clockwise winding.
+                                      // gl.enable(glow::CULL_FACE);
+                                      // gl.cull_face(glow::BACK);
+        }
+    }
+
+    /// Access to the raw OpenGL context, as an escape hatch for calls
+    /// this crate doesn't wrap.
+    ///
+    /// # Safety
+    ///
+    /// The context is not thread safe, and must only be used on the
+    /// thread that owns the device.
+    ///
+    /// Resources owned by the crate, like textures, shaders and vertex
+    /// buffers, must not be deleted through the context. They are
+    /// deallocated when dropped. Global state changed via the context,
+    /// like bindings, may also affect the crate's drawing, and should be
+    /// restored afterwards.
+    pub unsafe fn gl_context(&self) -> &glow::Context {
+        &self.gl
+    }
+
     pub fn has_extension(&self, extension: &str) -> bool {
         self.extensions.contains(extension)
     }
 
+    /// Create a device drawing to the window of a glutin context, with
+    /// the viewport set to the window's size.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current on this thread.
+    #[cfg(feature = "window")]
     pub unsafe fn from_windowed_context(
         windowed_context: &glutin::WindowedContext<PossiblyCurrent>,
     ) -> Self {
-        let gl = glow::Context::from_loader_function(|s| {
-            windowed_context.get_proc_address(s) as *const _
-        });
+        Self::builder().build_windowed(windowed_context)
+    }
 
-        let device = Self::new(gl);
+    /// # Safety
+    ///
+    /// The context must be current on this thread.
+    #[cfg(feature = "window")]
+    unsafe fn from_windowed_context_with(
+        windowed_context: &glutin::WindowedContext<PossiblyCurrent>,
+        builder: DeviceBuilder,
+    ) -> Self {
+        let device =
+            builder.build_with_loader(|s| windowed_context.get_proc_address(s) as *const _);
         device.set_viewport_size(windowed_context.window().inner_size());
-
         device
     }
 
+    /// Version and driver strings of the context, queried when the
+    /// device was created.
     pub fn opengl_info(&self) -> OpenGlInfo {
-        unsafe {
-            let version = self.gl.get_parameter_string(glow::VERSION);
-            let vendor = self.gl.get_parameter_string(glow::VENDOR);
-            let renderer = self.gl.get_parameter_string(glow::RENDERER);
-            debug_assert_gl(&self.gl, ());
+        self.info.clone()
+    }
 
-            OpenGlInfo {
-                version,
-                vendor,
-                renderer,
-            }
-        }
+    /// Major and minor version of the context, like `(3, 3)`.
+    ///
+    /// OpenGL ES contexts report their ES version. `(0, 0)` when the
+    /// driver's version string could not be parsed.
+    pub fn gl_version(&self) -> (u32, u32) {
+        self.gl_version
     }
 
-    pub(crate) fn destroy_sender(&self) -> mpsc::Sender<Destroy> {
+    /// Whether the context is OpenGL ES, rather than desktop OpenGL.
+    pub fn is_gles(&self) -> bool {
+        is_gles_version(&self.info.version)
+    }
+
+    pub(crate) fn destroy_sender(&self) -> DestroySender {
         self.tx.clone()
     }
 
+    /// Set the size of the window's framebuffer in pixels.
+    ///
+    /// When either dimension is 0, like when the window is minimized,
+    /// drawing and clearing the screen are skipped until a non-empty
+    /// size is set. The stored size is kept at least 1x1, so resolution
+    /// uniforms never divide by zero.
     pub fn set_viewport_size(&self, size: PhysicalSize<u32>) {
+        let (size, empty) = clamp_viewport_size(size);
         self.size.set(size);
+        self.viewport_empty.set(empty);
+    }
+
+    /// Whether the last viewport size set had a zero dimension.
+    pub fn is_viewport_empty(&self) -> bool {
+        self.viewport_empty.get()
     }
 
     pub fn get_viewport_size(&self) -> PhysicalSize<u32> {
         self.size.get()
     }
 
+    /// Set whether the current render target needs its y-axis flipped.
+    ///
+    /// The window's default framebuffer has its origin in the bottom-left,
+    /// so the sprite shader flips the y-axis to put the origin in the top-left.
+    /// Off-screen render targets that will be sampled as textures should not
+    /// be flipped, otherwise their contents end up upside down.
+    ///
+    /// Defaults to `true`, the window behaviour.
+    pub fn set_flip_y(&self, flip_y: bool) {
+        self.flip_y.set(flip_y);
+    }
+
+    pub fn get_flip_y(&self) -> bool {
+        self.flip_y.get()
+    }
+
+    /// Set how drawn fragments are combined with the contents of the framebuffer.
+    pub fn set_blend_mode(&self, blend_mode: BlendMode) {
+        unsafe {
+            match blend_mode.blend_state() {
+                None => {
+                    self.gl.disable(glow::BLEND);
+                }
+                Some(state) => {
+                    self.gl.enable(glow::BLEND);
+                    let [src_rgb, dst_rgb, src_alpha, dst_alpha] = state.factors;
+                    self.gl.blend_func_separate(
+                        src_rgb.to_gl(),
+                        dst_rgb.to_gl(),
+                        src_alpha.to_gl(),
+                        dst_alpha.to_gl(),
+                    );
+                    let [equation_rgb, equation_alpha] = state.equations;
+                    self.gl
+                        .blend_equation_separate(equation_rgb.to_gl(), equation_alpha.to_gl());
+                }
+            }
+            debug_assert_gl(&self.gl, ());
+        }
+    }
+
+    /// Reads back the current blend factors from OpenGL, in the order
+    /// source RGB, destination RGB, source alpha, destination alpha.
+    ///
+    /// Returns the raw OpenGL enums, for debugging blend state.
+    pub fn blend_factors(&self) -> [u32; 4] {
+        let mut factors = [0; 4];
+        let parameters = [
+            glow::BLEND_SRC_RGB,
+            glow::BLEND_DST_RGB,
+            glow::BLEND_SRC_ALPHA,
+            glow::BLEND_DST_ALPHA,
+        ];
+        for (factor, parameter) in factors.iter_mut().zip(parameters.iter()) {
+            *factor = unsafe { self.gl.get_parameter_i32(*parameter) } as u32;
+        }
+        factors
+    }
+
+    /// Unbinds the state the crate's drawing leaves bound, returning to a
+    /// known baseline.
+    ///
+    /// Use as a boundary between the crate's drawing and rendering done
+    /// through the raw context or third-party libraries. Unbinds the vertex
+    /// array, shader program, array and pixel buffers, and the texture and
+    /// sampler of unit 0, which is left as the active unit.
+    ///
+    /// When `disable_capabilities` is set, blending and the scissor test
+    /// are disabled as well. The blend mode must then be set again before
+    /// drawing translucent sprites.
+    pub fn reset_gl_state(&self, disable_capabilities: bool) {
+        unsafe {
+            self.gl.bind_vertex_array(None);
+            self.gl.use_program(None);
+            self.gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            self.gl.bind_buffer(glow::PIXEL_PACK_BUFFER, None);
+            self.gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, None);
+
+            self.gl.active_texture(glow::TEXTURE0);
+            self.gl.bind_texture(glow::TEXTURE_2D, None);
+            self.gl.bind_sampler(0, None);
+
+            if disable_capabilities {
+                self.gl.disable(glow::BLEND);
+                self.gl.disable(glow::SCISSOR_TEST);
+                self.scissor.set(None);
+            }
+
+            debug_assert_gl(&self.gl, ());
+        }
+    }
+
+    /// Set the constant colour used by `BlendMode::Constant`.
+    ///
+    /// The colour is stored on the device and persists until changed.
+    pub fn set_blend_color(&self, color: Color) {
+        self.blend_color.set(color);
+
+        unsafe {
+            self.gl.blend_color(color[0], color[1], color[2], color[3]);
+            debug_assert_gl(&self.gl, ());
+        }
+    }
+
+    pub fn get_blend_color(&self) -> Color {
+        self.blend_color.get()
+    }
+
+    /// Set the width in pixels of lines drawn with `Primitive::Lines`.
+    ///
+    /// The width is clamped to the device's supported range. Core profile
+    /// contexts may only support a width of 1.0, so thick lines should be
+    /// drawn as geometry instead, see `shape::line_aa`.
+    ///
+    /// Devices created without a function loader assume a range of 1.0 to 1.0.
+    pub fn set_line_width(&self, width: f32) {
+        let [min, max] = self.line_width_range;
+        let width = width.clamp(min, max);
+        self.line_width.set(width);
+
+        unsafe {
+            self.gl.line_width(width);
+            debug_assert_gl(&self.gl, ());
+        }
+    }
+
+    pub fn get_line_width(&self) -> f32 {
+        self.line_width.get()
+    }
+
+    /// Minimum and maximum supported line width.
+    pub fn line_width_range(&self) -> [f32; 2] {
+        self.line_width_range
+    }
+
+    /// Enable primitive restart with the given index, or disable it with `None`.
+    ///
+    /// While enabled, an index equal to the restart index in an indexed
+    /// draw ends the current primitive, and the next index starts a new
+    /// one. A tilemap chunk can then draw each row as a triangle strip in
+    /// a single draw call, without the rows being joined by triangles.
+    /// See `primitives::grid_strip_indices`.
+    ///
+    /// Requires OpenGL 3.1 or later, with `glPrimitiveRestartIndex` loaded
+    /// by `from_loader_function`. Otherwise only `primitives::RESTART_INDEX`
+    /// is supported, which requires OpenGL 4.3 or OpenGL ES 3.0.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Unsupported` when the index can't be used on this device.
+    pub fn set_primitive_restart(&self, index: Option<u32>) -> errors::Result<()> {
+        unsafe {
+            if let Some((_, capability)) = self.primitive_restart.take() {
+                self.gl.disable(capability);
+            }
+
+            if let Some(index) = index {
+                let has_restart_index = self.fns.primitive_restart_index.is_some();
+                let capability = primitive_restart_capability(index, has_restart_index)?;
+                self.gl.enable(capability);
+                if let Some(primitive_restart_index) = self.fns.primitive_restart_index {
+                    primitive_restart_index(index);
+                }
+                gl_error_at(&self.gl, "primitive_restart_index", ())?;
+                self.primitive_restart.set(Some((index, capability)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Index that restarts primitives, when enabled.
+    pub fn primitive_restart(&self) -> Option<u32> {
+        self.primitive_restart.get().map(|(index, _)| index)
+    }
+
+    /// Take the oldest error recorded by OpenGL, if any.
+    ///
+    /// Unlike `errors::assert_gl`, this never panics, and unlike
+    /// `errors::debug_assert_gl` it also checks in release builds, so
+    /// errors can be logged without crashing.
+    pub fn take_gl_error(&self) -> Option<u32> {
+        match unsafe { self.gl.get_error() } {
+            glow::NO_ERROR => None,
+            code => Some(code),
+        }
+    }
+
+    /// Take all errors recorded by OpenGL, oldest first.
+    ///
+    /// Implementations may queue more than one error, one per flag. The
+    /// result is empty when there were no errors.
+    pub fn drain_gl_errors(&self) -> Vec<u32> {
+        drain_errors(|| self.take_gl_error())
+    }
+
+    /// Number of texture units a fragment shader can sample from,
+    /// which is at least 16.
+    pub fn max_texture_units(&self) -> u32 {
+        if let Some(units) = self.max_texture_units.get() {
+            return units;
+        }
+
+        let units =
+            unsafe { self.gl.get_parameter_i32(glow::MAX_TEXTURE_IMAGE_UNITS) }.max(0) as u32;
+        self.max_texture_units.set(Some(units));
+        units
+    }
+
+    /// Highest anisotropic filtering level accepted by
+    /// `TextureParams::anisotropy`, or `None` when the context lacks
+    /// `GL_EXT_texture_filter_anisotropic`.
+    pub fn max_anisotropy(&self) -> Option<f32> {
+        if !self.has_extension("GL_EXT_texture_filter_anisotropic") {
+            return None;
+        }
+
+        // Queried as an integer, since glow doesn't wrap glGetFloatv.
+        // Drivers report whole numbers, usually 16.
+        let max = unsafe { self.gl.get_parameter_i32(glow::MAX_TEXTURE_MAX_ANISOTROPY) };
+        Some((max as f32).max(1.0))
+    }
+
+    /// Whether the OpenGL context is usable.
+    ///
+    /// Returns `false` after `mark_context_lost`, until the device is
+    /// given a new context via `recreate`.
+    pub fn is_context_valid(&self) -> bool {
+        !self.context_lost.get()
+    }
+
+    /// Signal that the OpenGL context was lost.
+    ///
+    /// On some platforms, like mobile or after a driver reset, the context
+    /// can be lost, invalidating every handle it allocated. Once marked:
+    ///
+    /// - Draws and clears become no-ops.
+    /// - `maintain` discards pending destroys without making OpenGL calls,
+    ///   since the handles no longer refer to anything.
+    ///
+    /// # Recovery
+    ///
+    /// Call `recreate_with_loader` or `recreate` with a new context, then
+    /// rebuild every GPU resource, like textures, shaders and sprite batches,
+    /// using the device. Resources created before the loss must be dropped
+    /// and not used again. Their drops will be discarded by the next `maintain`.
+    pub fn mark_context_lost(&self) {
+        self.context_lost.set(true);
+    }
+
+    /// Replace a lost OpenGL context with a new one.
+    ///
+    /// Pending destroys belonging to the old context are discarded, and
+    /// the device's preferred settings are applied to the new context.
+    ///
+    /// Entry points loaded for the old context may not be valid for the
+    /// new one, so like a device created with `new`, features relying on
+    /// them become unsupported. See `recreate_with_loader`.
+    ///
+    /// See `mark_context_lost` for the recovery contract.
+    pub fn recreate(&mut self, gl: glow::Context) {
+        self.replace_context(gl, GlFunctions::default());
+    }
+
+    /// Replace a lost OpenGL context with a new one, created from a function
+    /// that looks up its entry points by name.
+    ///
+    /// Entry points glow doesn't wrap are reloaded with the same function,
+    /// like `from_loader_function`.
+    ///
+    /// # Safety
+    ///
+    /// The context the functions belong to must be current on this thread.
+    pub unsafe fn recreate_with_loader<F>(&mut self, mut loader: F)
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        let gl = glow::Context::from_loader_function(&mut loader);
+        self.replace_context(gl, GlFunctions::load(loader));
+    }
+
+    fn replace_context(&mut self, gl: glow::Context, fns: GlFunctions) {
+        // Cached resources, and handles queued up to now,
+        // belong to the old context.
+        self.context_lost.set(true);
+        self.solid_textures.borrow_mut().clear();
+        self.missing_texture.borrow_mut().take();
+        self.fullscreen_quad.borrow_mut().take();
+        let _ = self.maintain();
+
+        self.extensions = Self::query_extensions(&gl, false);
+        self.info = Self::query_info(&gl);
+        self.gl_version = parse_gl_version(&self.info.version).unwrap_or((0, 0));
+        Self::apply_preferred_settings(&gl);
+        self.gl = gl;
+        self.fns = fns;
+        self.primitive_restart.set(None);
+        self.line_width.set(1.0);
+        self.line_width_range = Self::query_line_width_range(&self.fns);
+        self.max_texture_units.set(None);
+        self.context_lost.set(false);
+    }
+
+    /// Shared 1x1 white texture, created on first use.
+    ///
+    /// Useful for drawing untextured shapes with a textured shader.
+    pub fn white_texture(&self) -> errors::Result<Texture> {
+        self.solid_texture(color::WHITE)
+    }
+
+    /// Shared 1x1 texture of the given colour, created on first use.
+    ///
+    /// Colours are cached by their 8-bit value. Once the cache
+    /// is full, textures of new colours are not kept.
+    pub fn solid_texture(&self, color: Color) -> errors::Result<Texture> {
+        let rgba = color::to_rgba8(color);
+        if let Some(texture) = self.solid_textures.borrow().get(&rgba) {
+            return Ok(texture.clone());
+        }
+
+        let mut texture = Texture::new(self, 1, 1)?;
+        texture.update_data(self, &rgba)?;
+
+        let mut solid_textures = self.solid_textures.borrow_mut();
+        if solid_textures.len() < Self::SOLID_TEXTURE_CACHE_SIZE {
+            solid_textures.insert(rgba, texture.clone());
+        }
+
+        Ok(texture)
+    }
+
+    /// Shared magenta and black checkerboard texture, created on first use.
+    ///
+    /// Stands in for textures that failed to load, so they are
+    /// obvious on screen. See `Texture::from_path_or_missing`.
+    pub fn missing_texture(&self) -> errors::Result<Texture> {
+        if let Some(texture) = self.missing_texture.borrow().as_ref() {
+            return Ok(texture.clone());
+        }
+
+        let size = Texture::MISSING_SIZE;
+        let mut texture = Texture::new(self, size, size)?;
+        texture.update_data(self, &texture::missing_texture_data())?;
+        *self.missing_texture.borrow_mut() = Some(texture.clone());
+
+        Ok(texture)
+    }
+
+    /// Panics if resources were dropped, but not yet deallocated
+    /// by `maintain`.
+    ///
+    /// Call after the last `maintain` at shutdown to detect video memory
+    /// that would otherwise leak. Only checks in debug builds.
+    pub fn debug_assert_no_leaks(&self) {
+        #[cfg(debug_assertions)]
+        {
+            let pending = self.rx.len();
+            if pending > 0 {
+                panic!(
+                    "{} dropped resources were not deallocated. Call GraphicDevice::maintain before shutting down.",
+                    pending
+                );
+            }
+        }
+    }
+
+    /// Whether `shutdown` has been called.
+    ///
+    /// Resources may already be deallocated, so nothing should be drawn.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.get()
+    }
+
     pub fn shutdown(&self) {
         self.shutting_down.set(true);
-        self.maintain();
+        if let Err(err) = self.maintain() {
+            warn!("Failed to deallocate resources during shutdown: {}", err);
+        }
     }
 
     pub fn draw(&self, sprites: &[crate::sprite::Sprite], shader: &crate::shader::Shader) {
@@ -109,7 +673,11 @@ impl GraphicDevice {
         // Destroying resources before a draw will cause memory access errors.
         // FIXME: Test whether the drop and maintain prevents this.
         if self.shutting_down.get() {
-            println!("Shutting down");
+            info!("Shutting down");
+            return;
+        }
+
+        if self.context_lost.get() || self.viewport_empty.get() {
             return;
         }
 
@@ -128,21 +696,17 @@ impl GraphicDevice {
                 canvas_size.width as f32,
                 canvas_size.height as f32,
             );
+            self.gl.uniform_1_i32(Some(&2), self.flip_y.get() as i32);
         }
 
         for sprite in sprites {
             unsafe {
                 // Only sprites with textures are drawn.
                 if let Some(texture_handle) = sprite.texture_handle() {
-                    self.gl.bind_vertex_array(Some(sprite.vertex_buffer.vbo));
-
                     self.gl.active_texture(glow::TEXTURE0);
                     self.gl.bind_texture(glow::TEXTURE_2D, Some(texture_handle));
 
-                    // FIXME: Unsigned short is a detail of the vertex buffer, so drawing should probably happen there.
-                    self.gl
-                        .draw_elements(glow::TRIANGLES, 6, glow::UNSIGNED_SHORT, 0);
-                    debug_assert_gl(&self.gl, ());
+                    sprite.vertex_buffer.draw(self, 0, 6);
                 }
             }
         }
@@ -154,7 +718,57 @@ impl GraphicDevice {
         }
     }
 
+    /// Draw the texture stretched over the whole viewport, bypassing the
+    /// pixel coordinates of the sprite path.
+    ///
+    /// The quad's positions are in clip space, from -1 to 1, with texture
+    /// coordinates from 0 to 1, where (0, 0) is the top-left corner. So
+    /// the shader passes the position straight through, and the first row
+    /// of uploaded image data is drawn at the top, like sprites. The attribute
+    /// locations are those of `Vertex`: position 0, UV 1 and colour 2.
+    ///
+    /// Draws into whatever framebuffer and viewport are bound, so a bound
+    /// `RenderTarget` is covered instead of the window.
+    ///
+    /// The quad is created on first use, and shared by later calls.
+    pub fn draw_fullscreen(&self, texture: &Texture, shader: &Shader) -> errors::Result<()> {
+        if self.shutting_down.get() || self.context_lost.get() || self.viewport_empty.get() {
+            return Ok(());
+        }
+
+        if self.fullscreen_quad.borrow().is_none() {
+            let quad = VertexBuffer::new_static(
+                self,
+                &fullscreen_quad_vertices(),
+                &fullscreen_quad_indices(),
+            )?;
+            *self.fullscreen_quad.borrow_mut() = Some(quad);
+        }
+        let quad = self.fullscreen_quad.borrow();
+        let quad = quad.as_ref().expect("fullscreen quad was just created");
+
+        unsafe {
+            self.gl.use_program(Some(shader.program));
+
+            self.gl.active_texture(glow::TEXTURE0);
+            self.gl
+                .bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+
+            quad.draw(self, 0, 6);
+
+            self.gl.bind_vertex_array(None);
+            self.gl.bind_texture(glow::TEXTURE_2D, None);
+            self.gl.use_program(None);
+        }
+
+        Ok(())
+    }
+
     pub fn clear_screen(&self, color: [f32; 4]) {
+        if self.context_lost.get() || self.viewport_empty.get() {
+            return;
+        }
+
         unsafe {
             let physical_size_i32 = self.size.get().cast::<i32>();
             self.gl
@@ -166,21 +780,249 @@ impl GraphicDevice {
         }
     }
 
-    pub fn maintain(&self) -> crate::errors::Result<()> {
-        while let Ok(resource) = self.rx.try_recv() {
+    /// Clear only a rectangle of the framebuffer, like a dirty region,
+    /// leaving the pixels outside of it untouched.
+    ///
+    /// The rectangle is in pixels, like `set_scissor`. The previous
+    /// scissor state is restored afterwards.
+    pub fn clear_region(&self, rect: Rect<u32>, color: [f32; 4]) {
+        if self.context_lost.get() || self.viewport_empty.get() {
+            return;
+        }
+
+        let _save = ScissorSave::new(self);
+        self.set_scissor(Some(rect));
+
+        unsafe {
+            self.gl.clear_color(color[0], color[1], color[2], color[3]);
+            self.gl.clear(glow::COLOR_BUFFER_BIT);
+            debug_assert_gl(&self.gl, ());
+        }
+    }
+
+    /// Restrict drawing and clearing to a rectangle, or disable the
+    /// scissor test with `None`.
+    ///
+    /// The rectangle is in pixels with the origin in the top-left, like
+    /// sprites. While the y-axis is flipped for the window, it's converted
+    /// to OpenGL's bottom-left origin using the viewport height.
+    ///
+    /// Only the state set through the device is tracked. Scissor state
+    /// changed via the raw context is overwritten.
+    pub fn set_scissor(&self, rect: Option<Rect<u32>>) {
+        self.scissor.set(rect);
+
+        unsafe {
+            match rect {
+                Some(rect) => {
+                    let flip_height = Some(self.size.get().height).filter(|_| self.flip_y.get());
+                    let [x, y, width, height] = scissor_box(rect, flip_height);
+                    self.gl.enable(glow::SCISSOR_TEST);
+                    self.gl.scissor(x, y, width, height);
+                }
+                None => self.gl.disable(glow::SCISSOR_TEST),
+            }
+            debug_assert_gl(&self.gl, ());
+        }
+    }
+
+    /// Scissor rectangle set by `set_scissor`, if enabled.
+    pub fn scissor(&self) -> Option<Rect<u32>> {
+        self.scissor.get()
+    }
+
+    /// Limit the bytes uploaded to textures each frame, or remove the
+    /// limit with `None`.
+    ///
+    /// The limit is soft. Uploads are accepted while the bytes uploaded
+    /// since `begin_frame` are under the budget, so the last one may go
+    /// over it. Once it's spent, texture updates return `UploadBudgetExceeded`
+    /// without uploading, and the caller can defer them to the next frame.
+    pub fn set_upload_budget(&self, budget: Option<usize>) {
+        self.upload_budget.set(budget);
+    }
+
+    pub fn upload_budget(&self) -> Option<usize> {
+        self.upload_budget.get()
+    }
+
+    /// Bytes uploaded to textures since the last `begin_frame`.
+    pub fn upload_bytes_this_frame(&self) -> usize {
+        self.upload_bytes.get()
+    }
+
+    /// Count an upload of the given size against the frame's budget.
+    ///
+    /// Returns `UploadBudgetExceeded` when the budget is already spent,
+    /// in which case the bytes are not counted.
+    pub(crate) fn charge_upload(&self, bytes: usize) -> errors::Result<()> {
+        let used = self.upload_bytes.get();
+        check_upload_budget(used, self.upload_budget.get())?;
+        self.upload_bytes.set(used + bytes);
+        Ok(())
+    }
+
+    /// Set the byte alignment of pixel rows uploaded to textures.
+    ///
+    /// OpenGL defaults to 4, which assumes each row in client memory starts
+    /// on a 4-byte boundary. Tightly packed rows whose byte length is not a
+    /// multiple of 4, like single channel images with odd widths, need an
+    /// alignment of 1.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidAlignment` if the alignment is not 1, 2, 4 or 8.
+    pub fn set_unpack_alignment(&self, alignment: u8) -> errors::Result<()> {
+        Self::validate_alignment(alignment)?;
+
+        unsafe {
+            self.gl
+                .pixel_store_i32(glow::UNPACK_ALIGNMENT, alignment as i32);
+            gl_error(&self.gl, ())
+        }
+    }
+
+    pub fn get_unpack_alignment(&self) -> u8 {
+        unsafe { self.gl.get_parameter_i32(glow::UNPACK_ALIGNMENT) as u8 }
+    }
+
+    /// Set the byte alignment of pixel rows read back from the framebuffer.
+    ///
+    /// See `set_unpack_alignment`.
+    pub fn set_pack_alignment(&self, alignment: u8) -> errors::Result<()> {
+        Self::validate_alignment(alignment)?;
+
+        unsafe {
+            self.gl
+                .pixel_store_i32(glow::PACK_ALIGNMENT, alignment as i32);
+            gl_error(&self.gl, ())
+        }
+    }
+
+    pub fn get_pack_alignment(&self) -> u8 {
+        unsafe { self.gl.get_parameter_i32(glow::PACK_ALIGNMENT) as u8 }
+    }
+
+    fn validate_alignment(alignment: u8) -> errors::Result<()> {
+        match alignment {
+            1 | 2 | 4 | 8 => Ok(()),
+            _ => Err(errors::Error::InvalidAlignment(alignment)),
+        }
+    }
+
+    /// Reads back a rectangle of the currently bound framebuffer as RGBA8.
+    ///
+    /// The rectangle is in pixels with the origin in the top-left, like
+    /// the sprite shader's. OpenGL's bottom-left origin is flipped, so the
+    /// first row of the returned data is the top of the rectangle, ready to
+    /// be saved with the `image` crate.
+    ///
+    /// While a `RenderTarget` is bound, the y-axis is not flipped, so the
+    /// rows are read in storage order, the same as its colour attachment's
+    /// `Texture::read_pixels`.
+    ///
+    /// Rows of RGBA8 pixels are always a multiple of 4 bytes, so the default
+    /// `PACK_ALIGNMENT` of 4 introduces no padding between rows.
+    pub fn read_framebuffer(&self, rect: Rect<u32>) -> errors::Result<Vec<u8>> {
+        let [x, y] = rect.pos;
+        let [width, height] = rect.size;
+        let row_len = PixelFormat::Rgba8.data_len(width, 1);
+        let mut data = vec![0; PixelFormat::Rgba8.data_len(width, height)];
+
+        // Convert from top-left to OpenGL's bottom-left origin.
+        let flip_y = self.flip_y.get();
+        let gl_y = if flip_y {
+            self.size.get().height as i32 - y as i32 - height as i32
+        } else {
+            y as i32
+        };
+
+        unsafe {
+            self.gl.read_pixels(
+                x as i32,
+                gl_y,
+                width as i32,
+                height as i32,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut data),
+            );
+            gl_error(&self.gl, ())?;
+        }
+
+        if flip_y {
+            utils::flip_rows(&mut data, row_len);
+        }
+
+        Ok(data)
+    }
+
+    /// Marks the start of a frame.
+    ///
+    /// Deallocates resources dropped since the last frame, so calling
+    /// `maintain` separately is not needed.
+    pub fn begin_frame(&self) -> errors::Result<()> {
+        debug_assert!(
+            !self.in_frame.get(),
+            "begin_frame called twice without end_frame"
+        );
+        self.in_frame.set(true);
+        self.upload_bytes.set(0);
+        self.maintain()
+    }
+
+    /// Marks the end of a frame, before the buffers are swapped.
+    ///
+    /// Deallocates resources dropped during the frame.
+    pub fn end_frame(&self) -> errors::Result<()> {
+        debug_assert!(self.in_frame.get(), "end_frame called without begin_frame");
+        self.in_frame.set(false);
+        self.maintain()
+    }
+
+    /// Deallocates the resources dropped since the last call.
+    ///
+    /// Resources are dropped on the thread owning the device, since they
+    /// can't be sent to another, so their deallocation is queued without
+    /// synchronisation. The device itself can't leave its thread either,
+    /// which keeps the OpenGL calls on the thread the context is current on.
+    pub fn maintain(&self) -> errors::Result<()> {
+        if self.context_lost.get() {
+            // Handles died with the context, so there is nothing to delete.
+            self.rx.clear();
+            return Ok(());
+        }
+
+        while let Some(resource) = self.rx.pop() {
             match resource {
                 Destroy::Texture(handle) => unsafe {
-                    println!("destroying texture");
+                    debug!("destroying texture {}", handle);
                     self.gl.delete_texture(handle);
                 },
                 Destroy::Shader(program) => unsafe {
-                    println!("destroying texture");
+                    debug!("destroying shader program {}", program);
                     self.gl.delete_program(program);
                 },
                 Destroy::VertexArray(handle) => unsafe {
-                    println!("destroying texture");
+                    debug!("destroying vertex array {}", handle);
                     self.gl.delete_vertex_array(handle);
                 },
+                Destroy::Sampler(handle) => unsafe {
+                    debug!("destroying sampler {}", handle);
+                    self.gl.delete_sampler(handle);
+                },
+                Destroy::Buffer(handle) => unsafe {
+                    debug!("destroying buffer {}", handle);
+                    self.gl.delete_buffer(handle);
+                },
+                Destroy::Framebuffer(handle) => unsafe {
+                    debug!("destroying framebuffer {}", handle);
+                    self.gl.delete_framebuffer(handle);
+                },
+                Destroy::Renderbuffer(handle) => unsafe {
+                    debug!("destroying renderbuffer {}", handle);
+                    self.gl.delete_renderbuffer(handle);
+                },
             }
         }
 
@@ -188,12 +1030,505 @@ impl GraphicDevice {
     }
 }
 
+/// Corners of the clip space quad drawn by `draw_fullscreen`, in the
+/// same order as a sprite's, with texture coordinates following the
+/// position, and the texture's first row at the top.
+fn fullscreen_quad_vertices() -> [Vertex; 4] {
+    const WHITE: [f32; 4] = [1.0; 4];
+    let corner = |x: f32, y: f32| Vertex {
+        position: [x, y],
+        uv: [x * 0.5 + 0.5, 0.5 - y * 0.5],
+        color: WHITE,
+    };
+
+    [
+        corner(-1.0, -1.0),
+        corner(1.0, -1.0),
+        corner(1.0, 1.0),
+        corner(-1.0, 1.0),
+    ]
+}
+
+fn fullscreen_quad_indices() -> [u16; 6] {
+    primitives::quad_indices_at(primitives::SPRITE_WINDING, 0)
+}
+
+/// Clamps each dimension to at least 1, and reports whether either was 0.
+fn clamp_viewport_size(size: PhysicalSize<u32>) -> (PhysicalSize<u32>, bool) {
+    let empty = size.width == 0 || size.height == 0;
+    (
+        PhysicalSize::new(size.width.max(1), size.height.max(1)),
+        empty,
+    )
+}
+
+/// Upper bound on the errors drained at once. A lost context may keep
+/// reporting `GL_CONTEXT_LOST`, which would otherwise loop forever.
+const MAX_DRAINED_ERRORS: usize = 32;
+
+fn drain_errors(mut take_error: impl FnMut() -> Option<u32>) -> Vec<u32> {
+    let mut errors = vec![];
+    while errors.len() < MAX_DRAINED_ERRORS {
+        match take_error() {
+            Some(code) => errors.push(code),
+            None => break,
+        }
+    }
+    errors
+}
+
+/// Entry points glow doesn't wrap, looked up by name with the
+/// context's function loader. `None` when the driver lacks them.
+#[derive(Default, Clone, Copy)]
+pub(crate) struct GlFunctions {
+    pub(crate) compressed_tex_image_2d: Option<CompressedTexImage2D>,
+    pub(crate) copy_image_sub_data: Option<CopyImageSubData>,
+    pub(crate) primitive_restart_index: Option<PrimitiveRestartIndex>,
+    pub(crate) get_float_v: Option<GetFloatv>,
+}
+
+impl GlFunctions {
+    /// # Safety
+    ///
+    /// The loader must return pointers to the named functions of the
+    /// current context, or null.
+    unsafe fn load<F>(mut loader: F) -> Self
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        Self {
+            compressed_tex_image_2d: load_function(&mut loader, "glCompressedTexImage2D"),
+            copy_image_sub_data: load_function(&mut loader, "glCopyImageSubData"),
+            primitive_restart_index: load_function(&mut loader, "glPrimitiveRestartIndex"),
+            get_float_v: load_function(&mut loader, "glGetFloatv"),
+        }
+    }
+}
+
+/// Function pointer of the given type, or `None` when the loader
+/// returns null.
+///
+/// # Safety
+///
+/// The type must be the function's signature.
+unsafe fn load_function<F, T>(loader: &mut F, name: &str) -> Option<T>
+where
+    F: FnMut(&str) -> *const c_void,
+{
+    let function = loader(name);
+    if function.is_null() {
+        None
+    } else {
+        Some(mem::transmute_copy::<*const c_void, T>(&function))
+    }
+}
+
+/// Signature of `glCompressedTexImage2D`.
+pub(crate) type CompressedTexImage2D = unsafe extern "system" fn(
+    target: u32,
+    level: i32,
+    internal_format: u32,
+    width: i32,
+    height: i32,
+    border: i32,
+    image_size: i32,
+    data: *const c_void,
+);
+
+/// Saves the device's scissor state onto the call stack, and restores
+/// it on drop.
+///
+/// Used so that drawing with a temporary scissor rectangle does not
+/// disrupt the caller's.
+pub(crate) struct ScissorSave<'a> {
+    device: &'a GraphicDevice,
+    scissor: Option<Rect<u32>>,
+}
+
+impl<'a> ScissorSave<'a> {
+    pub(crate) fn new(device: &'a GraphicDevice) -> Self {
+        Self {
+            device,
+            scissor: device.scissor(),
+        }
+    }
+}
+
+impl<'a> Drop for ScissorSave<'a> {
+    fn drop(&mut self) {
+        self.device.set_scissor(self.scissor);
+    }
+}
+
+/// Whether a `GL_VERSION` string belongs to an OpenGL ES context, which
+/// always starts with `OpenGL ES`.
+fn is_gles_version(version: &str) -> bool {
+    version.starts_with("OpenGL ES")
+}
+
+/// Scissor box `[x, y, width, height]` passed to OpenGL for a rectangle
+/// with a top-left origin, flipped within the height when given.
+fn scissor_box(rect: Rect<u32>, flip_height: Option<u32>) -> [i32; 4] {
+    let [x, y] = rect.pos;
+    let [width, height] = rect.size;
+    let gl_y = match flip_height {
+        Some(canvas_height) => canvas_height as i32 - y as i32 - height as i32,
+        None => y as i32,
+    };
+    [x as i32, gl_y, width as i32, height as i32]
+}
+
+/// Checks whether another upload fits the frame's budget, given the
+/// bytes already uploaded.
+fn check_upload_budget(used: usize, budget: Option<usize>) -> errors::Result<()> {
+    match budget {
+        Some(budget) if used >= budget => Err(errors::Error::UploadBudgetExceeded { used, budget }),
+        _ => Ok(()),
+    }
+}
+
+/// Signature of `glPrimitiveRestartIndex`.
+pub(crate) type PrimitiveRestartIndex = unsafe extern "system" fn(index: u32);
+
+/// Capability to enable for the primitive restart index.
+///
+/// Without `glPrimitiveRestartIndex`, the fixed index of OpenGL 4.3 is
+/// the only option, which is the maximum value of the `u16` indices.
+fn primitive_restart_capability(index: u32, has_restart_index: bool) -> errors::Result<u32> {
+    if has_restart_index {
+        Ok(glow::PRIMITIVE_RESTART)
+    } else if index == primitives::RESTART_INDEX as u32 {
+        Ok(glow::PRIMITIVE_RESTART_FIXED_INDEX)
+    } else {
+        Err(errors::Error::Unsupported("glPrimitiveRestartIndex"))
+    }
+}
+
+/// Signature of `glGetFloatv`.
+pub(crate) type GetFloatv = unsafe extern "system" fn(parameter: u32, data: *mut f32);
+
+/// Signature of `glCopyImageSubData`.
+pub(crate) type CopyImageSubData = unsafe extern "system" fn(
+    src_name: u32,
+    src_target: u32,
+    src_level: i32,
+    src_x: i32,
+    src_y: i32,
+    src_z: i32,
+    dst_name: u32,
+    dst_target: u32,
+    dst_level: i32,
+    dst_x: i32,
+    dst_y: i32,
+    dst_z: i32,
+    width: i32,
+    height: i32,
+    depth: i32,
+);
+
+impl Drop for GraphicDevice {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            let pending = self.rx.len();
+            if pending > 0 {
+                warn!(
+                    "Graphic device dropped with {} resources not deallocated. Call GraphicDevice::maintain before shutting down.",
+                    pending
+                );
+            }
+        }
+    }
+}
+
+/// Handle that dropped resources use to request deallocation
+/// from the device.
+///
+/// Holds a weak reference to the device's queue, so it is neither
+/// `Send` nor `Sync`, and resources holding it stay on the thread
+/// that owns the OpenGL context.
+#[derive(Clone)]
+pub(crate) struct DestroySender {
+    queue: Weak<RefCell<VecDeque<Destroy>>>,
+}
+
+impl DestroySender {
+    /// Creates a queue, and a sender pushing onto it.
+    pub(crate) fn channel() -> (Self, DestroyQueue) {
+        let queue = Rc::new(RefCell::new(VecDeque::new()));
+        let sender = Self {
+            queue: Rc::downgrade(&queue),
+        };
+
+        (sender, DestroyQueue { queue })
+    }
+
+    /// Requests deallocation of a resource.
+    ///
+    /// When the device was already dropped, the OpenGL context is gone
+    /// along with the resource, so the request is skipped.
+    pub(crate) fn send(&self, resource: Destroy) {
+        match self.queue.upgrade() {
+            Some(queue) => queue.borrow_mut().push_back(resource),
+            None => debug!(
+                "Graphic device dropped before resource {:?}. Skipping deallocation.",
+                resource
+            ),
+        }
+    }
+}
+
+/// Deallocation requests waiting for `GraphicDevice::maintain`, in
+/// the order the resources were dropped.
+pub(crate) struct DestroyQueue {
+    queue: Rc<RefCell<VecDeque<Destroy>>>,
+}
+
+impl DestroyQueue {
+    /// Takes the oldest request off the queue.
+    pub(crate) fn pop(&self) -> Option<Destroy> {
+        self.queue.borrow_mut().pop_front()
+    }
+
+    /// Discards every request.
+    fn clear(&self) {
+        self.queue.borrow_mut().clear();
+    }
+
+    /// Number of requests not yet taken off the queue.
+    pub(crate) fn len(&self) -> usize {
+        self.queue.borrow().len()
+    }
+}
+
+#[derive(Debug)]
 pub(crate) enum Destroy {
     Texture(u32),
     Shader(u32),
     VertexArray(u32),
+    Sampler(u32),
+    Buffer(u32),
+    Framebuffer(u32),
+    Renderbuffer(u32),
+}
+
+/// Blend equation applied when drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Blending disabled. Fragments replace the framebuffer contents.
+    Replace,
+    /// Traditional alpha blending, using the source alpha.
+    Alpha,
+    /// Source colour is multiplied by the device's blend colour, and
+    /// blended over the destination using the source alpha.
+    ///
+    /// Allows effects like darkening everything drawn in a frame without
+    /// touching geometry. See `GraphicDevice::set_blend_color`.
+    Constant,
+    /// Separate blend factors for the colour and alpha channels.
+    ///
+    /// Needed when drawing to a texture that is composited later, where
+    /// the destination alpha must stay correct. For example premultiplied
+    /// alpha over the whole target uses `One` and `OneMinusSrcAlpha` for
+    /// both colour and alpha. The colour and alpha equations are separate
+    /// as well.
+    Custom {
+        src_rgb: BlendFactor,
+        dst_rgb: BlendFactor,
+        src_alpha: BlendFactor,
+        dst_alpha: BlendFactor,
+        equation_rgb: BlendEquation,
+        equation_alpha: BlendEquation,
+    },
+}
+
+impl BlendMode {
+    /// Factors and equations to configure, or `None` when blending is disabled.
+    fn blend_state(self) -> Option<BlendState> {
+        let uniform = |src, dst| BlendState {
+            factors: [src, dst, src, dst],
+            equations: [BlendEquation::Add; 2],
+        };
+
+        match self {
+            BlendMode::Replace => None,
+            BlendMode::Alpha => Some(uniform(
+                BlendFactor::SrcAlpha,
+                BlendFactor::OneMinusSrcAlpha,
+            )),
+            BlendMode::Constant => Some(uniform(
+                BlendFactor::ConstantColor,
+                BlendFactor::OneMinusSrcAlpha,
+            )),
+            BlendMode::Custom {
+                src_rgb,
+                dst_rgb,
+                src_alpha,
+                dst_alpha,
+                equation_rgb,
+                equation_alpha,
+            } => Some(BlendState {
+                factors: [src_rgb, dst_rgb, src_alpha, dst_alpha],
+                equations: [equation_rgb, equation_alpha],
+            }),
+        }
+    }
+}
+
+/// Arguments of `glBlendFuncSeparate` and `glBlendEquationSeparate`.
+#[derive(Debug, PartialEq, Eq)]
+struct BlendState {
+    /// Source RGB, destination RGB, source alpha, destination alpha.
+    factors: [BlendFactor; 4],
+    /// RGB, alpha.
+    equations: [BlendEquation; 2],
+}
+
+/// Weight a source or destination colour is multiplied by when blending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstColor,
+    OneMinusDstColor,
+    DstAlpha,
+    OneMinusDstAlpha,
+    ConstantColor,
+    OneMinusConstantColor,
+}
+
+impl BlendFactor {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            BlendFactor::Zero => glow::ZERO,
+            BlendFactor::One => glow::ONE,
+            BlendFactor::SrcColor => glow::SRC_COLOR,
+            BlendFactor::OneMinusSrcColor => glow::ONE_MINUS_SRC_COLOR,
+            BlendFactor::SrcAlpha => glow::SRC_ALPHA,
+            BlendFactor::OneMinusSrcAlpha => glow::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::DstColor => glow::DST_COLOR,
+            BlendFactor::OneMinusDstColor => glow::ONE_MINUS_DST_COLOR,
+            BlendFactor::DstAlpha => glow::DST_ALPHA,
+            BlendFactor::OneMinusDstAlpha => glow::ONE_MINUS_DST_ALPHA,
+            BlendFactor::ConstantColor => glow::CONSTANT_COLOR,
+            BlendFactor::OneMinusConstantColor => glow::ONE_MINUS_CONSTANT_COLOR,
+        }
+    }
+}
+
+/// How the weighted source and destination colours are combined.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendEquation {
+    /// Source plus destination.
+    #[default]
+    Add,
+    /// Source minus destination.
+    Subtract,
+    /// Destination minus source.
+    ReverseSubtract,
+    /// Per channel minimum, ignoring the factors.
+    Min,
+    /// Per channel maximum, ignoring the factors.
+    Max,
+}
+
+impl BlendEquation {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            BlendEquation::Add => glow::FUNC_ADD,
+            BlendEquation::Subtract => glow::FUNC_SUBTRACT,
+            BlendEquation::ReverseSubtract => glow::FUNC_REVERSE_SUBTRACT,
+            BlendEquation::Min => glow::MIN,
+            BlendEquation::Max => glow::MAX,
+        }
+    }
+}
+
+/// Creates a `GraphicDevice` with non-default settings, started by
+/// `GraphicDevice::builder`.
+pub struct DeviceBuilder {
+    log_extensions: bool,
+}
+
+impl DeviceBuilder {
+    /// Log every extension supported by the driver at debug level.
+    ///
+    /// Off by default, since some drivers list hundreds. The extensions
+    /// are queried either way for `GraphicDevice::has_extension`.
+    pub fn log_extensions(mut self, log_extensions: bool) -> Self {
+        self.log_extensions = log_extensions;
+        self
+    }
+
+    pub fn build(self, gl: glow::Context) -> GraphicDevice {
+        GraphicDevice::with_builder(gl, GlFunctions::default(), self)
+    }
+
+    /// Like `GraphicDevice::from_loader_function`.
+    ///
+    /// # Safety
+    ///
+    /// The context the functions belong to must be current on this thread.
+    pub unsafe fn build_with_loader<F>(self, mut loader: F) -> GraphicDevice
+    where
+        F: FnMut(&str) -> *const c_void,
+    {
+        let gl = glow::Context::from_loader_function(&mut loader);
+        let fns = GlFunctions::load(loader);
+        GraphicDevice::with_builder(gl, fns, self)
+    }
+
+    /// Like `GraphicDevice::from_windowed_context`.
+    ///
+    /// # Safety
+    ///
+    /// The context must be current on this thread.
+    #[cfg(feature = "window")]
+    pub unsafe fn build_windowed(
+        self,
+        windowed_context: &glutin::WindowedContext<PossiblyCurrent>,
+    ) -> GraphicDevice {
+        GraphicDevice::from_windowed_context_with(windowed_context, self)
+    }
 }
 
+/// Collects the extension names into a set, passing the lines of
+/// a listing to `log` when enabled.
+fn collect_extensions<I, F>(names: I, log_extensions: bool, mut log: F) -> HashSet<String>
+where
+    I: IntoIterator<Item = String>,
+    F: FnMut(&str),
+{
+    let extensions: HashSet<String> = names.into_iter().collect();
+
+    if log_extensions {
+        log("Extensions:");
+        for ext in extensions.iter() {
+            log(&format!("  {}", ext));
+        }
+    }
+
+    extensions
+}
+
+/// Parses the major and minor version from a `GL_VERSION` string, like
+/// `4.6.0 NVIDIA 470.57` or `OpenGL ES 3.0 Mesa 21.0`.
+fn parse_gl_version(version: &str) -> Option<(u32, u32)> {
+    let start = version.find(|c: char| c.is_ascii_digit())?;
+    let mut numbers = version[start..]
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|number| number.parse::<u32>().ok());
+
+    let major = numbers.next()??;
+    let minor = numbers.next()??;
+    Some((major, minor))
+}
+
+#[derive(Debug, Clone)]
 pub struct OpenGlInfo {
     pub version: String,
     pub vendor: String,
@@ -210,3 +1545,181 @@ impl fmt::Display for OpenGlInfo {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_collect_extensions() {
+        let names = || {
+            vec![
+                "GL_ARB_copy_image".to_string(),
+                "GL_EXT_texture_filter_anisotropic".to_string(),
+            ]
+        };
+
+        let mut lines = vec![];
+        let extensions = collect_extensions(names(), false, |line| lines.push(line.to_string()));
+        assert!(extensions.contains("GL_ARB_copy_image"));
+        assert!(!extensions.contains("GL_ARB_bindless_texture"));
+        assert!(lines.is_empty(), "nothing is logged when disabled");
+
+        collect_extensions(names(), true, |line| lines.push(line.to_string()));
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "Extensions:");
+    }
+
+    #[test]
+    fn test_parse_gl_version() {
+        assert_eq!(parse_gl_version("4.6.0 NVIDIA 470.57.02"), Some((4, 6)));
+        assert_eq!(
+            parse_gl_version("3.3 (Core Profile) Mesa 21.2.6"),
+            Some((3, 3))
+        );
+        assert_eq!(parse_gl_version("OpenGL ES 3.0 Mesa 21.0.3"), Some((3, 0)));
+        assert_eq!(parse_gl_version("unknown"), None);
+
+        assert!(is_gles_version("OpenGL ES 3.0 Mesa 21.0.3"));
+        assert!(!is_gles_version("4.6.0 NVIDIA 470.57.02"));
+    }
+
+    #[test]
+    fn test_scissor_box() {
+        let rect = Rect {
+            pos: [10, 20],
+            size: [30, 40],
+        };
+        // Top edge at 20 from the top is 100 - 20 - 40 from the bottom.
+        assert_eq!(scissor_box(rect, Some(100)), [10, 40, 30, 40]);
+        assert_eq!(scissor_box(rect, None), [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn test_check_upload_budget() {
+        assert!(check_upload_budget(1 << 20, None).is_ok());
+        assert!(check_upload_budget(0, Some(1024)).is_ok());
+        // The last upload under the budget may overrun it.
+        assert!(check_upload_budget(1023, Some(1024)).is_ok());
+        assert!(matches!(
+            check_upload_budget(4096, Some(1024)),
+            Err(errors::Error::UploadBudgetExceeded {
+                used: 4096,
+                budget: 1024
+            })
+        ));
+        assert!(check_upload_budget(1024, Some(1024)).is_err());
+    }
+
+    #[test]
+    fn test_primitive_restart_capability() {
+        assert_eq!(
+            primitive_restart_capability(7, true).unwrap(),
+            glow::PRIMITIVE_RESTART
+        );
+        assert_eq!(
+            primitive_restart_capability(0xffff, false).unwrap(),
+            glow::PRIMITIVE_RESTART_FIXED_INDEX
+        );
+        assert!(matches!(
+            primitive_restart_capability(7, false),
+            Err(errors::Error::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_alignment() {
+        for alignment in &[1, 2, 4, 8] {
+            assert!(GraphicDevice::validate_alignment(*alignment).is_ok());
+        }
+
+        for alignment in &[0, 3, 16] {
+            assert!(matches!(
+                GraphicDevice::validate_alignment(*alignment),
+                Err(errors::Error::InvalidAlignment(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_clamp_viewport_size() {
+        assert_eq!(
+            clamp_viewport_size(PhysicalSize::new(0, 0)),
+            (PhysicalSize::new(1, 1), true)
+        );
+        assert_eq!(
+            clamp_viewport_size(PhysicalSize::new(800, 0)),
+            (PhysicalSize::new(800, 1), true)
+        );
+        assert_eq!(
+            clamp_viewport_size(PhysicalSize::new(800, 600)),
+            (PhysicalSize::new(800, 600), false)
+        );
+    }
+
+    #[test]
+    fn test_blend_state() {
+        assert_eq!(BlendMode::Replace.blend_state(), None);
+
+        let alpha = BlendMode::Alpha.blend_state().unwrap();
+        assert_eq!(alpha.factors[0].to_gl(), glow::SRC_ALPHA);
+        assert_eq!(alpha.factors[3].to_gl(), glow::ONE_MINUS_SRC_ALPHA);
+
+        // Premultiplied colour, with destination alpha kept for compositing.
+        let custom = BlendMode::Custom {
+            src_rgb: BlendFactor::One,
+            dst_rgb: BlendFactor::OneMinusSrcAlpha,
+            src_alpha: BlendFactor::One,
+            dst_alpha: BlendFactor::OneMinusDstAlpha,
+            equation_rgb: BlendEquation::Add,
+            equation_alpha: BlendEquation::Max,
+        }
+        .blend_state()
+        .unwrap();
+
+        let gl_factors: Vec<u32> = custom.factors.iter().map(|f| f.to_gl()).collect();
+        assert_eq!(
+            gl_factors,
+            [
+                glow::ONE,
+                glow::ONE_MINUS_SRC_ALPHA,
+                glow::ONE,
+                glow::ONE_MINUS_DST_ALPHA
+            ]
+        );
+        assert_eq!(custom.equations[0].to_gl(), glow::FUNC_ADD);
+        assert_eq!(custom.equations[1].to_gl(), glow::MAX);
+    }
+
+    #[test]
+    fn test_drain_errors() {
+        let mut queue = vec![glow::INVALID_ENUM, glow::INVALID_OPERATION].into_iter();
+        assert_eq!(
+            drain_errors(|| queue.next()),
+            [glow::INVALID_ENUM, glow::INVALID_OPERATION]
+        );
+        assert!(drain_errors(|| queue.next()).is_empty());
+
+        // A context that never stops reporting errors.
+        let errors = drain_errors(|| Some(glow::CONTEXT_LOST));
+        assert_eq!(errors.len(), MAX_DRAINED_ERRORS);
+    }
+
+    #[test]
+    fn test_destroy_pending() {
+        let (tx, rx) = DestroySender::channel();
+        tx.send(Destroy::Texture(1));
+        tx.clone().send(Destroy::Buffer(2));
+        assert_eq!(rx.len(), 2);
+
+        // Drained in the order the resources were dropped.
+        assert!(matches!(rx.pop(), Some(Destroy::Texture(1))));
+        assert!(matches!(rx.pop(), Some(Destroy::Buffer(2))));
+        assert!(rx.pop().is_none());
+        assert_eq!(rx.len(), 0);
+
+        // Requests after the device is gone are skipped.
+        drop(rx);
+        tx.send(Destroy::Texture(3));
+    }
+}
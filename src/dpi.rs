@@ -0,0 +1,73 @@
+//! Sizes in physical pixels.
+//!
+//! With the `window` feature this is glutin's type, so sizes can be passed
+//! between the window and the device. Without it, a type of the same shape
+//! stands in, so the crate can be used with a context created elsewhere.
+
+#[cfg(feature = "window")]
+pub use glutin::dpi::{PhysicalSize, Pixel};
+
+/// Numeric type a size can be expressed in.
+#[cfg(not(feature = "window"))]
+pub trait Pixel: Copy + Into<f64> {
+    fn from_f64(f: f64) -> Self;
+
+    fn cast<P: Pixel>(self) -> P {
+        P::from_f64(self.into())
+    }
+}
+
+#[cfg(not(feature = "window"))]
+macro_rules! impl_pixel {
+    ($($ty:ty),+) => {
+        $(
+            impl Pixel for $ty {
+                fn from_f64(f: f64) -> Self {
+                    f.round() as $ty
+                }
+            }
+        )+
+    };
+}
+
+#[cfg(not(feature = "window"))]
+impl_pixel!(u8, u16, u32, i8, i16, i32);
+
+#[cfg(not(feature = "window"))]
+impl Pixel for f32 {
+    fn from_f64(f: f64) -> Self {
+        f as f32
+    }
+}
+
+#[cfg(not(feature = "window"))]
+impl Pixel for f64 {
+    fn from_f64(f: f64) -> Self {
+        f
+    }
+}
+
+/// Width and height in physical pixels.
+#[cfg(not(feature = "window"))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PhysicalSize<P> {
+    pub width: P,
+    pub height: P,
+}
+
+#[cfg(not(feature = "window"))]
+impl<P> PhysicalSize<P> {
+    pub const fn new(width: P, height: P) -> Self {
+        PhysicalSize { width, height }
+    }
+}
+
+#[cfg(not(feature = "window"))]
+impl<P: Pixel> PhysicalSize<P> {
+    pub fn cast<X: Pixel>(&self) -> PhysicalSize<X> {
+        PhysicalSize {
+            width: self.width.cast(),
+            height: self.height.cast(),
+        }
+    }
+}
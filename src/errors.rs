@@ -1,4 +1,4 @@
-use crate::rect::Rect;
+use crate::{rect::Rect, shader::ShaderLog, texture::PixelFormat};
 use glow::HasContext;
 use std::fmt;
 
@@ -13,7 +13,81 @@ pub enum Error {
         expected: usize,
         actual: usize,
     },
+    /// Texture storage format isn't the one the operation uploads.
+    InvalidPixelFormat {
+        expected: PixelFormat,
+        actual: PixelFormat,
+    },
+    /// Texture length can't be split into the given number of equal parts.
+    UnevenSplit {
+        length: u32,
+        count: u32,
+    },
+    /// Tile map needs a tile for each of its cells.
+    InvalidTileCount {
+        expected: usize,
+        actual: usize,
+    },
+    /// Frame index is outside the sprite sheet's grid of columns and rows.
+    FrameOutOfBounds {
+        index: [u32; 2],
+        grid: [u32; 2],
+    },
+    /// Render target needs at least one colour attachment, and no more than the device supports.
+    InvalidAttachmentCount {
+        count: u32,
+        max: u32,
+    },
+    /// Streaming texture needs at least two textures to alternate between.
+    InvalidRingLength(usize),
+    /// Texture upload refused because the bytes uploaded this frame reached
+    /// the device's upload budget.
+    UploadBudgetExceeded {
+        used: usize,
+        budget: usize,
+    },
+    /// Framebuffer is not complete, with the status returned by the driver.
+    IncompleteFramebuffer(u32),
+    /// Texture unit is not below the number of units the device supports.
+    InvalidTextureUnit {
+        unit: u32,
+        max: u32,
+    },
+    /// Pixel row alignment must be 1, 2, 4 or 8.
+    InvalidAlignment(u8),
+    /// The device lacks a feature, like an extension, required by the operation.
+    Unsupported(&'static str),
+    /// A uniform or uniform block with the given name is not active in the shader program.
+    UniformNotFound(String),
+    /// Shader attribute is missing from the vertex buffer's layout, or read
+    /// with a different number of components than the buffer provides.
+    AttributeMismatch {
+        name: String,
+        location: u32,
+        components: i32,
+        buffer_components: Option<i32>,
+    },
+    /// Image file could not be read or decoded.
+    ImageLoad(String),
+    /// Image file could not be encoded or written.
+    ImageSave(String),
+    /// Window or OpenGL context could not be created.
+    ContextCreation(String),
+    /// Window buffers could not be swapped, like when the context was lost.
+    SwapBuffers(String),
+    /// Shader stages failed to compile, or the program failed to link.
+    /// Holds the info log of each failure.
+    ShaderCompile(Vec<ShaderLog>),
+    /// Shader source includes a file the resolver could not supply.
+    IncludeNotFound(String),
+    /// Shader source includes itself, directly or through other files.
+    IncludeCycle(Vec<String>),
     OpenGl(u32),
+    /// OpenGL error code, with the operation that raised it.
+    OpenGlAt {
+        code: u32,
+        op: &'static str,
+    },
     OpenGlMessage(String),
 }
 
@@ -27,7 +101,35 @@ impl fmt::Display for Error {
             ),
             Error::InvalidSubTexture { source, target } => write!(f, "Sub-texture rectangle {} does not fit in {}.", target, source),
             Error::InvalidImageData { expected, actual } => write!(f, "Image data does not match texture storage size. Expected {} bytes. Actual {} bytes.", expected, actual),
+            Error::InvalidPixelFormat { expected, actual } => write!(f, "Texture storage format {:?} does not match the uploaded format {:?}.", actual, expected),
+            Error::UnevenSplit { length, count } => write!(f, "Texture length {} can't be split into {} equal parts.", length, count),
+            Error::InvalidTileCount { expected, actual } => write!(f, "Expected {} tiles, one for each cell of the map, got {}.", expected, actual),
+            Error::FrameOutOfBounds { index, grid } => write!(f, "Frame ({}, {}) is outside the sprite sheet grid of {} columns and {} rows.", index[0], index[1], grid[0], grid[1]),
+            Error::InvalidAttachmentCount { count, max } => write!(f, "Invalid number of colour attachments {}. Must be between 1 and {}.", count, max),
+            Error::InvalidRingLength(count) => write!(f, "Streaming texture needs at least 2 textures, got {}.", count),
+            Error::UploadBudgetExceeded { used, budget } => write!(f, "Texture upload budget of {} bytes per frame exceeded, {} bytes uploaded.", budget, used),
+            Error::IncompleteFramebuffer(status) => write!(f, "Framebuffer is incomplete: 0x{:x}", status),
+            Error::InvalidTextureUnit { unit, max } => write!(f, "Invalid texture unit {}. The device supports {} units.", unit, max),
+            Error::InvalidAlignment(alignment) => write!(f, "Invalid pixel alignment {}. Must be 1, 2, 4 or 8.", alignment),
+            Error::Unsupported(feature) => write!(f, "Unsupported by graphics device: {}", feature),
+            Error::UniformNotFound(name) => write!(f, "Uniform '{}' not found in shader program.", name),
+            Error::AttributeMismatch { name, location, components, buffer_components: None } => write!(f, "Shader attribute '{}' at location {} with {} components is not provided by the vertex buffer.", name, location, components),
+            Error::AttributeMismatch { name, location, components, buffer_components: Some(buffer_components) } => write!(f, "Shader attribute '{}' at location {} has {} components, but the vertex buffer provides {}.", name, location, components, buffer_components),
+            Error::ImageLoad(error_msg) => write!(f, "Failed to load image: {}", error_msg),
+            Error::ImageSave(error_msg) => write!(f, "Failed to save image: {}", error_msg),
+            Error::ContextCreation(error_msg) => write!(f, "Failed to create OpenGL context: {}", error_msg),
+            Error::SwapBuffers(error_msg) => write!(f, "Failed to swap buffers: {}", error_msg),
+            Error::ShaderCompile(logs) => {
+                write!(f, "Failed to build shader program.")?;
+                for ShaderLog { stage, log } in logs {
+                    write!(f, "\n{}:\n{}", stage, log.trim_end())?;
+                }
+                Ok(())
+            }
+            Error::IncludeNotFound(name) => write!(f, "Shader include \"{}\" not found.", name),
+            Error::IncludeCycle(chain) => write!(f, "Shader includes form a cycle: {}", chain.join(" -> ")),
             Error::OpenGl(error_code) => write!(f, "OpenGL Error: 0x{:x}", error_code),
+            Error::OpenGlAt { code, op } => write!(f, "OpenGL Error: 0x{:x} in {}", code, op),
             Error::OpenGlMessage(error_msg) => write!(f, "OpenGL Error: {}", error_msg),
         }
     }
@@ -37,6 +139,11 @@ impl std::error::Error for Error {}
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Panics if OpenGL has recorded an error.
+///
+/// # Safety
+///
+/// The OpenGL context of `gl` must be current on the calling thread.
 pub unsafe fn assert_gl(gl: &glow::Context) {
     let gl_err = gl.get_error();
     if gl_err != glow::NO_ERROR {
@@ -44,6 +151,11 @@ pub unsafe fn assert_gl(gl: &glow::Context) {
     }
 }
 
+/// Panics if OpenGL has recorded an error, in debug builds only.
+///
+/// # Safety
+///
+/// The OpenGL context of `gl` must be current on the calling thread.
 #[inline(always)]
 pub unsafe fn debug_assert_gl<T>(gl: &glow::Context, value: T) -> T {
     #[cfg(debug_assertions)]
@@ -57,6 +169,30 @@ pub unsafe fn debug_assert_gl<T>(gl: &glow::Context, value: T) -> T {
     value
 }
 
+/// Like `debug_assert_gl`, but names the operation in the panic message.
+///
+/// # Safety
+///
+/// The OpenGL context of `gl` must be current on the calling thread.
+#[inline(always)]
+pub unsafe fn debug_assert_gl_at<T>(gl: &glow::Context, op: &'static str, value: T) -> T {
+    #[cfg(debug_assertions)]
+    {
+        let gl_err = gl.get_error();
+        if gl_err != glow::NO_ERROR {
+            panic!("{}", Error::OpenGlAt { code: gl_err, op });
+        }
+    }
+
+    value
+}
+
+/// Converts the result of a glow call, and any error OpenGL has
+/// recorded, into an `errors::Result`.
+///
+/// # Safety
+///
+/// The OpenGL context of `gl` must be current on the calling thread.
 #[inline(always)]
 pub unsafe fn gl_result<T>(
     gl: &glow::Context,
@@ -73,6 +209,11 @@ pub unsafe fn gl_result<T>(
     }
 }
 
+/// Returns the value, or the error OpenGL has recorded.
+///
+/// # Safety
+///
+/// The OpenGL context of `gl` must be current on the calling thread.
 #[inline(always)]
 pub unsafe fn gl_error<T>(gl: &glow::Context, value: T) -> crate::errors::Result<T> {
     let gl_err = gl.get_error();
@@ -82,3 +223,38 @@ pub unsafe fn gl_error<T>(gl: &glow::Context, value: T) -> crate::errors::Result
         Ok(value)
     }
 }
+
+/// Like `gl_error`, but labels an error code with the operation that raised it.
+///
+/// # Safety
+///
+/// The OpenGL context of `gl` must be current on the calling thread.
+#[inline(always)]
+pub unsafe fn gl_error_at<T>(
+    gl: &glow::Context,
+    op: &'static str,
+    value: T,
+) -> crate::errors::Result<T> {
+    let gl_err = gl.get_error();
+    if gl_err != glow::NO_ERROR {
+        Err(Error::OpenGlAt { code: gl_err, op })
+    } else {
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_opengl_at_display() {
+        let err = Error::OpenGlAt {
+            code: glow::INVALID_OPERATION,
+            op: "tex_sub_image_2d",
+        };
+        let message = err.to_string();
+        assert!(message.contains("tex_sub_image_2d"));
+        assert!(message.contains("0x502"));
+    }
+}
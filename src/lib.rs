@@ -1,12 +1,35 @@
+#[macro_use]
+mod logging;
+
+pub mod color;
 pub mod device;
+pub mod dpi;
 mod draw;
 pub mod errors;
 mod marker;
+pub mod math;
+pub mod post;
+pub mod primitives;
 pub mod rect;
+pub mod render_target;
+pub mod renderer;
+pub mod retained_batch;
+pub mod sampler;
 pub mod shader;
+pub mod shape;
 pub mod sprite;
 pub mod sprite_batch;
+pub mod sprite_sheet;
+pub mod streaming_texture;
 pub mod texture;
+pub mod texture_array;
 pub mod texture_pack;
+pub mod tile_map;
+pub mod uniform;
 pub mod utils;
-mod vertex;
+pub mod vertex;
+#[cfg(feature = "window")]
+mod window;
+
+#[cfg(feature = "window")]
+pub use window::init_window;
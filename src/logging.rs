@@ -0,0 +1,41 @@
+//! Internal logging macros.
+//!
+//! Messages are forwarded to the `log` crate when the `log` feature is
+//! enabled, letting the embedding application control verbosity via its
+//! logger. Without the feature, messages are discarded.
+
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::debug!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! info {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::info!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+macro_rules! error {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::error!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
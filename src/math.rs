@@ -0,0 +1,415 @@
+//! Small 2D vector and transform types.
+//!
+//! Interoperates with the `[f32; N]` arrays used across the crate
+//! through `From` conversions.
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+/// Two dimensional vector, used both for points and directions.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+    pub const ONE: Vec2 = Vec2 { x: 1.0, y: 1.0 };
+
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn dot(self, other: Vec2) -> f32 {
+        self.x * other.x + self.y * other.y
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Unit vector pointing in the same direction.
+    ///
+    /// Returns `None` for the zero vector, which has no direction.
+    pub fn normalize(self) -> Option<Vec2> {
+        let length = self.length();
+        if length > 0.0 {
+            Some(self * (1.0 / length))
+        } else {
+            None
+        }
+    }
+
+    pub fn to_array(self) -> [f32; 2] {
+        [self.x, self.y]
+    }
+}
+
+impl From<[f32; 2]> for Vec2 {
+    fn from([x, y]: [f32; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<Vec2> for [f32; 2] {
+    fn from(v: Vec2) -> Self {
+        v.to_array()
+    }
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Vec2) -> Self::Output {
+        Vec2::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl AddAssign for Vec2 {
+    fn add_assign(&mut self, rhs: Vec2) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Vec2 {
+    type Output = Vec2;
+
+    fn sub(self, rhs: Vec2) -> Self::Output {
+        Vec2::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl SubAssign for Vec2 {
+    fn sub_assign(&mut self, rhs: Vec2) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<f32> for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Vec2::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+impl Neg for Vec2 {
+    type Output = Vec2;
+
+    fn neg(self) -> Self::Output {
+        Vec2::new(-self.x, -self.y)
+    }
+}
+
+/// 2D affine transform, a linear part followed by a translation.
+///
+/// Transforms compose right to left, like matrices: `a * b` applies
+/// `b` first, then `a`. Rotations are clockwise in screen space, where
+/// the y axis points down, matching sprite rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine2 {
+    /// Image of the x axis.
+    pub x_axis: Vec2,
+    /// Image of the y axis.
+    pub y_axis: Vec2,
+    pub translation: Vec2,
+}
+
+impl Affine2 {
+    pub const IDENTITY: Affine2 = Affine2 {
+        x_axis: Vec2::new(1.0, 0.0),
+        y_axis: Vec2::new(0.0, 1.0),
+        translation: Vec2::ZERO,
+    };
+
+    pub fn from_translation(translation: impl Into<Vec2>) -> Self {
+        Self {
+            translation: translation.into(),
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_rotation(radians: f32) -> Self {
+        let (sin, cos) = radians.sin_cos();
+        Self {
+            x_axis: Vec2::new(cos, sin),
+            y_axis: Vec2::new(-sin, cos),
+            translation: Vec2::ZERO,
+        }
+    }
+
+    pub fn from_scale(scale: impl Into<Vec2>) -> Self {
+        let scale = scale.into();
+        Self {
+            x_axis: Vec2::new(scale.x, 0.0),
+            y_axis: Vec2::new(0.0, scale.y),
+            translation: Vec2::ZERO,
+        }
+    }
+
+    /// Scale, then rotate, then translate. The usual order for
+    /// placing an object in the world.
+    pub fn from_scale_rotation_translation(
+        scale: impl Into<Vec2>,
+        radians: f32,
+        translation: impl Into<Vec2>,
+    ) -> Self {
+        Self::from_translation(translation) * Self::from_rotation(radians) * Self::from_scale(scale)
+    }
+
+    /// Apply a translation after this transform.
+    pub fn translate(self, translation: impl Into<Vec2>) -> Self {
+        Self::from_translation(translation) * self
+    }
+
+    /// Apply a rotation around the origin after this transform.
+    pub fn rotate(self, radians: f32) -> Self {
+        Self::from_rotation(radians) * self
+    }
+
+    /// Apply a scale relative to the origin after this transform.
+    pub fn scale(self, scale: impl Into<Vec2>) -> Self {
+        Self::from_scale(scale) * self
+    }
+
+    /// Transform a position, including the translation.
+    pub fn transform_point(&self, point: impl Into<Vec2>) -> Vec2 {
+        self.transform_vector(point) + self.translation
+    }
+
+    /// Transform a direction, ignoring the translation.
+    pub fn transform_vector(&self, vector: impl Into<Vec2>) -> Vec2 {
+        let v = vector.into();
+        self.x_axis * v.x + self.y_axis * v.y
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.x_axis.x * self.y_axis.y - self.y_axis.x * self.x_axis.y
+    }
+
+    /// Transform undoing this one.
+    ///
+    /// Returns `None` when the transform collapses space onto a line
+    /// or point, like a zero scale, and can't be undone.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let x_axis = Vec2::new(self.y_axis.y, -self.x_axis.y) * inv_det;
+        let y_axis = Vec2::new(-self.y_axis.x, self.x_axis.x) * inv_det;
+        let linear = Self {
+            x_axis,
+            y_axis,
+            translation: Vec2::ZERO,
+        };
+
+        Some(Self {
+            translation: -linear.transform_vector(self.translation),
+            ..linear
+        })
+    }
+}
+
+impl Default for Affine2 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Affine2 {
+    type Output = Affine2;
+
+    fn mul(self, rhs: Affine2) -> Self::Output {
+        Affine2 {
+            x_axis: self.transform_vector(rhs.x_axis),
+            y_axis: self.transform_vector(rhs.y_axis),
+            translation: self.transform_point(rhs.translation),
+        }
+    }
+}
+
+/// 3x3 matrix in column-major order, the layout expected by
+/// `mat3` shader uniforms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub cols: [[f32; 3]; 3],
+}
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3 {
+        cols: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub fn from_cols_array(cols: [f32; 9]) -> Self {
+        let mut m = Self::IDENTITY;
+        for (i, value) in cols.iter().enumerate() {
+            m.cols[i / 3][i % 3] = *value;
+        }
+        m
+    }
+
+    /// Flattened columns, for uploading with `uniform_matrix_3_f32_slice`.
+    pub fn to_cols_array(&self) -> [f32; 9] {
+        let mut array = [0.0; 9];
+        for (i, value) in array.iter_mut().enumerate() {
+            *value = self.cols[i / 3][i % 3];
+        }
+        array
+    }
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl From<Affine2> for Mat3 {
+    fn from(affine: Affine2) -> Self {
+        let Affine2 {
+            x_axis,
+            y_axis,
+            translation,
+        } = affine;
+        Self {
+            cols: [
+                [x_axis.x, x_axis.y, 0.0],
+                [y_axis.x, y_axis.y, 0.0],
+                [translation.x, translation.y, 1.0],
+            ],
+        }
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Self::Output {
+        let mut cols = [[0.0; 3]; 3];
+        for (c, col) in cols.iter_mut().enumerate() {
+            for (r, value) in col.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.cols[k][r] * rhs.cols[c][k]).sum();
+            }
+        }
+        Mat3 { cols }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn assert_near(a: Vec2, b: Vec2) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_affine_near(a: Affine2, b: Affine2) {
+        assert_near(a.x_axis, b.x_axis);
+        assert_near(a.y_axis, b.y_axis);
+        assert_near(a.translation, b.translation);
+    }
+
+    #[test]
+    fn test_vec2() {
+        let v = Vec2::from([3.0, 4.0]);
+        assert_eq!(v.length(), 5.0);
+        assert_eq!(v + Vec2::ONE, Vec2::new(4.0, 5.0));
+        assert_eq!(v - Vec2::ONE, Vec2::new(2.0, 3.0));
+        assert_eq!(-v * 2.0, Vec2::new(-6.0, -8.0));
+        assert_eq!(v.dot(Vec2::new(1.0, 0.0)), 3.0);
+        assert_eq!(v.normalize(), Some(Vec2::new(0.6, 0.8)));
+        assert_eq!(Vec2::ZERO.normalize(), None);
+        assert_eq!(<[f32; 2]>::from(v), [3.0, 4.0]);
+    }
+
+    #[test]
+    fn test_rotation_is_clockwise_on_screen() {
+        // With y pointing down, a quarter turn takes right to down.
+        let rotation = Affine2::from_rotation(FRAC_PI_2);
+        assert_near(rotation.transform_point([1.0, 0.0]), Vec2::new(0.0, 1.0));
+        assert_near(rotation.transform_point([0.0, 1.0]), Vec2::new(-1.0, 0.0));
+    }
+
+    #[test]
+    fn test_composition_order() {
+        let translate = Affine2::from_translation([10.0, 0.0]);
+        let scale = Affine2::from_scale([2.0, 2.0]);
+
+        // Scale first, then translate.
+        assert_near(
+            (translate * scale).transform_point([1.0, 1.0]),
+            Vec2::new(12.0, 2.0),
+        );
+        // Translate first, then scale.
+        assert_near(
+            (scale * translate).transform_point([1.0, 1.0]),
+            Vec2::new(22.0, 2.0),
+        );
+
+        // Chained builders apply in reading order.
+        let chained = Affine2::IDENTITY
+            .scale([2.0, 2.0])
+            .rotate(FRAC_PI_2)
+            .translate([10.0, 0.0]);
+        let composed = Affine2::from_scale_rotation_translation([2.0, 2.0], FRAC_PI_2, [10.0, 0.0]);
+        assert_affine_near(chained, composed);
+        assert_near(chained.transform_point([1.0, 0.0]), Vec2::new(10.0, 2.0));
+
+        // Composition is associative.
+        let rotate = Affine2::from_rotation(0.3);
+        assert_affine_near((translate * rotate) * scale, translate * (rotate * scale));
+    }
+
+    #[test]
+    fn test_transform_vector_ignores_translation() {
+        let transform = Affine2::from_translation([5.0, 5.0]).scale([3.0, 1.0]);
+        assert_near(transform.transform_vector([1.0, 1.0]), Vec2::new(3.0, 1.0));
+        assert_near(transform.transform_point([1.0, 1.0]), Vec2::new(18.0, 6.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Affine2::from_scale_rotation_translation([2.0, 0.5], 0.7, [-30.0, 12.5]);
+        let inverse = transform.inverse().unwrap();
+
+        assert_affine_near(transform * inverse, Affine2::IDENTITY);
+        assert_affine_near(inverse * transform, Affine2::IDENTITY);
+
+        let point = Vec2::new(3.0, -8.0);
+        assert_near(
+            inverse.transform_point(transform.transform_point(point)),
+            point,
+        );
+
+        assert_eq!(Affine2::from_scale([0.0, 1.0]).inverse(), None);
+        assert_eq!(Affine2::IDENTITY.inverse(), Some(Affine2::IDENTITY));
+    }
+
+    #[test]
+    fn test_mat3() {
+        let a = Affine2::from_scale_rotation_translation([2.0, 3.0], 0.4, [7.0, -1.0]);
+        let b = Affine2::from_rotation(-1.2).translate([0.5, 0.5]);
+
+        // Matrix product agrees with affine composition.
+        let product = Mat3::from(a) * Mat3::from(b);
+        let expected = Mat3::from(a * b);
+        for (p, e) in product
+            .to_cols_array()
+            .iter()
+            .zip(expected.to_cols_array().iter())
+        {
+            assert!((p - e).abs() < 1e-5);
+        }
+
+        let translation = Mat3::from(Affine2::from_translation([4.0, 5.0]));
+        assert_eq!(&translation.to_cols_array()[6..], &[4.0, 5.0, 1.0]);
+        assert_eq!(
+            Mat3::from_cols_array(translation.to_cols_array()),
+            translation
+        );
+        assert_eq!(Mat3::IDENTITY * translation, translation);
+    }
+}
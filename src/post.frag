@@ -0,0 +1,22 @@
+#version 330 core
+
+precision highp float;
+
+uniform sampler2D u_texture;
+
+// Display gamma. 2.2 leaves the colour unchanged.
+uniform float u_gamma;
+
+// Colour multiplier. 1.0 leaves the colour unchanged.
+uniform float u_brightness;
+
+in vec2 v_TexCoord;
+
+out vec4 Color;
+
+void main() {
+    vec4 color = texture(u_texture, v_TexCoord);
+    vec3 rgb = pow(color.rgb * u_brightness, vec3(2.2 / u_gamma));
+
+    Color = vec4(rgb, color.a);
+}
@@ -0,0 +1,115 @@
+//! Full-screen post-processing.
+use crate::{
+    device::{Destroy, DestroySender, GraphicDevice},
+    errors::{self, debug_assert_gl_at, gl_result},
+    shader::Shader,
+    texture::Texture,
+};
+use glow::HasContext;
+
+/// Applies gamma and brightness adjustment to a texture, usually the
+/// colour output of an off-screen render, while drawing it over the
+/// whole window.
+pub struct PostProcess {
+    shader: Shader,
+    /// Empty vertex array. The full-screen triangle is generated
+    /// in the vertex shader, but core profile still requires a
+    /// vertex array to be bound when drawing.
+    vertex_array: u32,
+    gamma: f32,
+    brightness: f32,
+    destroy: DestroySender,
+}
+
+impl PostProcess {
+    /// Gamma that leaves colours unchanged.
+    pub const DEFAULT_GAMMA: f32 = 2.2;
+
+    /// Brightness that leaves colours unchanged.
+    pub const DEFAULT_BRIGHTNESS: f32 = 1.0;
+
+    pub fn new(device: &GraphicDevice) -> errors::Result<Self> {
+        let shader =
+            Shader::try_from_source(device, include_str!("post.vert"), include_str!("post.frag"))?;
+        let vertex_array = unsafe { gl_result(&device.gl, device.gl.create_vertex_array())? };
+
+        Ok(Self {
+            shader,
+            vertex_array,
+            gamma: Self::DEFAULT_GAMMA,
+            brightness: Self::DEFAULT_BRIGHTNESS,
+            destroy: device.destroy_sender(),
+        })
+    }
+
+    pub fn set_gamma(&mut self, gamma: f32) {
+        self.gamma = gamma;
+    }
+
+    pub fn gamma(&self) -> f32 {
+        self.gamma
+    }
+
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness;
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    /// Draw the texture over the whole default framebuffer, applying
+    /// the gamma and brightness.
+    pub fn draw(&self, device: &GraphicDevice, texture: &Texture) {
+        if !device.is_context_valid() || device.is_viewport_empty() {
+            return;
+        }
+
+        let size = device.get_viewport_size().cast::<i32>();
+        let program = self.shader.program;
+
+        unsafe {
+            device.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            device.gl.viewport(0, 0, size.width, size.height);
+
+            device.gl.use_program(Some(program));
+            device.gl.uniform_1_i32(
+                device
+                    .gl
+                    .get_uniform_location(program, "u_texture")
+                    .as_ref(),
+                0,
+            );
+            device.gl.uniform_1_f32(
+                device.gl.get_uniform_location(program, "u_gamma").as_ref(),
+                self.gamma,
+            );
+            device.gl.uniform_1_f32(
+                device
+                    .gl
+                    .get_uniform_location(program, "u_brightness")
+                    .as_ref(),
+                self.brightness,
+            );
+
+            device.gl.active_texture(glow::TEXTURE0);
+            device
+                .gl
+                .bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+            device.gl.bind_vertex_array(Some(self.vertex_array));
+
+            device.gl.draw_arrays(glow::TRIANGLES, 0, 3);
+            debug_assert_gl_at(&device.gl, "draw_arrays", ());
+
+            device.gl.bind_vertex_array(None);
+            device.gl.bind_texture(glow::TEXTURE_2D, None);
+            device.gl.use_program(None);
+        }
+    }
+}
+
+impl Drop for PostProcess {
+    fn drop(&mut self) {
+        self.destroy.send(Destroy::VertexArray(self.vertex_array));
+    }
+}
@@ -0,0 +1,21 @@
+#version 330 core
+
+// Full-screen triangle generated from the vertex index, so
+// no vertex buffer is needed. The triangle overshoots the
+// screen, which is clipped, leaving the screen covered.
+const vec2 verts[3] = vec2[3](
+    vec2(-1.0, -1.0),
+    vec2(3.0, -1.0),
+    vec2(-1.0, 3.0)
+);
+
+out vec2 v_TexCoord;
+
+void main() {
+    vec2 pos = verts[gl_VertexID];
+    gl_Position = vec4(pos, 0.0, 1.0);
+
+    // Convert from clip space -1->+1 to texture space 0->1, with
+    // the first texture row at the top of the screen.
+    v_TexCoord = vec2(pos.x * 0.5 + 0.5, 0.5 - pos.y * 0.5);
+}
@@ -0,0 +1,204 @@
+//! Primitive types, and index orders for building them.
+//!
+//! Quads are described by four corners, in the order
+//! top-left, top-right, bottom-right, bottom-left.
+
+/// How vertices are assembled into shapes when drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    /// Every three vertices form a separate triangle.
+    #[default]
+    Triangles,
+    /// Every two vertices form a separate line.
+    Lines,
+    /// Each vertex after the first two forms a triangle
+    /// with the two vertices before it.
+    TriangleStrip,
+    /// Each vertex is a single point.
+    Points,
+}
+
+impl Primitive {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            Primitive::Triangles => glow::TRIANGLES,
+            Primitive::Lines => glow::LINES,
+            Primitive::TriangleStrip => glow::TRIANGLE_STRIP,
+            Primitive::Points => glow::POINTS,
+        }
+    }
+}
+
+/// Order in which the vertices of a triangle are listed.
+///
+/// OpenGL considers the winding to determine which side of a
+/// triangle is the front face.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Winding {
+    /// Counter-clockwise, the OpenGL default.
+    #[default]
+    Ccw,
+    /// Clockwise.
+    Cw,
+}
+
+impl Winding {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            Winding::Ccw => glow::CCW,
+            Winding::Cw => glow::CW,
+        }
+    }
+
+    /// Indices of the two triangles making up a quad.
+    pub fn quad_indices(self) -> [u16; 6] {
+        match self {
+            Winding::Ccw => quad_indices_ccw(),
+            Winding::Cw => quad_indices_cw(),
+        }
+    }
+}
+
+/// Index that ends a primitive when primitive restart is enabled with
+/// `GraphicDevice::set_primitive_restart`.
+///
+/// The maximum value of the `u16` index type, which is also the fixed
+/// restart index of OpenGL 4.3.
+pub const RESTART_INDEX: u16 = u16::MAX;
+
+/// Indices drawing a grid of quads as one triangle strip per row,
+/// separated by `RESTART_INDEX`.
+///
+/// The grid's vertices are stored row by row, with `columns + 1` vertices
+/// per row and `rows + 1` rows, starting at the top. Triangles are wound
+/// like `SPRITE_WINDING`.
+///
+/// Draw with `Primitive::TriangleStrip`, and primitive restart enabled
+/// with `RESTART_INDEX`. Without restart the rows are joined by
+/// stray triangles.
+///
+/// # Panics
+///
+/// When the grid has too many vertices for `u16` indices.
+pub fn grid_strip_indices(columns: u16, rows: u16) -> Vec<u16> {
+    let row_len = columns as u32 + 1;
+    assert!(
+        row_len * (rows as u32 + 1) <= RESTART_INDEX as u32,
+        "Grid of {}x{} quads has too many vertices",
+        columns,
+        rows
+    );
+
+    let row_len = row_len as u16;
+    let mut indices = Vec::with_capacity(rows as usize * (row_len as usize * 2 + 1));
+    for row in 0..rows {
+        if row > 0 {
+            indices.push(RESTART_INDEX);
+        }
+        let top = row * row_len;
+        for column in 0..row_len {
+            // Bottom first keeps the triangles counter-clockwise.
+            indices.push(top + row_len + column);
+            indices.push(top + column);
+        }
+    }
+    indices
+}
+
+/// Winding of the quads built by `sprite::Sprite` and `SpriteBatch`.
+///
+/// The indices are listed counter-clockwise in the sprite's coordinates,
+/// where y points down. The sprite shader flips the y-axis for the window,
+/// so whether they are front facing depends on the render target. Face
+/// culling is left disabled, and both sprite paths share this definition.
+pub const SPRITE_WINDING: Winding = Winding::Ccw;
+
+/// Indices of a quad stored after `first_vertex` other vertices, as in
+/// a buffer of consecutive quads.
+pub fn quad_indices_at(winding: Winding, first_vertex: u16) -> [u16; 6] {
+    let mut indices = winding.quad_indices();
+    for index in indices.iter_mut() {
+        *index += first_vertex;
+    }
+    indices
+}
+
+/// Quad indices with counter-clockwise winding.
+///
+/// Note that the sprite shader flips the y-axis, so in screen space
+/// with y pointing down these triangles appear clockwise.
+pub fn quad_indices_ccw() -> [u16; 6] {
+    [0, 1, 2, 0, 2, 3]
+}
+
+/// Quad indices with clockwise winding.
+pub fn quad_indices_cw() -> [u16; 6] {
+    [0, 2, 1, 0, 3, 2]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_primitive_to_gl() {
+        assert_eq!(Primitive::default().to_gl(), glow::TRIANGLES);
+        assert_eq!(Primitive::Lines.to_gl(), glow::LINES);
+        assert_eq!(Primitive::TriangleStrip.to_gl(), glow::TRIANGLE_STRIP);
+        assert_eq!(Primitive::Points.to_gl(), glow::POINTS);
+    }
+
+    #[test]
+    fn test_grid_strip_indices() {
+        // 2x2 quads, with vertices:
+        // 0 1 2
+        // 3 4 5
+        // 6 7 8
+        let indices = grid_strip_indices(2, 2);
+        assert_eq!(indices, [3, 0, 4, 1, 5, 2, RESTART_INDEX, 6, 3, 7, 4, 8, 5]);
+
+        // Assemble the strips like OpenGL with restart enabled.
+        let mut triangles = vec![];
+        for strip in indices.split(|index| *index == RESTART_INDEX) {
+            for window in strip.windows(3) {
+                triangles.push([window[0], window[1], window[2]]);
+            }
+        }
+
+        // The rows don't connect, so every triangle stays within a row of quads.
+        assert_eq!(triangles.len(), 8);
+        for triangle in &triangles {
+            let row = |index: u16| index / 3;
+            let top = triangle.iter().map(|index| row(*index)).min().unwrap();
+            let bottom = triangle.iter().map(|index| row(*index)).max().unwrap();
+            assert_eq!(bottom - top, 1, "{:?} spans rows", triangle);
+        }
+
+        // Without restart, the strip would join the rows.
+        let joined: Vec<u16> = indices
+            .into_iter()
+            .filter(|i| *i != RESTART_INDEX)
+            .collect();
+        assert!(joined.windows(3).any(|w| w.contains(&2) && w.contains(&6)));
+    }
+
+    #[test]
+    fn test_quad_indices() {
+        assert_eq!(quad_indices_ccw(), [0, 1, 2, 0, 2, 3]);
+        assert_eq!(quad_indices_cw(), [0, 2, 1, 0, 3, 2]);
+
+        // Each triangle is the same set of vertices, listed in reverse.
+        let ccw = quad_indices_ccw();
+        let cw = quad_indices_cw();
+        for (a, b) in ccw.chunks(3).zip(cw.chunks(3)) {
+            assert_eq!(a[0], b[0]);
+            assert_eq!(a[1], b[2]);
+            assert_eq!(a[2], b[1]);
+        }
+
+        assert_eq!(quad_indices_at(Winding::Ccw, 8), [8, 9, 10, 8, 10, 11]);
+
+        assert_eq!(Winding::default().quad_indices(), ccw);
+        assert_eq!(Winding::Cw.quad_indices(), cw);
+    }
+}
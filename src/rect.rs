@@ -1,9 +1,12 @@
-use std::fmt::{self, Debug, Display};
+use std::{
+    fmt::{self, Debug, Display},
+    ops::{Add, Div, Mul, Sub},
+};
 
 /// General purpose 2D rectangle.
 ///
 /// Contains a position and size.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rect<T: Debug + Copy> {
     pub pos: [T; 2],
     pub size: [T; 2],
@@ -34,3 +37,201 @@ where
             && other.size[1] <= self.size[1]
     }
 }
+
+impl<T> Rect<T>
+where
+    T: PartialOrd + Add<Output = T> + Debug + Copy,
+{
+    /// Checks whether this rectangle and `other` overlap.
+    ///
+    /// Rectangles that only touch at their edges do not overlap.
+    pub fn intersects(&self, other: &Rect<T>) -> bool {
+        self.pos[0] < other.pos[0] + other.size[0]
+            && other.pos[0] < self.pos[0] + self.size[0]
+            && self.pos[1] < other.pos[1] + other.size[1]
+            && other.pos[1] < self.pos[1] + self.size[1]
+    }
+
+    /// Rectangle moved by the offset, with the same size.
+    pub fn translated(&self, offset: [T; 2]) -> Self {
+        Rect {
+            pos: [self.pos[0] + offset[0], self.pos[1] + offset[1]],
+            size: self.size,
+        }
+    }
+}
+
+impl<T> Rect<T>
+where
+    T: Mul<Output = T> + Debug + Copy,
+{
+    /// Rectangle with both position and size multiplied by the factor,
+    /// as when zooming a camera around the origin.
+    pub fn scaled(&self, factor: T) -> Self {
+        Rect {
+            pos: [self.pos[0] * factor, self.pos[1] * factor],
+            size: [self.size[0] * factor, self.size[1] * factor],
+        }
+    }
+}
+
+impl<T> Rect<T>
+where
+    T: PartialOrd + Add<Output = T> + Sub<Output = T> + Div<Output = T> + From<u8> + Default,
+    T: Debug + Copy,
+{
+    /// Point in the middle of the rectangle.
+    ///
+    /// Integer coordinates are rounded down.
+    pub fn center(&self) -> [T; 2] {
+        let two = T::from(2);
+        [
+            self.pos[0] + self.size[0] / two,
+            self.pos[1] + self.size[1] / two,
+        ]
+    }
+
+    /// Rectangle shrunk by the amount on all four sides.
+    ///
+    /// A negative amount grows the rectangle instead. When the amount is
+    /// more than half the width or height, that dimension is clamped to
+    /// zero, at the rectangle's center.
+    pub fn inset(&self, amount: T) -> Self {
+        let two = T::from(2);
+        let mut rect = *self;
+        for axis in 0..2 {
+            // Compared before subtracting, so unsigned sizes don't underflow.
+            if amount + amount > self.size[axis] {
+                rect.pos[axis] = self.pos[axis] + self.size[axis] / two;
+                rect.size[axis] = T::default();
+            } else {
+                rect.pos[axis] = self.pos[axis] + amount;
+                rect.size[axis] = self.size[axis] - (amount + amount);
+            }
+        }
+        rect
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intersects() {
+        let rect = Rect {
+            pos: [0.0, 0.0],
+            size: [100.0, 100.0],
+        };
+
+        let inside = Rect {
+            pos: [10.0, 10.0],
+            size: [10.0, 10.0],
+        };
+        let overlapping = Rect {
+            pos: [90.0, -5.0],
+            size: [20.0, 20.0],
+        };
+        let touching = Rect {
+            pos: [100.0, 0.0],
+            size: [10.0, 10.0],
+        };
+        let outside = Rect {
+            pos: [200.0, 200.0],
+            size: [10.0, 10.0],
+        };
+
+        assert!(rect.intersects(&inside));
+        assert!(rect.intersects(&overlapping));
+        assert!(overlapping.intersects(&rect));
+        assert!(!rect.intersects(&touching));
+        assert!(!rect.intersects(&outside));
+    }
+
+    #[test]
+    fn test_translated() {
+        let rect = Rect {
+            pos: [10, 20],
+            size: [30, 40],
+        };
+        assert_eq!(
+            rect.translated([-5, 5]),
+            Rect {
+                pos: [5, 25],
+                size: [30, 40]
+            }
+        );
+    }
+
+    #[test]
+    fn test_scaled() {
+        let rect = Rect {
+            pos: [10.0, 20.0],
+            size: [30.0, 40.0],
+        };
+        assert_eq!(
+            rect.scaled(0.5),
+            Rect {
+                pos: [5.0, 10.0],
+                size: [15.0, 20.0]
+            }
+        );
+    }
+
+    #[test]
+    fn test_center() {
+        let rect = Rect {
+            pos: [10.0, 20.0],
+            size: [30.0, 40.0],
+        };
+        assert_eq!(rect.center(), [25.0, 40.0]);
+
+        // Rounded down.
+        let rect = Rect {
+            pos: [0u32, 0],
+            size: [5, 5],
+        };
+        assert_eq!(rect.center(), [2, 2]);
+    }
+
+    #[test]
+    fn test_inset() {
+        let rect = Rect {
+            pos: [10, 20],
+            size: [30, 40],
+        };
+        assert_eq!(
+            rect.inset(5),
+            Rect {
+                pos: [15, 25],
+                size: [20, 30]
+            }
+        );
+
+        // Negative insets grow the rectangle.
+        assert_eq!(
+            rect.inset(-5),
+            Rect {
+                pos: [5, 15],
+                size: [40, 50]
+            }
+        );
+
+        // Insets past the middle clamp to zero size, at the center.
+        assert_eq!(
+            rect.inset(18),
+            Rect {
+                pos: [25, 38],
+                size: [0, 4]
+            }
+        );
+        assert_eq!(rect.inset(100).size, [0, 0]);
+
+        // Unsigned sizes don't underflow.
+        let rect = Rect {
+            pos: [0u32, 0],
+            size: [4, 4],
+        };
+        assert_eq!(rect.inset(3).size, [0, 0]);
+    }
+}
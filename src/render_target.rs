@@ -0,0 +1,319 @@
+//! Off-screen framebuffers drawn into textures.
+use crate::{
+    device::{Destroy, DestroySender, GraphicDevice},
+    dpi::PhysicalSize,
+    errors::{self, gl_error_at, gl_result},
+    marker::Invariant,
+    texture::Texture,
+};
+use glow::HasContext;
+
+/// Framebuffer with one or more colour textures attached, for drawing
+/// off-screen.
+///
+/// With several attachments, fragment shaders write to each through
+/// `out` variables with explicit locations, matching the attachment
+/// index. For example colour and normals for 2D lighting:
+///
+/// ```glsl
+/// layout(location = 0) out vec4 Color;
+/// layout(location = 1) out vec4 Normal;
+/// ```
+///
+/// A depth and stencil buffer can be attached as well, see `with_depth_stencil`.
+pub struct RenderTarget {
+    fbo: glow::Framebuffer,
+    /// Textures attached to `GL_COLOR_ATTACHMENT0..N`, in order.
+    color_attachments: Vec<Texture>,
+    /// Renderbuffer for the depth and stencil attachments.
+    renderbuffer: Option<glow::Renderbuffer>,
+    depth_stencil: DepthStencil,
+    size: PhysicalSize<u32>,
+    destroy: DestroySender,
+    _invariant: Invariant,
+}
+
+impl RenderTarget {
+    /// Create a target with a single colour attachment.
+    pub fn new(device: &GraphicDevice, width: u32, height: u32) -> errors::Result<Self> {
+        Self::with_color_attachments(device, width, height, 1)
+    }
+
+    /// Create a target with the given number of colour attachments, each
+    /// an RGBA8 texture of the target's size. Fragment outputs are routed
+    /// to the attachments with `glDrawBuffers`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidAttachmentCount` if the count is 0, or more than the
+    /// device supports, `InvalidTextureSize` if a dimension is 0, and
+    /// `IncompleteFramebuffer` if the driver rejects the combination.
+    pub fn with_color_attachments(
+        device: &GraphicDevice,
+        width: u32,
+        height: u32,
+        count: u32,
+    ) -> errors::Result<Self> {
+        Self::with_depth_stencil(device, width, height, count, DepthStencil::default())
+    }
+
+    /// Create a target with the given number of colour attachments, and a
+    /// renderbuffer for depth testing and stencilling off-screen passes.
+    ///
+    /// When both depth and stencil are requested, they share a single
+    /// `DEPTH24_STENCIL8` renderbuffer, the combination drivers support best.
+    /// The buffers are not sampled, and are cleared with the device's
+    /// clear calls while the target is bound.
+    ///
+    /// # Errors
+    ///
+    /// Like `with_color_attachments`.
+    pub fn with_depth_stencil(
+        device: &GraphicDevice,
+        width: u32,
+        height: u32,
+        count: u32,
+        depth_stencil: DepthStencil,
+    ) -> errors::Result<Self> {
+        let max = unsafe {
+            let max_attachments = device.gl.get_parameter_i32(glow::MAX_COLOR_ATTACHMENTS);
+            let max_draw_buffers = device.gl.get_parameter_i32(glow::MAX_DRAW_BUFFERS);
+            max_attachments.min(max_draw_buffers).max(0) as u32
+        };
+        validate_attachment_count(count, max)?;
+
+        let color_attachments = (0..count)
+            .map(|_| Texture::new(device, width, height))
+            .collect::<errors::Result<Vec<_>>>()?;
+
+        unsafe {
+            let fbo = gl_result(&device.gl, device.gl.create_framebuffer())?;
+            // Take ownership right away, so the framebuffer is deleted on error.
+            let mut target = Self {
+                fbo,
+                color_attachments,
+                renderbuffer: None,
+                depth_stencil,
+                size: PhysicalSize::new(width, height),
+                destroy: device.destroy_sender(),
+                _invariant: Default::default(),
+            };
+
+            if let Some((internal_format, _)) = depth_stencil.renderbuffer_format() {
+                let renderbuffer = gl_result(&device.gl, device.gl.create_renderbuffer())?;
+                target.renderbuffer = Some(renderbuffer);
+
+                let previous = device.gl.get_parameter_i32(glow::RENDERBUFFER_BINDING) as u32;
+                device
+                    .gl
+                    .bind_renderbuffer(glow::RENDERBUFFER, Some(renderbuffer));
+                device.gl.renderbuffer_storage(
+                    glow::RENDERBUFFER,
+                    internal_format,
+                    width as i32,
+                    height as i32,
+                );
+                device
+                    .gl
+                    .bind_renderbuffer(glow::RENDERBUFFER, Some(previous));
+                gl_error_at(&device.gl, "renderbuffer_storage", ())?;
+            }
+
+            let previous = device.gl.get_parameter_i32(glow::FRAMEBUFFER_BINDING) as u32;
+            device.gl.bind_framebuffer(glow::FRAMEBUFFER, Some(fbo));
+
+            let result = target.attach(device);
+
+            device
+                .gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(previous));
+
+            result.map(|_| target)
+        }
+    }
+
+    /// Attach the colour textures and renderbuffer to the bound framebuffer,
+    /// and check it's complete.
+    unsafe fn attach(&self, device: &GraphicDevice) -> errors::Result<()> {
+        for (index, texture) in self.color_attachments.iter().enumerate() {
+            device.gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0 + index as u32,
+                glow::TEXTURE_2D,
+                Some(texture.raw_handle()),
+                0,
+            );
+        }
+        gl_error_at(&device.gl, "framebuffer_texture_2d", ())?;
+
+        if let (Some(renderbuffer), Some((_, attachment))) =
+            (self.renderbuffer, self.depth_stencil.renderbuffer_format())
+        {
+            device.gl.framebuffer_renderbuffer(
+                glow::FRAMEBUFFER,
+                attachment,
+                glow::RENDERBUFFER,
+                Some(renderbuffer),
+            );
+            gl_error_at(&device.gl, "framebuffer_renderbuffer", ())?;
+        }
+
+        device
+            .gl
+            .draw_buffers(&draw_buffers(self.color_attachments.len() as u32));
+        gl_error_at(&device.gl, "draw_buffers", ())?;
+
+        match device.gl.check_framebuffer_status(glow::FRAMEBUFFER) {
+            glow::FRAMEBUFFER_COMPLETE => Ok(()),
+            status => Err(errors::Error::IncompleteFramebuffer(status)),
+        }
+    }
+
+    /// Direct drawing into this target.
+    ///
+    /// Sets the viewport to the target's size. Since the attachments are
+    /// sampled as textures, the y-axis is not flipped while bound.
+    pub fn bind(&self, device: &GraphicDevice) {
+        unsafe {
+            device
+                .gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(self.fbo));
+            device
+                .gl
+                .viewport(0, 0, self.size.width as i32, self.size.height as i32);
+        }
+        device.set_flip_y(false);
+    }
+
+    /// Direct drawing back to the window.
+    ///
+    /// Restores the device's viewport size and y-axis flip.
+    pub fn unbind(device: &GraphicDevice) {
+        let size = device.get_viewport_size();
+        unsafe {
+            device.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            device
+                .gl
+                .viewport(0, 0, size.width as i32, size.height as i32);
+        }
+        device.set_flip_y(true);
+    }
+
+    /// Size of the target in pixels, as passed to `SpriteBatch::draw_to_target`.
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    /// Texture attached to `GL_COLOR_ATTACHMENT0 + index`.
+    pub fn color_attachment(&self, index: usize) -> Option<&Texture> {
+        self.color_attachments.get(index)
+    }
+
+    pub fn color_attachments(&self) -> &[Texture] {
+        &self.color_attachments
+    }
+
+    /// Depth and stencil buffers attached to the target.
+    pub fn depth_stencil(&self) -> DepthStencil {
+        self.depth_stencil
+    }
+
+    pub fn raw_handle(&self) -> glow::Framebuffer {
+        self.fbo
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        // Attached textures are released by their own handles.
+        self.destroy.send(Destroy::Framebuffer(self.fbo));
+        if let Some(renderbuffer) = self.renderbuffer {
+            self.destroy.send(Destroy::Renderbuffer(renderbuffer));
+        }
+    }
+}
+
+/// Depth and stencil buffers to attach to a `RenderTarget`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DepthStencil {
+    /// Attach a 24-bit depth buffer.
+    pub depth: bool,
+    /// Attach an 8-bit stencil buffer.
+    pub stencil: bool,
+}
+
+impl DepthStencil {
+    /// Both a depth and a stencil buffer.
+    pub const BOTH: Self = Self {
+        depth: true,
+        stencil: true,
+    };
+
+    /// Internal format of the renderbuffer, and the attachment point
+    /// it's attached to. `None` when neither buffer is requested.
+    fn renderbuffer_format(self) -> Option<(u32, u32)> {
+        match (self.depth, self.stencil) {
+            (true, true) => Some((glow::DEPTH24_STENCIL8, glow::DEPTH_STENCIL_ATTACHMENT)),
+            (true, false) => Some((glow::DEPTH_COMPONENT24, glow::DEPTH_ATTACHMENT)),
+            (false, true) => Some((glow::STENCIL_INDEX8, glow::STENCIL_ATTACHMENT)),
+            (false, false) => None,
+        }
+    }
+}
+
+fn validate_attachment_count(count: u32, max: u32) -> errors::Result<()> {
+    if count == 0 || count > max {
+        Err(errors::Error::InvalidAttachmentCount { count, max })
+    } else {
+        Ok(())
+    }
+}
+
+/// Buffers passed to `glDrawBuffers`, routing fragment output `i`
+/// to colour attachment `i`.
+fn draw_buffers(count: u32) -> Vec<u32> {
+    (0..count).map(|i| glow::COLOR_ATTACHMENT0 + i).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_attachment_count() {
+        assert!(validate_attachment_count(1, 8).is_ok());
+        assert!(validate_attachment_count(8, 8).is_ok());
+        assert!(matches!(
+            validate_attachment_count(9, 8),
+            Err(errors::Error::InvalidAttachmentCount { count: 9, max: 8 })
+        ));
+        assert!(validate_attachment_count(0, 8).is_err());
+    }
+
+    #[test]
+    fn test_depth_stencil_format() {
+        // Both share one renderbuffer.
+        assert_eq!(
+            DepthStencil::BOTH.renderbuffer_format(),
+            Some((glow::DEPTH24_STENCIL8, glow::DEPTH_STENCIL_ATTACHMENT))
+        );
+        let depth = DepthStencil {
+            depth: true,
+            stencil: false,
+        };
+        assert_eq!(
+            depth.renderbuffer_format(),
+            Some((glow::DEPTH_COMPONENT24, glow::DEPTH_ATTACHMENT))
+        );
+        assert_eq!(DepthStencil::default().renderbuffer_format(), None);
+    }
+
+    #[test]
+    fn test_draw_buffers() {
+        // DRAW_BUFFER0 and DRAW_BUFFER1 of a 2-attachment target.
+        assert_eq!(
+            draw_buffers(2),
+            [glow::COLOR_ATTACHMENT0, glow::COLOR_ATTACHMENT1]
+        );
+    }
+}
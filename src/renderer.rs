@@ -0,0 +1,154 @@
+//! Top-level renderer owning the device, a sprite batch and the sprite shader.
+use crate::{
+    device::GraphicDevice,
+    dpi::PhysicalSize,
+    errors,
+    shader::Shader,
+    sprite_batch::{Sprite, SpriteBatch},
+};
+#[cfg(feature = "window")]
+use glutin::{window::Window, PossiblyCurrent, WindowedContext};
+use std::mem::ManuallyDrop;
+
+/// Draws sprites with the default sprite shader, taking care of the
+/// order of per-frame calls.
+///
+/// Resources owned by the renderer are released before the device when
+/// it's dropped, so no video memory is left behind.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use grok_glow::{renderer::Renderer, sprite_batch::Sprite};
+/// # #[cfg(feature = "window")]
+/// # fn main() -> grok_glow::errors::Result<()> {
+/// let (device, _event_loop, windowed_context) = grok_glow::init_window("Grok", 1024, 768)?;
+/// let mut renderer = Renderer::with_context(device, windowed_context)?;
+///
+/// renderer.begin([0.1, 0.2, 0.3, 1.0])?;
+/// renderer.draw_sprite(&Sprite::with([100, 100], [64, 64]));
+/// renderer.end()?;
+/// # Ok(())
+/// # }
+/// # #[cfg(not(feature = "window"))]
+/// # fn main() {}
+/// ```
+pub struct Renderer {
+    /// Dropped manually, before the device deallocates its handles.
+    batch: ManuallyDrop<SpriteBatch>,
+    shader: ManuallyDrop<Shader>,
+    device: GraphicDevice,
+    /// Window context presented by `end`. Declared last so it outlives
+    /// the device's final OpenGL calls.
+    #[cfg(feature = "window")]
+    context: Option<WindowedContext<PossiblyCurrent>>,
+}
+
+impl Renderer {
+    /// Create a renderer that draws to the device's current framebuffer,
+    /// without presenting it, like with a headless context.
+    pub fn new(device: GraphicDevice) -> errors::Result<Self> {
+        let shader = Shader::try_from_source(
+            &device,
+            include_str!("sprite.vert"),
+            include_str!("sprite.frag"),
+        )?;
+        let batch = SpriteBatch::new(&device)?;
+
+        Ok(Self {
+            batch: ManuallyDrop::new(batch),
+            shader: ManuallyDrop::new(shader),
+            device,
+            #[cfg(feature = "window")]
+            context: None,
+        })
+    }
+
+    /// Create a renderer that swaps the window's buffers at the end of
+    /// each frame.
+    ///
+    /// The device must have been created from the same context.
+    #[cfg(feature = "window")]
+    pub fn with_context(
+        device: GraphicDevice,
+        context: WindowedContext<PossiblyCurrent>,
+    ) -> errors::Result<Self> {
+        let mut renderer = Self::new(device)?;
+        renderer.context = Some(context);
+        Ok(renderer)
+    }
+
+    /// Start a frame, deallocating resources dropped since the last one,
+    /// and clear the screen to the colour.
+    pub fn begin(&mut self, clear_color: [f32; 4]) -> errors::Result<()> {
+        self.device.begin_frame()?;
+        self.device.clear_screen(clear_color);
+        Ok(())
+    }
+
+    /// Queue a sprite to be drawn by `end`.
+    pub fn draw_sprite(&mut self, sprite: &Sprite) {
+        self.batch.add(sprite);
+    }
+
+    /// Draw the queued sprites, end the frame, and present it when the
+    /// renderer owns a window context.
+    ///
+    /// # Errors
+    ///
+    /// Returns `SwapBuffers` when the buffers could not be swapped.
+    pub fn end(&mut self) -> errors::Result<()> {
+        self.batch.draw(&self.device, &self.shader);
+        self.device.end_frame()?;
+
+        #[cfg(feature = "window")]
+        if let Some(context) = &self.context {
+            context
+                .swap_buffers()
+                .map_err(|err| errors::Error::SwapBuffers(err.to_string()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Resize the window context, if any, and the device's viewport.
+    ///
+    /// Call when the window is resized.
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        #[cfg(feature = "window")]
+        if let Some(context) = &self.context {
+            // Required on some platforms.
+            context.resize(size);
+        }
+        self.device.set_viewport_size(size);
+    }
+
+    pub fn device(&self) -> &GraphicDevice {
+        &self.device
+    }
+
+    /// Batch the sprites are queued in, to change its settings.
+    pub fn batch_mut(&mut self) -> &mut SpriteBatch {
+        &mut self.batch
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    #[cfg(feature = "window")]
+    pub fn window(&self) -> Option<&Window> {
+        self.context.as_ref().map(|context| context.window())
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        // SAFETY: The fields are not used again.
+        unsafe {
+            ManuallyDrop::drop(&mut self.batch);
+            ManuallyDrop::drop(&mut self.shader);
+        }
+        self.device.shutdown();
+    }
+}
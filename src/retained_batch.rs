@@ -0,0 +1,401 @@
+//! Sprites kept in video memory between frames.
+use crate::{
+    color,
+    device::GraphicDevice,
+    errors::{self, debug_assert_gl},
+    shader::Shader,
+    sprite_batch::{self, Sprite, SpriteBatch},
+    texture::Texture,
+    utils,
+    vertex::{Vertex, VertexBuffer},
+};
+use glow::HasContext;
+use std::{mem, ops::Range};
+
+/// Number of vertices making up a sprite's quad.
+const QUAD_VERTICES: usize = 4;
+
+/// Number of indices making up a sprite's two triangles.
+const QUAD_INDICES: usize = 6;
+
+/// Sprite batch that keeps its sprites in video memory, in stable slots.
+///
+/// Where `SpriteBatch` rebuilds and uploads all of its vertices each
+/// draw, a retained batch only uploads the slots that changed since the
+/// last `update`. Suited for UI and other geometry that rarely changes.
+///
+/// Sprites are positioned absolutely; screen anchors are ignored.
+pub struct RetainedBatch {
+    slots: Slots,
+    vertex_buffer: VertexBuffer,
+}
+
+/// Handle to a sprite inserted into a `RetainedBatch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SlotId(usize);
+
+impl RetainedBatch {
+    /// Maximum number of sprites in the batch.
+    pub const CAPACITY: usize = SpriteBatch::BATCH_SIZE;
+
+    pub fn new(device: &GraphicDevice) -> errors::Result<Self> {
+        Ok(Self {
+            slots: Slots::new(Self::CAPACITY),
+            vertex_buffer: SpriteBatch::create_vertex_buffer(device)?,
+        })
+    }
+
+    /// Add a sprite, reusing the slot of a removed sprite if there is one.
+    ///
+    /// Returns `None` when the batch is full.
+    pub fn insert(&mut self, sprite: &Sprite) -> Option<SlotId> {
+        self.slots.insert(RetainedItem::from_sprite(sprite))
+    }
+
+    /// Replace the sprite in the given slot.
+    ///
+    /// # Panics
+    ///
+    /// When the slot is empty.
+    pub fn set(&mut self, slot: SlotId, sprite: &Sprite) {
+        self.slots.set(slot, RetainedItem::from_sprite(sprite));
+    }
+
+    /// Remove the sprite in the given slot, freeing it for reuse.
+    ///
+    /// Returns `false` when the slot was already empty.
+    pub fn remove(&mut self, slot: SlotId) -> bool {
+        self.slots.remove(slot)
+    }
+
+    /// Number of sprites in the batch.
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Upload the vertices of slots changed since the last update.
+    ///
+    /// Contiguous changed slots are uploaded together. Unchanged
+    /// geometry is left in place.
+    pub fn update(&mut self, device: &GraphicDevice) {
+        let ranges = self.slots.take_dirty();
+        if ranges.is_empty() {
+            return;
+        }
+
+        unsafe {
+            device
+                .gl
+                .bind_buffer(glow::ARRAY_BUFFER, Some(self.vertex_buffer.vertex_buffer));
+
+            for range in ranges {
+                let vertices = self.slots.vertices(range.clone());
+                device.gl.buffer_sub_data_u8_slice(
+                    glow::ARRAY_BUFFER,
+                    byte_range(range).start as i32,
+                    utils::as_u8(&vertices),
+                );
+                debug_assert_gl(&device.gl, ());
+            }
+
+            device.gl.bind_buffer(glow::ARRAY_BUFFER, None);
+        }
+    }
+
+    /// Update, then draw the sprites in slot order.
+    ///
+    /// A draw call is issued for each run of sprites sharing a texture.
+    pub fn draw(&mut self, device: &GraphicDevice, shader: &Shader) {
+        self.update(device);
+
+        if self.is_empty()
+            || !device.is_context_valid()
+            || device.is_shutting_down()
+            || device.is_viewport_empty()
+        {
+            return;
+        }
+
+        let white_texture = device.white_texture().ok();
+        let canvas_size = device.get_viewport_size();
+
+        unsafe {
+            device.gl.use_program(Some(shader.program));
+
+            // FIXME: Specific to the sprite shader.
+            device.gl.uniform_2_f32(
+                Some(&0),
+                canvas_size.width as f32,
+                canvas_size.height as f32,
+            );
+            device
+                .gl
+                .uniform_1_i32(Some(&2), device.get_flip_y() as i32);
+
+            // Texture slot determined by sprite shader.
+            device.gl.active_texture(glow::TEXTURE0);
+        }
+
+        for (slots, texture) in self.slots.texture_runs() {
+            let texture = match texture.or(white_texture.as_ref()) {
+                Some(texture) => texture,
+                None => continue,
+            };
+
+            unsafe {
+                device
+                    .gl
+                    .bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+            }
+
+            self.vertex_buffer.draw(
+                device,
+                slots.start * QUAD_INDICES,
+                slots.len() * QUAD_INDICES,
+            );
+        }
+
+        unsafe {
+            device.gl.bind_texture(glow::TEXTURE_2D, None);
+            device.gl.bind_vertex_array(None);
+            device.gl.use_program(None);
+        }
+    }
+}
+
+/// Sprite stored in a slot.
+struct RetainedItem {
+    vertices: [Vertex; QUAD_VERTICES],
+    /// Sprites without a texture are drawn with the device's white texture.
+    texture: Option<Texture>,
+}
+
+impl RetainedItem {
+    fn from_sprite(sprite: &Sprite) -> Self {
+        Self {
+            vertices: sprite_batch::quad_vertices(sprite.corners(), sprite.uv_rect(), sprite.color),
+            texture: sprite.texture.clone(),
+        }
+    }
+}
+
+/// Slot bookkeeping, separate from video memory.
+struct Slots {
+    items: Vec<Option<RetainedItem>>,
+    /// Slots changed since the last upload, indexed like `items`.
+    dirty: Vec<bool>,
+    /// Emptied slots, reused before growing.
+    free: Vec<usize>,
+    capacity: usize,
+}
+
+impl Slots {
+    fn new(capacity: usize) -> Self {
+        Self {
+            items: vec![],
+            dirty: vec![],
+            free: vec![],
+            capacity,
+        }
+    }
+
+    fn insert(&mut self, item: RetainedItem) -> Option<SlotId> {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None if self.items.len() < self.capacity => {
+                self.items.push(None);
+                self.dirty.push(false);
+                self.items.len() - 1
+            }
+            None => return None,
+        };
+
+        self.items[index] = Some(item);
+        self.dirty[index] = true;
+        Some(SlotId(index))
+    }
+
+    fn set(&mut self, SlotId(index): SlotId, item: RetainedItem) {
+        let slot = &mut self.items[index];
+        assert!(slot.is_some(), "Sprite slot {} is empty", index);
+        *slot = Some(item);
+        self.dirty[index] = true;
+    }
+
+    fn remove(&mut self, SlotId(index): SlotId) -> bool {
+        match self.items.get_mut(index).and_then(Option::take) {
+            Some(_) => {
+                // Degenerate vertices overwrite the sprite in video memory.
+                self.dirty[index] = true;
+                self.free.push(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.items.len() - self.free.len()
+    }
+
+    /// Ranges of contiguous changed slots, clearing the changes.
+    fn take_dirty(&mut self) -> Vec<Range<usize>> {
+        let ranges = dirty_ranges(&self.dirty);
+        for dirty in self.dirty.iter_mut() {
+            *dirty = false;
+        }
+        ranges
+    }
+
+    /// Vertices of a range of slots, with empty slots collapsed to a point.
+    fn vertices(&self, slots: Range<usize>) -> Vec<Vertex> {
+        let empty = Vertex {
+            position: [0.0, 0.0],
+            uv: [0.0, 0.0],
+            color: color::TRANSPARENT,
+        };
+
+        let mut vertices = Vec::with_capacity(slots.len() * QUAD_VERTICES);
+        for item in &self.items[slots] {
+            match item {
+                Some(item) => vertices.extend_from_slice(&item.vertices),
+                None => vertices.extend((0..QUAD_VERTICES).map(|_| empty.clone())),
+            }
+        }
+        vertices
+    }
+
+    /// Ranges of slots that can be drawn with a single texture.
+    ///
+    /// Empty slots are degenerate and don't interrupt a run.
+    fn texture_runs(&self) -> Vec<(Range<usize>, Option<&Texture>)> {
+        let mut runs: Vec<(Range<usize>, Option<&Texture>)> = vec![];
+
+        for (index, item) in self.items.iter().enumerate() {
+            let item = match item {
+                Some(item) => item,
+                None => continue,
+            };
+            let key = item.texture.as_ref().map(Texture::sort_key);
+
+            match runs.last_mut() {
+                Some((range, texture)) if texture.map(Texture::sort_key) == key => {
+                    range.end = index + 1;
+                }
+                _ => runs.push((index..index + 1, item.texture.as_ref())),
+            }
+        }
+
+        runs
+    }
+}
+
+/// Merges the indices of changed slots into contiguous ranges.
+fn dirty_ranges(dirty: &[bool]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+
+    for (index, _) in dirty.iter().enumerate().filter(|(_, dirty)| **dirty) {
+        match ranges.last_mut() {
+            Some(range) if range.end == index => range.end += 1,
+            _ => ranges.push(index..index + 1),
+        }
+    }
+
+    ranges
+}
+
+/// Bytes occupied in the vertex buffer by a range of slots.
+fn byte_range(slots: Range<usize>) -> Range<usize> {
+    let slot_size = QUAD_VERTICES * mem::size_of::<Vertex>();
+    slots.start * slot_size..slots.end * slot_size
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::test::dummy_texture;
+
+    fn sprite(x: i32) -> Sprite {
+        Sprite::with([x, 0], [8, 8])
+    }
+
+    #[test]
+    fn test_update_changed_slots() {
+        let mut slots = Slots::new(16);
+        let ids: Vec<SlotId> = (0..10)
+            .map(|x| slots.insert(RetainedItem::from_sprite(&sprite(x))).unwrap())
+            .collect();
+
+        // Initial upload of all sprites at once.
+        assert_eq!(slots.take_dirty(), vec![Range { start: 0, end: 10 }]);
+        assert!(slots.take_dirty().is_empty());
+
+        slots.set(ids[2], RetainedItem::from_sprite(&sprite(100)));
+        slots.set(ids[7], RetainedItem::from_sprite(&sprite(100)));
+
+        let slot_size = 4 * mem::size_of::<Vertex>();
+        let bytes: Vec<Range<usize>> = slots.take_dirty().into_iter().map(byte_range).collect();
+        assert_eq!(
+            bytes,
+            [2 * slot_size..3 * slot_size, 7 * slot_size..8 * slot_size]
+        );
+    }
+
+    #[test]
+    fn test_reuse_removed_slot() {
+        let mut slots = Slots::new(2);
+        let a = slots.insert(RetainedItem::from_sprite(&sprite(0))).unwrap();
+        let _b = slots.insert(RetainedItem::from_sprite(&sprite(1))).unwrap();
+        assert!(slots
+            .insert(RetainedItem::from_sprite(&sprite(2)))
+            .is_none());
+        slots.take_dirty();
+
+        assert!(slots.remove(a));
+        assert!(!slots.remove(a));
+        assert_eq!(slots.len(), 1);
+
+        // Removed sprite is overwritten with a degenerate quad.
+        assert_eq!(slots.take_dirty(), vec![Range { start: 0, end: 1 }]);
+        assert!(slots
+            .vertices(0..1)
+            .iter()
+            .all(|v| v.position == [0.0, 0.0]));
+
+        assert_eq!(slots.insert(RetainedItem::from_sprite(&sprite(3))), Some(a));
+        assert_eq!(slots.len(), 2);
+    }
+
+    #[test]
+    fn test_texture_runs() {
+        let (_rx_a, texture_a) = dummy_texture(1, 8, 8);
+        let (_rx_b, texture_b) = dummy_texture(2, 8, 8);
+
+        let mut slots = Slots::new(8);
+        let mut ids = vec![];
+        for texture in &[&texture_a, &texture_a, &texture_b, &texture_b, &texture_a] {
+            let mut sprite = sprite(0);
+            sprite.set_texture((*texture).clone());
+            ids.push(slots.insert(RetainedItem::from_sprite(&sprite)).unwrap());
+        }
+
+        let ranges = |slots: &Slots| -> Vec<Range<usize>> {
+            slots
+                .texture_runs()
+                .into_iter()
+                .map(|(range, _)| range)
+                .collect()
+        };
+        assert_eq!(ranges(&slots), [0..2, 2..4, 4..5]);
+
+        // Empty slots don't break a run.
+        slots.remove(ids[1]);
+        slots.remove(ids[2]);
+        slots.remove(ids[3]);
+        assert_eq!(ranges(&slots), vec![Range { start: 0, end: 5 }]);
+    }
+}
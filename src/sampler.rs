@@ -0,0 +1,161 @@
+//! Sampler objects, decoupling texture sampling state from textures.
+use crate::{
+    color::{self, Color},
+    device::{Destroy, DestroySender, GraphicDevice},
+    errors::{self, gl_error, gl_result},
+};
+use glow::HasContext;
+
+/// Texture filtering applied when sampling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Nearest texel. Crisp, suitable for pixel art.
+    Nearest,
+    /// Weighted average of the nearest texels. Smooth.
+    Linear,
+}
+
+impl Filter {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            Filter::Nearest => glow::NEAREST,
+            Filter::Linear => glow::LINEAR,
+        }
+    }
+}
+
+/// Behaviour of texture coordinates outside the 0.0 to 1.0 range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    ClampToEdge,
+    /// Coordinates outside the texture sample the sampler's border colour.
+    ///
+    /// Not available in OpenGL ES 2.
+    ClampToBorder,
+    Repeat,
+    MirroredRepeat,
+}
+
+impl Wrap {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            Wrap::ClampToEdge => glow::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => glow::CLAMP_TO_BORDER,
+            Wrap::Repeat => glow::REPEAT,
+            Wrap::MirroredRepeat => glow::MIRRORED_REPEAT,
+        }
+    }
+}
+
+/// Handle to a sampler object located in video memory.
+///
+/// While a sampler is bound to a texture unit, its parameters override
+/// the sampling parameters of the texture bound to the same unit. Many
+/// textures can share the same filtering without each texture's state
+/// being changed.
+pub struct Sampler {
+    pub(crate) sampler: glow::Sampler,
+    filter: Filter,
+    wrap: Wrap,
+    border_color: Color,
+    destroy: DestroySender,
+}
+
+impl Sampler {
+    pub fn new(device: &GraphicDevice, filter: Filter, wrap: Wrap) -> errors::Result<Self> {
+        unsafe {
+            let sampler = gl_result(&device.gl, device.gl.create_sampler())?;
+
+            let gl_filter = filter.to_gl() as i32;
+            let gl_wrap = wrap.to_gl() as i32;
+            device
+                .gl
+                .sampler_parameter_i32(sampler, glow::TEXTURE_MIN_FILTER, gl_filter);
+            device
+                .gl
+                .sampler_parameter_i32(sampler, glow::TEXTURE_MAG_FILTER, gl_filter);
+            device
+                .gl
+                .sampler_parameter_i32(sampler, glow::TEXTURE_WRAP_S, gl_wrap);
+            device
+                .gl
+                .sampler_parameter_i32(sampler, glow::TEXTURE_WRAP_T, gl_wrap);
+            gl_error(&device.gl, ())?;
+
+            Ok(Self {
+                sampler,
+                filter,
+                wrap,
+                border_color: color::TRANSPARENT,
+                destroy: device.destroy_sender(),
+            })
+        }
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn wrap(&self) -> Wrap {
+        self.wrap
+    }
+
+    /// Colour sampled outside the texture when wrapping with `Wrap::ClampToBorder`.
+    pub fn border_color(&self) -> Color {
+        self.border_color
+    }
+
+    /// Set the colour sampled outside the texture when wrapping
+    /// with `Wrap::ClampToBorder`. Defaults to transparent black.
+    ///
+    /// Border colours are not available in OpenGL ES 2.
+    pub fn set_border_color(&mut self, device: &GraphicDevice, color: Color) -> errors::Result<()> {
+        // Parameter is only read, but glow's binding takes a mutable slice.
+        let mut values = color;
+        unsafe {
+            device.gl.sampler_parameter_f32_slice(
+                self.sampler,
+                glow::TEXTURE_BORDER_COLOR,
+                &mut values,
+            );
+            gl_error(&device.gl, ())?;
+        }
+
+        self.border_color = color;
+        Ok(())
+    }
+
+    /// Bind the sampler to the given texture unit, where unit
+    /// 0 is `GL_TEXTURE0`.
+    pub fn bind(&self, device: &GraphicDevice, unit: u32) {
+        unsafe {
+            device.gl.bind_sampler(unit, Some(self.sampler));
+        }
+    }
+
+    /// Clear any sampler bound to the given texture unit.
+    pub fn unbind(device: &GraphicDevice, unit: u32) {
+        unsafe {
+            device.gl.bind_sampler(unit, None);
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.destroy.send(Destroy::Sampler(self.sampler));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_wrap_to_gl() {
+        assert_eq!(Wrap::ClampToEdge.to_gl(), glow::CLAMP_TO_EDGE);
+        assert_eq!(Wrap::ClampToBorder.to_gl(), glow::CLAMP_TO_BORDER);
+        assert_eq!(Wrap::Repeat.to_gl(), glow::REPEAT);
+        assert_eq!(Wrap::MirroredRepeat.to_gl(), glow::MIRRORED_REPEAT);
+    }
+}
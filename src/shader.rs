@@ -1,62 +1,409 @@
-use crate::device::{Destroy, GraphicDevice};
+use crate::{
+    device::{Destroy, DestroySender, GraphicDevice},
+    errors::{self, gl_error, gl_result},
+};
 use glow::HasContext;
-use std::sync::mpsc::Sender;
+use std::fmt;
 
 pub struct Shader {
     pub(crate) program: u32,
-    destroy: Sender<Destroy>,
+    destroy: DestroySender,
+}
+
+/// Step of building a shader program that can fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Link,
+}
+
+impl fmt::Display for ShaderStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderStage::Vertex => write!(f, "vertex shader"),
+            ShaderStage::Fragment => write!(f, "fragment shader"),
+            ShaderStage::Link => write!(f, "program link"),
+        }
+    }
+}
+
+/// Vertex attribute read by a shader program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeInfo {
+    pub name: String,
+    pub location: u32,
+    /// OpenGL type, like `GL_FLOAT_VEC2`. See `glsl_type_name`.
+    pub gl_type: u32,
+    /// Number of array elements, 1 when not an array.
+    pub size: i32,
+    /// Number of components, like 2 for a `vec2`. 0 for types
+    /// other than scalars and vectors.
+    pub components: i32,
+}
+
+/// Uniform declared by a shader program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UniformInfo {
+    /// Name as reported by the driver. Arrays end in `[0]`.
+    pub name: String,
+    /// `None` for uniforms in a uniform block, which are set through
+    /// a `UniformBuffer` instead.
+    pub location: Option<u32>,
+    /// OpenGL type, like `GL_SAMPLER_2D`. See `glsl_type_name`.
+    pub gl_type: u32,
+    /// Number of array elements, 1 when not an array.
+    pub size: i32,
+}
+
+/// Info log of a failed shader stage.
+#[derive(Debug, Clone)]
+pub struct ShaderLog {
+    pub stage: ShaderStage,
+    pub log: String,
 }
 
 impl Shader {
+    /// Compile and link a shader program.
+    ///
+    /// # Panics
+    ///
+    /// When a stage fails to compile, or the program fails to link.
+    /// See `try_from_source` to handle the error instead.
     pub fn from_source(device: &GraphicDevice, vertex: &str, fragment: &str) -> Self {
-        // Create Shader program.
-        let program = unsafe { device.gl.create_program().unwrap() };
+        Self::try_from_source(device, vertex, fragment).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Compile and link a shader program.
+    ///
+    /// Both stages are compiled even when the first fails, so all
+    /// compile errors are reported together.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ShaderCompile` with the info log of each failed stage.
+    /// Linking is only attempted when both stages compiled.
+    pub fn try_from_source(
+        device: &GraphicDevice,
+        vertex: &str,
+        fragment: &str,
+    ) -> errors::Result<Self> {
+        // Create Shader program. Owned right away, so it's deleted on error.
+        let shader = Self {
+            program: unsafe { gl_result(&device.gl, device.gl.create_program())? },
+            destroy: device.destroy_sender(),
+        };
+        let program = shader.program;
 
-        // Link shaders.
         let shader_sources = [
-            (glow::VERTEX_SHADER, vertex),
-            (glow::FRAGMENT_SHADER, fragment),
+            (ShaderStage::Vertex, glow::VERTEX_SHADER, vertex),
+            (ShaderStage::Fragment, glow::FRAGMENT_SHADER, fragment),
         ];
 
         let mut shaders = Vec::with_capacity(shader_sources.len());
+        let mut logs = vec![];
 
-        for (shader_type, shader_source) in shader_sources.iter() {
+        for (stage, shader_type, shader_source) in shader_sources.iter() {
             unsafe {
-                let shader = device.gl.create_shader(*shader_type).unwrap();
-                device.gl.shader_source(shader, shader_source);
-                device.gl.compile_shader(shader);
-                if !device.gl.get_shader_compile_status(shader) {
-                    panic!(device.gl.get_shader_info_log(shader));
+                let stage_shader =
+                    match gl_result(&device.gl, device.gl.create_shader(*shader_type)) {
+                        Ok(stage_shader) => stage_shader,
+                        Err(err) => {
+                            for stage_shader in shaders {
+                                device.gl.delete_shader(stage_shader);
+                            }
+                            return Err(err);
+                        }
+                    };
+                device.gl.shader_source(stage_shader, shader_source);
+                device.gl.compile_shader(stage_shader);
+                if !device.gl.get_shader_compile_status(stage_shader) {
+                    logs.push(ShaderLog {
+                        stage: *stage,
+                        log: device.gl.get_shader_info_log(stage_shader),
+                    });
                 }
-                device.gl.attach_shader(program, shader);
-                shaders.push(shader);
+                device.gl.attach_shader(program, stage_shader);
+                shaders.push(stage_shader);
             }
         }
 
-        unsafe {
-            device.gl.link_program(program);
-            if !device.gl.get_program_link_status(program) {
-                panic!(device.gl.get_program_info_log(program));
+        // Link shaders.
+        if logs.is_empty() {
+            unsafe {
+                device.gl.link_program(program);
+                if !device.gl.get_program_link_status(program) {
+                    logs.push(ShaderLog {
+                        stage: ShaderStage::Link,
+                        log: device.gl.get_program_info_log(program),
+                    });
+                }
             }
         }
 
         // Once the shaders are linked to a program, it's safe to detach and delete them.
-        for shader in shaders {
+        for stage_shader in shaders {
             unsafe {
-                device.gl.detach_shader(program, shader);
-                device.gl.delete_shader(shader);
+                device.gl.detach_shader(program, stage_shader);
+                device.gl.delete_shader(stage_shader);
             }
         }
 
-        Self {
-            program,
-            destroy: device.destroy_sender(),
+        if logs.is_empty() {
+            Ok(shader)
+        } else {
+            Err(errors::Error::ShaderCompile(logs))
+        }
+    }
+
+    /// Compile a shader program, expanding `#include "file"` directives first.
+    ///
+    /// The resolver supplies the content of an included file by name, or
+    /// `None` when it does not exist. Includes are expanded recursively,
+    /// and including a file that is already being expanded is an error.
+    pub fn from_source_with_includes<F>(
+        device: &GraphicDevice,
+        vertex: &str,
+        fragment: &str,
+        resolver: F,
+    ) -> errors::Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let vertex = expand_includes(vertex, &resolver, &mut vec![])?;
+        let fragment = expand_includes(fragment, &resolver, &mut vec![])?;
+
+        Self::try_from_source(device, &vertex, &fragment)
+    }
+
+    pub fn raw_handle(&self) -> glow::Program {
+        self.program
+    }
+
+    /// Vertex attributes read by the program, excluding built-in
+    /// inputs like `gl_VertexID`.
+    ///
+    /// Attributes the compiler optimised away are not active.
+    pub fn active_attributes(&self, device: &GraphicDevice) -> Vec<AttributeInfo> {
+        unsafe {
+            let count = device.gl.get_active_attributes(self.program);
+            (0..count)
+                .filter_map(|index| device.gl.get_active_attribute(self.program, index))
+                .filter(|attribute| !attribute.name.starts_with("gl_"))
+                .filter_map(|attribute| {
+                    let location = device
+                        .gl
+                        .get_attrib_location(self.program, &attribute.name)?;
+                    Some(AttributeInfo {
+                        components: attribute_components(attribute.atype),
+                        gl_type: attribute.atype,
+                        size: attribute.size,
+                        name: attribute.name,
+                        location,
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// Uniforms used by the program, including those in uniform blocks.
+    ///
+    /// Uniforms the compiler optimised away are not active.
+    pub fn active_uniforms(&self, device: &GraphicDevice) -> Vec<UniformInfo> {
+        unsafe {
+            let count = device.gl.get_active_uniforms(self.program);
+            (0..count)
+                .filter_map(|index| device.gl.get_active_uniform(self.program, index))
+                .map(|uniform| UniformInfo {
+                    location: device.gl.get_uniform_location(self.program, &uniform.name),
+                    gl_type: uniform.utype,
+                    size: uniform.size,
+                    name: uniform.name,
+                })
+                .collect()
+        }
+    }
+
+    /// Bind the named uniform block to a binding point, to read
+    /// from the `UniformBuffer` attached to the same point.
+    pub fn bind_uniform_block(
+        &self,
+        device: &GraphicDevice,
+        name: &str,
+        binding: u32,
+    ) -> errors::Result<()> {
+        unsafe {
+            let index = device
+                .gl
+                .get_uniform_block_index(self.program, name)
+                .ok_or_else(|| errors::Error::UniformNotFound(name.to_owned()))?;
+            device
+                .gl
+                .uniform_block_binding(self.program, index, binding);
+            gl_error(&device.gl, ())
         }
     }
 }
 
 impl Drop for Shader {
     fn drop(&mut self) {
-        self.destroy.send(Destroy::Shader(self.program)).unwrap();
+        self.destroy.send(Destroy::Shader(self.program));
+    }
+}
+
+/// GLSL name of an OpenGL type, like `vec2` for `GL_FLOAT_VEC2`,
+/// for displaying the types of attributes and uniforms.
+///
+/// Returns `None` for types without a name here, like double precision types.
+pub fn glsl_type_name(gl_type: u32) -> Option<&'static str> {
+    let name = match gl_type {
+        glow::FLOAT => "float",
+        glow::FLOAT_VEC2 => "vec2",
+        glow::FLOAT_VEC3 => "vec3",
+        glow::FLOAT_VEC4 => "vec4",
+        glow::INT => "int",
+        glow::INT_VEC2 => "ivec2",
+        glow::INT_VEC3 => "ivec3",
+        glow::INT_VEC4 => "ivec4",
+        glow::UNSIGNED_INT => "uint",
+        glow::UNSIGNED_INT_VEC2 => "uvec2",
+        glow::UNSIGNED_INT_VEC3 => "uvec3",
+        glow::UNSIGNED_INT_VEC4 => "uvec4",
+        glow::BOOL => "bool",
+        glow::FLOAT_MAT2 => "mat2",
+        glow::FLOAT_MAT3 => "mat3",
+        glow::FLOAT_MAT4 => "mat4",
+        glow::SAMPLER_2D => "sampler2D",
+        glow::SAMPLER_2D_ARRAY => "sampler2DArray",
+        glow::SAMPLER_CUBE => "samplerCube",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Number of components of an attribute's GLSL type.
+fn attribute_components(gl_type: u32) -> i32 {
+    match gl_type {
+        glow::FLOAT | glow::INT | glow::UNSIGNED_INT => 1,
+        glow::FLOAT_VEC2 | glow::INT_VEC2 | glow::UNSIGNED_INT_VEC2 => 2,
+        glow::FLOAT_VEC3 | glow::INT_VEC3 | glow::UNSIGNED_INT_VEC3 => 3,
+        glow::FLOAT_VEC4 | glow::INT_VEC4 | glow::UNSIGNED_INT_VEC4 => 4,
+        _ => 0,
+    }
+}
+
+/// Replaces each `#include "file"` line with the resolved content.
+///
+/// The stack holds the names of the files currently being expanded.
+fn expand_includes<F>(source: &str, resolver: &F, stack: &mut Vec<String>) -> errors::Result<String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::with_capacity(source.len());
+
+    for line in source.lines() {
+        match parse_include(line) {
+            Some(name) => {
+                if stack.iter().any(|other| other == name) {
+                    let mut chain = stack.clone();
+                    chain.push(name.to_owned());
+                    return Err(errors::Error::IncludeCycle(chain));
+                }
+
+                let content = resolver(name)
+                    .ok_or_else(|| errors::Error::IncludeNotFound(name.to_owned()))?;
+
+                stack.push(name.to_owned());
+                expanded.push_str(&expand_includes(&content, resolver, stack)?);
+                stack.pop();
+            }
+            None => {
+                expanded.push_str(line);
+                expanded.push('\n');
+            }
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// Name of the included file, when the line is an include directive.
+fn parse_include(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("#include")?.trim();
+    rest.strip_prefix('"')?.strip_suffix('"')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolve(name: &str) -> Option<String> {
+        match name {
+            "math.glsl" => {
+                Some("#include \"consts.glsl\"\nfloat sq(float x) { return x * x; }".to_owned())
+            }
+            "consts.glsl" => Some("const float PI = 3.14159;".to_owned()),
+            "a.glsl" => Some("#include \"b.glsl\"".to_owned()),
+            "b.glsl" => Some("#include \"a.glsl\"".to_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_includes() {
+        let source = "#version 330\n#include \"math.glsl\"\nvoid main() {}";
+        let expanded = expand_includes(source, &resolve, &mut vec![]).unwrap();
+        assert_eq!(
+            expanded,
+            "#version 330\nconst float PI = 3.14159;\nfloat sq(float x) { return x * x; }\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn test_glsl_types() {
+        // Symbols of the sprite shaders.
+        assert_eq!(glsl_type_name(glow::FLOAT_VEC2), Some("vec2"));
+        assert_eq!(glsl_type_name(glow::FLOAT_VEC4), Some("vec4"));
+        assert_eq!(glsl_type_name(glow::INT), Some("int"));
+        assert_eq!(glsl_type_name(glow::SAMPLER_2D), Some("sampler2D"));
+        assert_eq!(glsl_type_name(glow::DOUBLE), None);
+
+        assert_eq!(attribute_components(glow::FLOAT_VEC2), 2);
+        assert_eq!(attribute_components(glow::FLOAT_VEC4), 4);
+        assert_eq!(attribute_components(glow::FLOAT_MAT4), 0);
+    }
+
+    #[test]
+    fn test_shader_compile_display() {
+        let err = errors::Error::ShaderCompile(vec![
+            ShaderLog {
+                stage: ShaderStage::Vertex,
+                log: "0:12: 'vec' : undeclared identifier".to_owned(),
+            },
+            ShaderLog {
+                stage: ShaderStage::Fragment,
+                log: "0:7: 'Colour' : undeclared identifier".to_owned(),
+            },
+        ]);
+
+        let message = err.to_string();
+        assert!(message.contains("vertex shader:\n0:12: 'vec' : undeclared identifier"));
+        assert!(message.contains("fragment shader:\n0:7: 'Colour' : undeclared identifier"));
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let result = expand_includes("#include \"missing.glsl\"", &resolve, &mut vec![]);
+        assert!(
+            matches!(result, Err(errors::Error::IncludeNotFound(name)) if name == "missing.glsl")
+        );
+
+        let result = expand_includes("#include \"a.glsl\"", &resolve, &mut vec![]);
+        match result {
+            Err(errors::Error::IncludeCycle(chain)) => {
+                assert_eq!(chain, vec!["a.glsl", "b.glsl", "a.glsl"]);
+            }
+            _ => panic!("expected include cycle"),
+        }
     }
 }
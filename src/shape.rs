@@ -0,0 +1,191 @@
+//! Triangle geometry for simple shapes.
+//!
+//! Shapes are tessellated into untextured triangles with colour stored
+//! in the vertices. Texture coordinates are all zero, so the shapes
+//! can be drawn with any texture that is white at its origin.
+//!
+//! `SpriteBatch::draw_line_aa`, `draw_circle` and `draw_circle_filled`
+//! add the shapes to a sprite batch, which must draw `Primitive::Triangles`
+//! for them to appear filled.
+use crate::{color::Color, vertex::Vertex};
+use std::{convert::TryFrom, f32::consts::PI};
+
+/// Indexed triangle geometry.
+#[derive(Debug, Default, Clone)]
+pub struct Mesh {
+    pub vertices: Vec<Vertex>,
+    /// Triangle list, counter-clockwise in screen space.
+    pub indices: Vec<u16>,
+}
+
+impl Mesh {
+    fn push_vertex(&mut self, position: [f32; 2], color: Color) -> u16 {
+        let index = u16::try_from(self.vertices.len()).expect("mesh exceeds the u16 index range");
+        self.vertices.push(Vertex {
+            position,
+            uv: [0.0, 0.0],
+            color,
+        });
+        index
+    }
+
+    /// Two triangles covering the quad `a`, `b`, `c`, `d`.
+    fn push_quad_indices(&mut self, a: u16, b: u16, c: u16, d: u16) {
+        self.indices.extend_from_slice(&[a, b, c, a, c, d]);
+    }
+}
+
+/// Maximum number of segments circles are tessellated into, so their
+/// vertices can be addressed by `u16` indices.
+pub const MAX_SEGMENTS: u16 = u16::MAX / 2;
+
+/// Width in pixels of the transparent fringe added to antialiased edges.
+const FEATHER: f32 = 1.0;
+
+/// Line between two points, with antialiased edges.
+///
+/// The solid core of the line is bordered on both sides by a fringe
+/// that fades to transparent, which smooths the edges without
+/// multisampling. Produces 8 vertices.
+pub fn line_aa(from: [f32; 2], to: [f32; 2], width: f32, color: Color) -> Mesh {
+    let mut mesh = Mesh::default();
+
+    let [dx, dy] = [to[0] - from[0], to[1] - from[1]];
+    let len = (dx * dx + dy * dy).sqrt();
+    if len <= f32::EPSILON {
+        return mesh;
+    }
+
+    // Unit normal perpendicular to the line.
+    let [nx, ny] = [-dy / len, dx / len];
+    let core = width / 2.0;
+    let outer = core + FEATHER;
+    let transparent = [color[0], color[1], color[2], 0.0];
+
+    // Rows across the line, from one outer fringe to the other.
+    let rows = [
+        (outer, transparent),
+        (core, color),
+        (-core, color),
+        (-outer, transparent),
+    ];
+
+    for point in [from, to].iter() {
+        for (offset, row_color) in rows.iter() {
+            mesh.push_vertex([point[0] + nx * offset, point[1] + ny * offset], *row_color);
+        }
+    }
+
+    for row in 0..3 {
+        mesh.push_quad_indices(row, row + 4, row + 5, row + 1);
+    }
+
+    mesh
+}
+
+/// Outline of a circle, as a ring of the given thickness.
+///
+/// Produces `segments * 2` vertices, with `segments` clamped between 3
+/// and `MAX_SEGMENTS`.
+pub fn circle(center: [f32; 2], radius: f32, thickness: f32, segments: u16, color: Color) -> Mesh {
+    let mut mesh = Mesh::default();
+    let segments = segments.clamp(3, MAX_SEGMENTS);
+    let inner = (radius - thickness / 2.0).max(0.0);
+    let outer = radius + thickness / 2.0;
+
+    for i in 0..segments {
+        let [x, y] = rim_direction(i, segments);
+        mesh.push_vertex([center[0] + x * outer, center[1] + y * outer], color);
+        mesh.push_vertex([center[0] + x * inner, center[1] + y * inner], color);
+    }
+
+    for i in 0..segments {
+        let next = (i + 1) % segments;
+        mesh.push_quad_indices(i * 2, i * 2 + 1, next * 2 + 1, next * 2);
+    }
+
+    mesh
+}
+
+/// Filled circle, as a triangle fan around its center.
+///
+/// Produces `segments + 1` vertices, the first being the center, with
+/// `segments` clamped between 3 and `MAX_SEGMENTS`.
+pub fn circle_filled(center: [f32; 2], radius: f32, segments: u16, color: Color) -> Mesh {
+    let mut mesh = Mesh::default();
+    let segments = segments.clamp(3, MAX_SEGMENTS);
+
+    let center_index = mesh.push_vertex(center, color);
+    for i in 0..segments {
+        let [x, y] = rim_direction(i, segments);
+        mesh.push_vertex([center[0] + x * radius, center[1] + y * radius], color);
+    }
+
+    for i in 0..segments {
+        let next = (i + 1) % segments;
+        mesh.indices
+            .extend_from_slice(&[center_index, next + 1, i + 1]);
+    }
+
+    mesh
+}
+
+/// Unit vector pointing to the rim vertex at the given segment.
+fn rim_direction(segment: u16, segments: u16) -> [f32; 2] {
+    let angle = segment as f32 / segments as f32 * PI * 2.0;
+    [angle.cos(), angle.sin()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::WHITE;
+
+    #[test]
+    fn test_line_aa() {
+        let mesh = line_aa([0.0, 0.0], [10.0, 0.0], 2.0, WHITE);
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_eq!(mesh.indices.len(), 18);
+
+        // Outer fringe fades out.
+        assert_eq!(mesh.vertices[0].color[3], 0.0);
+        assert_eq!(mesh.vertices[1].color[3], 1.0);
+
+        // Degenerate lines produce nothing.
+        assert!(line_aa([1.0, 1.0], [1.0, 1.0], 2.0, WHITE)
+            .vertices
+            .is_empty());
+    }
+
+    #[test]
+    fn test_circle() {
+        let mesh = circle([0.0, 0.0], 10.0, 2.0, 16, WHITE);
+        assert_eq!(mesh.vertices.len(), 32);
+        assert_eq!(mesh.indices.len(), 16 * 6);
+    }
+
+    #[test]
+    fn test_circle_filled() {
+        let mesh = circle_filled([0.0, 0.0], 10.0, 16, WHITE);
+        assert_eq!(mesh.vertices.len(), 17);
+        assert_eq!(mesh.indices.len(), 16 * 3);
+
+        for vertex in &mesh.vertices[1..] {
+            let [x, y] = vertex.position;
+            assert!(((x * x + y * y).sqrt() - 10.0).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_max_segments() {
+        let outline = circle([0.0, 0.0], 10.0, 2.0, u16::MAX, WHITE);
+        assert_eq!(outline.vertices.len(), MAX_SEGMENTS as usize * 2);
+        let filled = circle_filled([0.0, 0.0], 10.0, u16::MAX, WHITE);
+        assert_eq!(filled.vertices.len(), MAX_SEGMENTS as usize + 1);
+
+        for mesh in &[outline, filled] {
+            let max = mesh.indices.iter().copied().max().unwrap();
+            assert_eq!(max as usize, mesh.vertices.len() - 1);
+        }
+    }
+}
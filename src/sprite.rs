@@ -1,5 +1,6 @@
 use crate::{
     device::GraphicDevice,
+    errors, primitives,
     texture::Texture,
     vertex::{Vertex, VertexBuffer},
 };
@@ -13,19 +14,24 @@ pub struct Sprite {
     pub(crate) texture: Option<Texture>,
 }
 
+/// Indices of the sprite's quad. See `primitives::SPRITE_WINDING`.
+pub(crate) fn quad_indices() -> [u16; 6] {
+    primitives::quad_indices_at(primitives::SPRITE_WINDING, 0)
+}
+
 impl Sprite {
-    pub fn with_size(device: &GraphicDevice, x: i32, y: i32, width: u32, height: u32) -> Self {
+    pub fn with_size(
+        device: &GraphicDevice,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    ) -> errors::Result<Self> {
         const WHITE: [f32; 4] = [1.0; 4];
 
         let [x, y] = [x as f32, y as f32];
         let [w, h] = [width as f32, height as f32];
 
-        // FIXME: This is counter-clockwise winding.
-        //        Since the shader is flipping the y-axis, and in the future
-        //        a camera matrix may as well, we are actually mirroring
-        //        the vertices and viewing the back.
-        //        Even though we don't do backface culling, this may or may not be
-        //        be ideal.
         let vertices = [
             Vertex {
                 position: [x, y],
@@ -49,15 +55,14 @@ impl Sprite {
             },
         ];
 
-        // Counter-clockwise
-        let indices = &[0, 1, 2, 0, 2, 3];
+        let indices = quad_indices();
 
-        Self {
+        Ok(Self {
             pos: [0, 0],
             size: [width, height],
-            vertex_buffer: VertexBuffer::new_static(device, &vertices, indices),
+            vertex_buffer: VertexBuffer::new_static(device, &vertices, &indices)?,
             texture: None,
-        }
+        })
     }
 
     pub fn set_texture(&mut self, texture: Texture) {
@@ -13,6 +13,21 @@ layout(location = 2) in vec4 a_Color;
 // This could be a matrix too.
 layout(location = 0) uniform vec2 u_Resolution;
 
+// Whether the y-axis is flipped to put the origin in the top-left.
+// The window framebuffer needs flipping, off-screen targets don't.
+layout(location = 2) uniform int u_FlipY;
+
+// Whether vertex colours are sRGB encoded, and converted to linear
+// before being interpolated.
+layout(location = 4) uniform int u_LinearColor;
+
+// Same conversion as `color::srgb_to_linear`.
+vec3 srgb_to_linear(vec3 c) {
+    vec3 low = c / 12.92;
+    vec3 high = pow((c + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(c, vec3(0.04045)));
+}
+
 // Varyings are values sent from the vertex shader to
 // the fragment shader. The value that reaches the fragment
 // shader is interpolated between the vertices.
@@ -31,8 +46,9 @@ void main() {
 
     // In clip space the bottom left corner is -1,-1.
     // To get a traditional 2D pixel space where 0,0 is top left, we flip the y.
-    gl_Position = vec4(pos * vec2(1, -1), 0.0, 1.0);
+    float flip = u_FlipY != 0 ? -1.0 : 1.0;
+    gl_Position = vec4(pos * vec2(1, flip), 0.0, 1.0);
 
-    v_Color = a_Color;
+    v_Color = u_LinearColor != 0 ? vec4(srgb_to_linear(a_Color.rgb), a_Color.a) : a_Color;
     v_TexCoord = a_UV;
 }
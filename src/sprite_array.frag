@@ -0,0 +1,19 @@
+#version 410
+#extension GL_ARB_explicit_uniform_location : enable
+
+precision highp float;
+
+layout(location = 1) uniform sampler2DArray u_Albedo;
+
+// Layer of the texture array to sample.
+layout(location = 3) uniform int u_Layer;
+
+// Varyings
+in vec4 v_Color;
+in vec2 v_TexCoord;
+
+out vec4 Color;
+
+void main() {
+    Color = v_Color * texture(u_Albedo, vec3(v_TexCoord, u_Layer));
+}
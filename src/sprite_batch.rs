@@ -1,20 +1,88 @@
 use crate::{
+    color::{self, Color},
     device::GraphicDevice,
-    errors::debug_assert_gl,
+    dpi::PhysicalSize,
+    errors::{self, debug_assert_gl},
+    primitives::{self, Primitive},
+    rect::Rect,
+    sampler::Sampler,
     shader::Shader,
+    shape::{self, Mesh},
     texture::Texture,
+    texture_array::TextureArray,
     utils,
-    vertex::{Vertex, VertexBuffer},
+    vertex::{BatchVertex, IndexType, Vertex, VertexBuffer},
 };
 use glow::HasContext;
-use glutin::dpi::PhysicalSize;
-use std::rc::Rc;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    ops::{Bound, Deref, DerefMut, Range, RangeBounds},
+    rc::{Rc, Weak},
+};
 
-pub struct SpriteBatch {
+/// Batches sprites into as few draw calls as possible.
+///
+/// The vertex type defaults to `Vertex`. Batches of custom vertex
+/// types, created with `new_custom`, fill in their extra attributes
+/// with the callback set by `set_corner_callback`.
+pub struct SpriteBatch<V: BatchVertex = Vertex> {
     items: Vec<BatchItem>,
-    vertices: Vec<Vertex>,
+    /// When retained, items are kept after drawing.
+    retained: bool,
+    vertices: Vec<V>,
     indices: Vec<u16>,
     vertex_buffer: VertexBuffer,
+    sampler: Option<Rc<Sampler>>,
+    /// Extra textures bound to units other than the sprite texture's.
+    aux_textures: Vec<AuxTexture>,
+    /// Sprites outside this rectangle are skipped when added.
+    cull_rect: Option<Rect<f32>>,
+    /// Number of sprites culled since the last draw.
+    culled: usize,
+    stats: BatchStats,
+    /// Number of flushes in a single draw above which a warning is logged.
+    flush_warn_threshold: Option<usize>,
+    /// Orders the items before drawing. Submission order when `None`.
+    sort_key: Option<Box<SortKey>>,
+    /// Indices of the drawn items in drawing order, reused across draws.
+    order: Vec<usize>,
+    /// When set, sprites sample a layer of this array instead of their own texture.
+    texture_array: Option<Rc<TextureArray>>,
+    /// Texture unit the sprite texture is bound to.
+    texture_unit: u32,
+    /// Round axis-aligned sprites to whole pixels.
+    pixel_snap: bool,
+    /// Fills in the custom attributes of each corner's vertex.
+    corner_fn: Option<Box<CornerFn<V>>>,
+    /// Check the shader's attributes against the vertex layout when drawing.
+    validate_layout: bool,
+    /// Corner of the target where sprite coordinates start.
+    origin: Origin,
+    /// Convert sRGB vertex colours to linear before interpolation.
+    linear_color: bool,
+    /// How the corners of each sprite are assembled when drawn.
+    primitive: Primitive,
+}
+
+/// Function computing the key a batch item is sorted by.
+type SortKey = dyn Fn(&BatchItem) -> u64;
+
+/// Function filling in the vertex of a batch item's corner, given
+/// its index in the order top-left, top-right, bottom-right, bottom-left.
+type CornerFn<V> = dyn Fn(&BatchItem, usize, &mut V);
+
+/// Function called each time a new texture is bound while drawing.
+type DrawCallback<'a> = dyn FnMut(&GraphicDevice, &Shader, Texture) + 'a;
+
+/// Counters describing the last draw of a sprite batch.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BatchStats {
+    /// Number of sprites skipped by the cull rectangle.
+    pub culled: usize,
+    /// Number of draw calls issued. A new draw call is needed each
+    /// time the texture changes, or the batch size is reached.
+    pub flushes: usize,
 }
 
 impl SpriteBatch {
@@ -25,59 +93,538 @@ impl SpriteBatch {
     pub const BATCH_SIZE: usize = 2048;
     // pub const BATCH_SIZE: usize = 512;
 
-    pub fn new(device: &GraphicDevice) -> Self {
-        // 4 vertices per sprite
-        let vertices = (0..Self::BATCH_SIZE * 4)
-            .map(|_| Vertex {
-                position: [0.0, 0.0],
-                uv: [0.0, 0.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            })
-            .collect::<Vec<_>>();
+    /// Texture unit the palette is bound to by `set_palette`.
+    pub const PALETTE_UNIT: u32 = 1;
 
-        // 2 triangles, 6 indices per sprite
-        let mut indices: Vec<u16> = vec![];
-        for i in 0..Self::BATCH_SIZE as u16 {
-            indices.push(i);
-            indices.push(i + 1);
-            indices.push(i + 2);
+    pub fn new(device: &GraphicDevice) -> errors::Result<Self> {
+        Ok(Self::with_vertex_buffer(Self::create_vertex_buffer(
+            device,
+        )?))
+    }
 
-            indices.push(i);
-            indices.push(i + 2);
-            indices.push(i + 3);
-        }
+    /// Allocates a vertex buffer in video memory large enough to hold a full batch.
+    pub(crate) fn create_vertex_buffer(device: &GraphicDevice) -> errors::Result<VertexBuffer> {
+        create_batch_vertex_buffer::<Vertex>(device)
+    }
+}
 
+impl<V: BatchVertex> SpriteBatch<V> {
+    /// Create a batch of a custom vertex type.
+    ///
+    /// The vertex buffer's layout includes the type's extra attributes.
+    pub fn new_custom(device: &GraphicDevice) -> errors::Result<Self> {
+        Ok(Self::with_vertex_buffer(create_batch_vertex_buffer::<V>(
+            device,
+        )?))
+    }
+
+    pub(crate) fn with_vertex_buffer(vertex_buffer: VertexBuffer) -> Self {
         Self {
-            items: Vec::with_capacity(Self::BATCH_SIZE),
-            vertices: Vec::with_capacity(Self::BATCH_SIZE * 4),
-            indices: Vec::with_capacity(Self::BATCH_SIZE * 6),
-            vertex_buffer: VertexBuffer::new_static(device, &vertices, &indices),
+            items: Vec::with_capacity(SpriteBatch::BATCH_SIZE),
+            retained: false,
+            vertices: Vec::with_capacity(SpriteBatch::BATCH_SIZE * 4),
+            indices: Vec::with_capacity(SpriteBatch::BATCH_SIZE * 6),
+            vertex_buffer,
+            sampler: None,
+            aux_textures: vec![],
+            cull_rect: None,
+            culled: 0,
+            stats: BatchStats::default(),
+            flush_warn_threshold: None,
+            sort_key: None,
+            order: Vec::with_capacity(SpriteBatch::BATCH_SIZE),
+            texture_array: None,
+            texture_unit: 0,
+            pixel_snap: false,
+            corner_fn: None,
+            validate_layout: false,
+            origin: Origin::TopLeft,
+            linear_color: false,
+            primitive: Primitive::Triangles,
+        }
+    }
+
+    /// Set the callback filling in the custom attributes of each
+    /// corner's vertex while drawing.
+    ///
+    /// The callback is given the batch item and the corner's index, in
+    /// the order top-left, top-right, bottom-right, bottom-left, and may
+    /// adjust any field of the vertex already built from the sprite.
+    pub fn set_corner_callback<F>(&mut self, corner_fn: F)
+    where
+        F: Fn(&BatchItem, usize, &mut V) + 'static,
+    {
+        self.corner_fn = Some(Box::new(corner_fn));
+    }
+
+    pub fn clear_corner_callback(&mut self) {
+        self.corner_fn = None;
+    }
+
+    /// Set the corner of the target where sprite coordinates start,
+    /// with y pointing away from it. Defaults to `Origin::TopLeft`.
+    ///
+    /// Sets the sprite shader's `u_FlipY` uniform, so the same shader
+    /// works for both conventions. With `Origin::BottomLeft`, textures
+    /// uploaded with their first row at the top are drawn upside down,
+    /// as in plain OpenGL, and screen anchors still assume a top-left
+    /// origin.
+    pub fn set_origin(&mut self, origin: Origin) {
+        self.origin = origin;
+    }
+
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    /// Treat vertex colours as sRGB, converting them to linear in the
+    /// vertex shader so gradients between corners are interpolated in
+    /// linear space. Sets the sprite shader's `u_LinearColor` uniform,
+    /// at location 4. Off by default.
+    ///
+    /// Meant for an sRGB framebuffer, where `GL_FRAMEBUFFER_SRGB` encodes
+    /// the linear output back to sRGB. Without one, linear colours are
+    /// written as is, and tinted sprites appear darker.
+    pub fn set_linear_color(&mut self, linear_color: bool) {
+        self.linear_color = linear_color;
+    }
+
+    /// Check that the attributes of the shader passed to each draw match
+    /// the batch's vertex layout, logging an error when they don't.
+    ///
+    /// Queries the shader on every draw, so is only done in debug builds,
+    /// and is off by default. See `VertexBuffer::validate_layout`.
+    pub fn set_validate_layout(&mut self, validate_layout: bool) {
+        self.validate_layout = validate_layout;
+    }
+
+    /// Set the sampler bound to the sprite texture's unit while drawing.
+    ///
+    /// When set, the sampler's filtering and wrapping override the
+    /// parameters of each drawn texture. `None` uses the textures' own
+    /// parameters.
+    pub fn set_sampler(&mut self, sampler: Option<Rc<Sampler>>) {
+        self.sampler = sampler;
+    }
+
+    /// Bind an additional texture to the given texture unit while drawing.
+    ///
+    /// Used by shaders that sample more than one texture, like a normal
+    /// map or palette lookup. The sampler uniform with the given name is
+    /// set to the unit. Replaces any texture previously set on the unit.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidTextureUnit` if the unit is not below the device's
+    /// `max_texture_units`.
+    ///
+    /// # Panics
+    ///
+    /// The sprite texture's unit, 0 by default, is reserved.
+    pub fn set_auxiliary_texture(
+        &mut self,
+        device: &GraphicDevice,
+        unit: u32,
+        uniform_name: &str,
+        texture: Texture,
+    ) -> errors::Result<()> {
+        validate_texture_unit(unit, device.max_texture_units())?;
+        self.insert_auxiliary_texture(unit, uniform_name, texture);
+        Ok(())
+    }
+
+    fn insert_auxiliary_texture(&mut self, unit: u32, uniform_name: &str, texture: Texture) {
+        assert!(
+            unit != self.texture_unit,
+            "Texture unit {} is reserved for the sprite texture.",
+            unit
+        );
+
+        self.aux_textures.retain(|aux| aux.unit != unit);
+        self.aux_textures.push(AuxTexture {
+            unit,
+            uniform_name: uniform_name.to_owned(),
+            texture,
+        });
+    }
+
+    /// Set the texture unit the sprite texture is bound to while drawing.
+    ///
+    /// The sprite shader's `u_Albedo` sampler uniform is set to the unit,
+    /// and the sampler set by `set_sampler` is bound to it. Defaults to 0.
+    ///
+    /// # Panics
+    ///
+    /// When an auxiliary texture is already set on the unit.
+    pub fn set_texture_unit(&mut self, unit: u32) {
+        assert!(
+            self.aux_textures.iter().all(|aux| aux.unit != unit),
+            "Texture unit {} is used by an auxiliary texture.",
+            unit
+        );
+
+        self.texture_unit = unit;
+    }
+
+    pub fn texture_unit(&self) -> u32 {
+        self.texture_unit
+    }
+
+    /// Set whether sprite corners are rounded to whole pixels when drawn.
+    ///
+    /// Keeps pixel art crisp when the camera or sprites move by fractional
+    /// amounts. Only axis-aligned sprites are snapped; rotated and skewed
+    /// quads are drawn as is. Off by default.
+    pub fn set_pixel_snap(&mut self, pixel_snap: bool) {
+        self.pixel_snap = pixel_snap;
+    }
+
+    /// Set how the corners of each sprite are assembled when drawn.
+    ///
+    /// `Lines` draws the outline of each sprite, and `Points` its
+    /// corners, which is useful for debug drawing. `TriangleStrip`
+    /// joins the sprites with degenerate triangles. Defaults to
+    /// `Triangles`.
+    ///
+    /// Shapes, like those added by `draw_line_aa` and `draw_circle`, are
+    /// tessellated into triangles, so `Lines` draws the edges of every
+    /// triangle instead of the shape's outline.
+    pub fn set_primitive(&mut self, primitive: Primitive) {
+        self.primitive = primitive;
+    }
+
+    pub fn primitive(&self) -> Primitive {
+        self.primitive
+    }
+
+    /// Set the palette looked up by the indexed colour sprite shader.
+    ///
+    /// The palette is a 256x1 texture, bound to `PALETTE_UNIT` and the
+    /// `u_Palette` sampler uniform. Draw with the `sprite_palette.frag`
+    /// shader, which uses the red channel of each sprite's texture as an
+    /// index into the palette. Swapping the palette recolours sprites
+    /// without touching their textures.
+    pub fn set_palette(&mut self, palette: Texture) {
+        // OpenGL guarantees at least 16 units, so no need to validate.
+        self.insert_auxiliary_texture(SpriteBatch::PALETTE_UNIT, "u_Palette", palette);
+    }
+
+    /// Remove all auxiliary textures.
+    pub fn clear_auxiliary_textures(&mut self) {
+        self.aux_textures.clear();
+    }
+
+    /// Set whether the batch keeps its sprites after drawing.
+    ///
+    /// By default the batch is in immediate mode, where `draw` consumes
+    /// the added sprites, and they must be added again each frame.
+    ///
+    /// In retained mode the sprites persist across draws, so a mostly
+    /// static scene can be drawn every frame without rebuilding the batch.
+    /// The same sprites can also be drawn to several targets, see `draw_to_target`.
+    /// Use `clear` to remove them.
+    pub fn set_retained(&mut self, retained: bool) {
+        self.retained = retained;
+    }
+
+    pub fn is_retained(&self) -> bool {
+        self.retained
+    }
+
+    /// Remove all sprites added to the batch.
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Set a rectangle, usually the visible area, outside of which
+    /// sprites are skipped by `add`.
+    ///
+    /// Avoids building vertices for sprites that won't be visible.
+    /// `None` disables culling, which is the default.
+    pub fn set_cull_rect(&mut self, cull_rect: Option<Rect<f32>>) {
+        self.cull_rect = cull_rect;
+    }
+
+    pub fn cull_rect(&self) -> Option<Rect<f32>> {
+        self.cull_rect
+    }
+
+    /// Set a number of flushes in a single draw, above which a warning
+    /// is logged with the actual count.
+    ///
+    /// Every texture change flushes the batch, so many flushes usually
+    /// point to unsorted sprites or textures that should be packed into
+    /// an atlas. `None` disables the warning, which is the default.
+    pub fn set_flush_warn_threshold(&mut self, threshold: Option<usize>) {
+        self.flush_warn_threshold = threshold;
+    }
+
+    /// Set a function computing the key items are sorted by before drawing,
+    /// for example by depth or material.
+    ///
+    /// The sort is stable, so items with equal keys keep their submission
+    /// order. Sprites are drawn in submission order by default. Only the
+    /// drawing order changes, so a retained batch keeps its items in the
+    /// order they were added.
+    pub fn set_sort_key<F>(&mut self, sort_key: F)
+    where
+        F: Fn(&BatchItem) -> u64 + 'static,
+    {
+        self.sort_key = Some(Box::new(sort_key));
+    }
+
+    /// Draw sprites in submission order.
+    pub fn clear_sort_key(&mut self) {
+        self.sort_key = None;
+    }
+
+    /// Set a texture array that all sprites sample from, instead of their
+    /// own textures.
+    ///
+    /// Each sprite selects a layer with `Sprite::set_layer`. Draw with the
+    /// `sprite_array.frag` shader, which samples the array bound to the sprite
+    /// texture's unit, at the layer given by the `u_Layer` uniform. The batch
+    /// is flushed when the layer changes, so sprites sharing a frame should
+    /// be added together. `None` goes back to per-sprite textures.
+    pub fn set_texture_array(&mut self, texture_array: Option<Rc<TextureArray>>) {
+        self.texture_array = texture_array;
+    }
+
+    /// Counters collected during the last draw.
+    pub fn stats(&self) -> BatchStats {
+        self.stats
+    }
+
+    /// Number of sprites waiting to be drawn, or kept by a retained batch.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Add a slice of sprites, reserving space for all of them at once.
+    ///
+    /// Sprites are culled as with `add`.
+    pub fn add_many(&mut self, sprites: &[Sprite]) {
+        self.items.reserve(sprites.len());
+        for sprite in sprites {
+            self.add(sprite);
+        }
+    }
+
+    /// Add sprites built on the fly, like particles, without collecting
+    /// them first. Space is reserved for the iterator's lower size bound.
+    pub fn add_from_iter<I>(&mut self, sprites: I)
+    where
+        I: IntoIterator<Item = Sprite>,
+    {
+        let sprites = sprites.into_iter();
+        self.items.reserve(sprites.size_hint().0);
+        for sprite in sprites {
+            self.add(&sprite);
         }
     }
 
     pub fn add(&mut self, sprite: &Sprite) {
+        if sprite.is_clipped_out() || sprite.texture_cleared {
+            return;
+        }
+
+        // The position of anchored sprites is only known when drawn.
+        let absolute = sprite.screen_anchor == ScreenAnchor::TopLeft;
+
+        if let Some(cull_rect) = self.cull_rect.as_ref().filter(|_| absolute) {
+            if !cull_rect.intersects(&sprite.bounds()) {
+                self.culled += 1;
+                return;
+            }
+        }
+
         // Copies stuff needed for drawing to the internal batch item buffer.
-        // Sprites without textures are not drawn anyway.
-        if let Some(texture) = sprite.texture.as_ref() {
-            let [x, y] = [sprite.pos[0] as f32, sprite.pos[1] as f32];
-            let [w, h] = [sprite.size[0] as f32, sprite.size[1] as f32];
+        // Sprites without textures are drawn as solid rectangles of their colour.
+        self.items.push(BatchItem {
+            corners: sprite.corners(),
+            uv_rect: sprite.uv_rect(),
+            color: sprite.color,
+            texture: sprite.texture.clone(),
+            screen_anchor: sprite.screen_anchor,
+            layer: sprite.layer,
+            corner_colors: None,
+        });
+    }
+
+    /// Add a textured quad with arbitrary corner positions.
+    ///
+    /// Unlike `add`, the quad is not assumed to be an axis-aligned
+    /// rectangle, which allows for skewed and trapezoid shapes.
+    ///
+    /// Corners are given in the order top-left, top-right, bottom-right
+    /// and bottom-left, and are mapped to the corresponding corners of
+    /// the normalised `uv_rect`.
+    pub fn add_quad(
+        &mut self,
+        corners: [[f32; 2]; 4],
+        texture: &Texture,
+        uv_rect: Rect<f32>,
+        color: Color,
+    ) {
+        self.items.push(BatchItem {
+            corners,
+            uv_rect,
+            color,
+            texture: Some(texture.clone()),
+            screen_anchor: ScreenAnchor::TopLeft,
+            layer: 0,
+            corner_colors: None,
+        });
+    }
+
+    /// Add a line between two points, with antialiased edges.
+    ///
+    /// Like sprites without a texture, the line is drawn with the white
+    /// texture, so only its colour shows. See `shape::line_aa`.
+    pub fn draw_line_aa(&mut self, from: [f32; 2], to: [f32; 2], width: f32, color: Color) {
+        self.add_mesh(&shape::line_aa(from, to, width, color));
+    }
+
+    /// Add the outline of a circle, one pixel thick, tessellated into
+    /// the given number of segments. See `shape::circle`.
+    pub fn draw_circle(&mut self, center: [f32; 2], radius: f32, segments: u16, color: Color) {
+        self.add_mesh(&shape::circle(center, radius, 1.0, segments, color));
+    }
+
+    /// Add a filled circle, tessellated into the given number of
+    /// segments. See `shape::circle_filled`.
+    pub fn draw_circle_filled(
+        &mut self,
+        center: [f32; 2],
+        radius: f32,
+        segments: u16,
+        color: Color,
+    ) {
+        self.add_mesh(&shape::circle_filled(center, radius, segments, color));
+    }
 
+    /// Add each triangle of the mesh as a quad with its last corner
+    /// repeated, so the second triangle of the quad is empty.
+    fn add_mesh(&mut self, mesh: &Mesh) {
+        self.items.reserve(mesh.indices.len() / 3);
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [
+                &mesh.vertices[triangle[0] as usize],
+                &mesh.vertices[triangle[1] as usize],
+                &mesh.vertices[triangle[2] as usize],
+            ];
             self.items.push(BatchItem {
-                pos: [x, y],
-                size: [w, h],
-                texture: texture.clone(),
+                corners: [a.position, b.position, c.position, c.position],
+                uv_rect: Rect {
+                    pos: [0.0, 0.0],
+                    size: [0.0, 0.0],
+                },
+                color: a.color,
+                texture: None,
+                screen_anchor: ScreenAnchor::TopLeft,
+                layer: 0,
+                corner_colors: Some([a.color, b.color, c.color, c.color]),
             });
         }
     }
 
     pub fn draw(&mut self, device: &GraphicDevice, shader: &Shader) {
+        self.draw_internal(device, shader, viewport_target(device), .., None)
+    }
+
+    /// Draw the batch to the currently bound target, which has
+    /// the given size in pixels.
+    ///
+    /// The viewport, resolution uniform and screen anchors are computed
+    /// from the target size instead of the device's viewport size. A
+    /// retained batch can be drawn to several targets, like a mirror
+    /// or minimap, without adding the sprites again.
+    pub fn draw_to_target(
+        &mut self,
+        device: &GraphicDevice,
+        shader: &Shader,
+        target_size: PhysicalSize<u32>,
+    ) {
+        self.draw_internal(device, shader, target_size, .., None)
+    }
+
+    /// Submit the sprites added so far, so custom drawing can be
+    /// interleaved between groups of sprites.
+    ///
+    /// Resets the batch's internal buffers afterwards, even when retained,
+    /// so sprites added after the flush are drawn by the next submission.
+    pub fn flush_now(&mut self, device: &GraphicDevice, shader: &Shader) {
+        self.draw_internal(device, shader, viewport_target(device), .., None);
+        self.items.clear();
+    }
+
+    /// Draw the batch, invoking the given callback each time a new
+    /// texture is bound.
+    ///
+    /// The callback is called after the texture is bound to the sprite
+    /// shader's texture slot, and before the sprites using it are drawn.
+    /// This allows extra uniforms, like per-texture parameters, to be set.
+    pub fn draw_with_callback(
+        &mut self,
+        device: &GraphicDevice,
+        shader: &Shader,
+        callback: &mut DrawCallback,
+    ) {
+        self.draw_internal(device, shader, viewport_target(device), .., Some(callback))
+    }
+
+    /// Draw only the sprites in the range, by the order they were added.
+    ///
+    /// Intended for retained batches, to submit part of the accumulated
+    /// sprites, like for culling or level of detail. The sort key only
+    /// orders the sprites within the range. The range is clamped to the
+    /// sprites in the batch.
+    ///
+    /// In immediate mode every sprite is still consumed.
+    pub fn draw_range(&mut self, device: &GraphicDevice, shader: &Shader, range: Range<usize>) {
+        self.draw_internal(device, shader, viewport_target(device), range, None)
+    }
+
+    fn draw_internal(
+        &mut self,
+        device: &GraphicDevice,
+        shader: &Shader,
+        target_size: PhysicalSize<u32>,
+        range: impl RangeBounds<usize>,
+        mut callback: Option<&mut DrawCallback>,
+    ) {
+        self.stats = BatchStats {
+            culled: self.culled,
+            flushes: 0,
+        };
+        self.culled = 0;
+
         // Nothing to draw.
         if self.items.is_empty() {
             return;
         }
 
+        // Handles are invalid after the context is lost, and
+        // textures may already be deleted while shutting down.
+        // Nothing is visible in an empty target, like a minimized window.
+        if !device.is_context_valid()
+            || device.is_shutting_down()
+            || target_size.width == 0
+            || target_size.height == 0
+        {
+            if !self.retained {
+                self.items.clear();
+            }
+            return;
+        }
+
         unsafe {
-            let canvas_size = device.get_viewport_size();
+            let canvas_size = target_size;
 
             let physical_size_i32 = canvas_size.cast::<i32>();
             device
@@ -92,86 +639,155 @@ impl SpriteBatch {
                 canvas_size.width as f32,
                 canvas_size.height as f32,
             );
+            device
+                .gl
+                .uniform_1_i32(Some(&2), self.origin.flip_y(device.get_flip_y()) as i32);
+            device.gl.uniform_1_i32(Some(&1), self.texture_unit as i32);
+            device
+                .gl
+                .uniform_1_i32(Some(&LINEAR_COLOR_LOC), self.linear_color as i32);
+        }
+
+        if cfg!(debug_assertions) && self.validate_layout {
+            if let Err(err) = self.vertex_buffer.validate_layout(device, shader) {
+                error!(
+                    "Sprite batch shader does not match its vertex layout: {}",
+                    err
+                );
+            }
         }
 
         unsafe {
             device.gl.bind_vertex_array(Some(self.vertex_buffer.vbo));
         }
 
+        if let Some(sampler) = self.sampler.as_ref() {
+            sampler.bind(device, self.texture_unit);
+        }
+
+        for aux in &self.aux_textures {
+            unsafe {
+                let location = device
+                    .gl
+                    .get_uniform_location(shader.program, &aux.uniform_name);
+                device.gl.uniform_1_i32(location.as_ref(), aux.unit as i32);
+
+                device.gl.active_texture(texture_slot(aux.unit));
+                device
+                    .gl
+                    .bind_texture(glow::TEXTURE_2D, Some(aux.texture.raw_handle()));
+                debug_assert_gl(&device.gl, ());
+            }
+        }
+
         let SpriteBatch {
             items,
+            retained,
             vertices,
             indices,
             vertex_buffer,
+            sampler,
+            aux_textures,
+            flush_warn_threshold,
+            stats,
+            sort_key,
+            order,
+            texture_array,
+            texture_unit,
+            pixel_snap,
+            corner_fn,
+            primitive,
+            ..
         } = self;
 
+        let range = clamp_range(range, items.len());
+        let items_in_range = &items[range];
+        sort_order(order, items_in_range, sort_key.as_deref());
+
+        let viewport_size = [target_size.width as f32, target_size.height as f32];
+
+        // Sprites without textures are drawn with a plain white texture,
+        // so only their vertex colour shows.
+        let white_texture = if texture_array.is_none()
+            && items_in_range.iter().any(|item| item.texture.is_none())
+        {
+            device.white_texture().ok()
+        } else {
+            None
+        };
+
+        if let Some(array) = texture_array.as_ref() {
+            unsafe {
+                device.gl.active_texture(texture_slot(*texture_unit));
+                device
+                    .gl
+                    .bind_texture(glow::TEXTURE_2D_ARRAY, Some(array.raw_handle()));
+            }
+        }
+
         let mut batch_count = 0;
         let mut last_texture = None;
 
-        for item in items.drain(..) {
+        for item in order.iter().map(|index| &items_in_range[*index]) {
             // println!("### BATCH {} ###", batch_count);
 
-            if batch_count >= Self::BATCH_SIZE {
-                Self::flush(device, vertex_buffer, &vertices, &indices);
+            // Sprites sampling a texture array are grouped by layer instead.
+            let (key, texture) = match texture_array.as_ref() {
+                Some(array) => (array.sort_key(item.layer), None),
+                None => match item.texture.as_ref().or(white_texture.as_ref()) {
+                    Some(texture) => (texture.sort_key(), Some(texture)),
+                    None => continue,
+                },
+            };
+
+            if batch_count >= SpriteBatch::BATCH_SIZE {
+                if Self::flush(device, vertex_buffer, *primitive, &vertices, &indices) {
+                    stats.flushes += 1;
+                }
                 vertices.clear();
                 indices.clear();
                 batch_count = 0;
             }
 
             // The buffer is flushed each time we encounter a new texture.
-            if last_texture != Some(item.texture.raw_handle()) {
-                Self::flush(device, vertex_buffer, &vertices, &indices);
+            if last_texture != Some(key) {
+                if Self::flush(device, vertex_buffer, *primitive, &vertices, &indices) {
+                    stats.flushes += 1;
+                }
                 vertices.clear();
                 indices.clear();
                 batch_count = 0;
-                last_texture = Some(item.texture.raw_handle());
-                unsafe {
-                    // Texture slot determined by sprite shader.
-                    device.gl.active_texture(glow::TEXTURE0);
-                    device
-                        .gl
-                        .bind_texture(glow::TEXTURE_2D, Some(item.texture.raw_handle()));
+                last_texture = Some(key);
+
+                match texture {
+                    Some(texture) => {
+                        unsafe {
+                            device.gl.active_texture(texture_slot(*texture_unit));
+                            device
+                                .gl
+                                .bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+                        }
+
+                        if let Some(callback) = callback.as_mut() {
+                            callback(device, shader, texture.clone());
+                        }
+                    }
+                    None => unsafe {
+                        device.gl.uniform_1_i32(Some(&LAYER_LOC), item.layer as i32);
+                    },
                 }
             }
 
-            let BatchItem {
-                pos: [x, y],
-                size: [w, h],
-                ..
-            } = item;
-            // println!("{:?} {:?}", [x, y], [w, h]);
-
             // Build vertices from sprite parameters.
             // TODO: scale UVs according to texture sub rectangle.
-            vertices.push(Vertex {
-                position: [x, y],
-                uv: [0.0, 0.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            });
-            vertices.push(Vertex {
-                position: [x + w, y],
-                uv: [1.0, 0.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            });
-            vertices.push(Vertex {
-                position: [x + w, y + h],
-                uv: [1.0, 1.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            });
-            vertices.push(Vertex {
-                position: [x, y + h],
-                uv: [0.0, 1.0],
-                color: [1.0, 1.0, 1.0, 1.0],
-            });
+            let mut corners = anchored_corners(item.corners, item.screen_anchor, viewport_size);
+            if *pixel_snap {
+                corners = snap_corners(corners);
+            }
+            vertices.extend_from_slice(&item_vertices(corners, item, corner_fn.as_deref()));
             // println!("{:?}", &vertices[vertices.len() - 4..vertices.len()]);
 
-            let i = batch_count as u16 * 4;
-            indices.push(i);
-            indices.push(i + 1);
-            indices.push(i + 2);
-            indices.push(i + 0);
-            indices.push(i + 2);
-            indices.push(i + 3);
+            extend_quad_indices(indices, *primitive, batch_count as u16);
             // println!("{:?}", &indices[indices.len() - 6..indices.len()]);
 
             batch_count += 1;
@@ -179,32 +795,71 @@ impl SpriteBatch {
 
         // Flush the last sprites that didn't reach the threshold.
         if batch_count > 0 {
-            Self::flush(device, vertex_buffer, &vertices, &indices);
+            if Self::flush(device, vertex_buffer, *primitive, &vertices, &indices) {
+                stats.flushes += 1;
+            }
             vertices.clear();
             indices.clear();
             batch_count = 0;
         }
 
+        if let Some(threshold) = *flush_warn_threshold {
+            if stats.flushes > threshold {
+                warn!(
+                    "Sprite batch flushed {} times in one draw, exceeding the threshold of {}. Consider sorting sprites by texture, or packing textures into an atlas.",
+                    stats.flushes, threshold
+                );
+            }
+        }
+
+        // Immediate mode consumes the sprites.
+        if !*retained {
+            items.clear();
+        }
+
+        if sampler.is_some() {
+            Sampler::unbind(device, *texture_unit);
+        }
+
+        for aux in aux_textures.iter() {
+            unsafe {
+                device.gl.active_texture(texture_slot(aux.unit));
+                device.gl.bind_texture(glow::TEXTURE_2D, None);
+            }
+        }
+
         unsafe {
+            device.gl.active_texture(texture_slot(*texture_unit));
             device.gl.bind_texture(glow::TEXTURE_2D, None);
+            if texture_array.is_some() {
+                device.gl.bind_texture(glow::TEXTURE_2D_ARRAY, None);
+            }
+            // Leave the default unit active.
+            device.gl.active_texture(glow::TEXTURE0);
             device.gl.bind_vertex_array(None);
             device.gl.use_program(None);
         }
     }
 
     /// this is where the actual drawing will happen.
+    ///
+    /// Returns `true` when a draw call was issued.
     fn flush(
         device: &GraphicDevice,
         vertex_buf: &VertexBuffer,
-        vertices: &[Vertex],
+        primitive: Primitive,
+        vertices: &[V],
         indices: &[u16],
-    ) {
+    ) -> bool {
         if vertices.is_empty() {
             // Nothing to draw
-            return;
+            return false;
         }
 
-        debug_assert!(vertices.len() / 4 == indices.len() / 6);
+        // Sprites are quads of two triangles.
+        debug_assert!(primitive != Primitive::Triangles || vertices.len() / 4 == indices.len() / 6);
+        // The buffer draws with its own index type, which must match the uploaded indices.
+        debug_assert_eq!(vertex_buf.index_type(), IndexType::U16);
 
         unsafe {
             // Upload new data.
@@ -226,14 +881,94 @@ impl SpriteBatch {
             );
             debug_assert_gl(&device.gl, ());
 
-            // FIXME: Unsigned short is a detail of the vertex buffer, so drawing should probably happen there.
-            device.gl.draw_elements(
-                glow::TRIANGLES,
-                indices.len() as i32,
-                glow::UNSIGNED_SHORT,
-                0,
-            );
-            debug_assert_gl(&device.gl, ());
+            vertex_buf.draw_primitives(device, primitive, 0, indices.len(), 0);
+        }
+
+        true
+    }
+}
+
+/// Recycles the video memory of transient sprite batches.
+///
+/// Each `SpriteBatch` allocates a vertex buffer large enough to hold
+/// `BATCH_SIZE` sprites. Creating many batches per frame, for example
+/// one per layer, would allocate and deallocate those buffers over and
+/// over. Batches acquired from the pool instead return their vertex
+/// buffer to the pool when dropped, to be reused by the next acquire.
+pub struct BatchPool {
+    /// Vertex buffers released by dropped batches, oldest first.
+    queue: Rc<RefCell<VecDeque<VertexBuffer>>>,
+}
+
+impl Default for BatchPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchPool {
+    pub fn new() -> Self {
+        Self {
+            queue: Rc::new(RefCell::new(VecDeque::new())),
+        }
+    }
+
+    /// Take a sprite batch from the pool.
+    ///
+    /// Reuses a released vertex buffer if one is available, otherwise
+    /// allocates a new one.
+    pub fn acquire(&self, device: &GraphicDevice) -> errors::Result<PooledBatch> {
+        self.acquire_with(|| SpriteBatch::create_vertex_buffer(device))
+    }
+
+    fn acquire_with<F>(&self, create: F) -> errors::Result<PooledBatch>
+    where
+        F: FnOnce() -> errors::Result<VertexBuffer>,
+    {
+        let released = self.queue.borrow_mut().pop_front();
+        let vertex_buffer = match released {
+            Some(vertex_buffer) => vertex_buffer,
+            None => create()?,
+        };
+
+        Ok(PooledBatch {
+            batch: Some(SpriteBatch::with_vertex_buffer(vertex_buffer)),
+            recycle: Rc::downgrade(&self.queue),
+        })
+    }
+}
+
+/// Sprite batch borrowed from a `BatchPool`.
+///
+/// Dereferences to the inner `SpriteBatch`. On drop the vertex buffer
+/// is sent back to the pool instead of being deallocated.
+pub struct PooledBatch {
+    batch: Option<SpriteBatch>,
+    recycle: Weak<RefCell<VecDeque<VertexBuffer>>>,
+}
+
+impl Deref for PooledBatch {
+    type Target = SpriteBatch;
+
+    fn deref(&self) -> &Self::Target {
+        self.batch.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledBatch {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.batch.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledBatch {
+    fn drop(&mut self) {
+        if let Some(SpriteBatch { vertex_buffer, .. }) = self.batch.take() {
+            // When the pool has already been dropped, the buffer is
+            // deallocated normally.
+            if let Some(queue) = self.recycle.upgrade() {
+                queue.borrow_mut().push_back(vertex_buffer);
+            }
         }
     }
 }
@@ -242,7 +977,28 @@ impl SpriteBatch {
 pub struct Sprite {
     pub(crate) pos: [i32; 2],
     pub(crate) size: [u32; 2],
+    /// Rotation in radians, clockwise in screen space, around the anchor.
+    pub(crate) rotation: f32,
+    pub(crate) scale: [f32; 2],
+    /// Normalised origin of the sprite relative to its size, where
+    /// `[0.0, 0.0]` is the top-left corner and `[1.0, 1.0]` is the
+    /// bottom-right. The sprite is positioned, scaled and rotated
+    /// around this point.
+    pub(crate) anchor: [f32; 2],
+    /// Vertex colour, multiplied with the texture.
+    pub(crate) color: Color,
     pub(crate) texture: Option<Texture>,
+    /// Texture was removed with `clear_texture`, so batches skip the
+    /// sprite until a texture is set again.
+    pub(crate) texture_cleared: bool,
+    /// Point of the viewport the position is relative to.
+    pub(crate) screen_anchor: ScreenAnchor,
+    /// Layer sampled when the batch draws from a texture array.
+    pub(crate) layer: u32,
+    /// Mirrors the texture horizontally.
+    pub(crate) flip_x: bool,
+    /// Part of the sprite that is drawn, in its local pixel coordinates.
+    pub(crate) clip: Option<Rect<f32>>,
 }
 
 impl Sprite {
@@ -250,17 +1006,1011 @@ impl Sprite {
         Self {
             pos,
             size,
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+            anchor: [0.0, 0.0],
+            color: color::WHITE,
             texture: None,
+            texture_cleared: false,
+            screen_anchor: ScreenAnchor::TopLeft,
+            layer: 0,
+            flip_x: false,
+            clip: None,
+        }
+    }
+
+    /// Create a sprite the size of the texture's view.
+    pub fn with_texture(pos: [i32; 2], texture: Texture) -> Self {
+        let mut sprite = Self::with(pos, texture.rect().size);
+        sprite.texture = Some(texture);
+        sprite
+    }
+
+    /// Start configuring a sprite, with every property at its default.
+    ///
+    /// ```no_run
+    /// # use grok_glow::{color, sprite_batch::Sprite};
+    /// let sprite = Sprite::builder()
+    ///     .pos([100, 50])
+    ///     .size([32, 32])
+    ///     .color(color::WHITE)
+    ///     .flip_x(true)
+    ///     .build();
+    /// ```
+    pub fn builder() -> SpriteBuilder {
+        SpriteBuilder {
+            sprite: Sprite::with([0, 0], [0, 0]),
         }
     }
 
     pub fn set_texture(&mut self, texture: Texture) {
         self.texture = Some(texture);
+        self.texture_cleared = false;
+    }
+
+    /// Set the sprite's size to the size of its texture's view, like
+    /// a region of an atlas. Keeps the current size when there is no texture.
+    pub fn fit_texture(&mut self) {
+        if let Some(texture) = self.texture.as_ref() {
+            self.size = texture.rect().size;
+        }
+    }
+
+    /// Remove the sprite's texture, keeping its other properties.
+    ///
+    /// `SpriteBatch::add` skips the sprite until a texture is set again,
+    /// like an animation without a current frame. Sprites built without
+    /// a texture are drawn as solid rectangles instead.
+    pub fn clear_texture(&mut self) {
+        self.texture = None;
+        self.texture_cleared = true;
+    }
+
+    pub fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Set the colour the sprite's texture is multiplied with.
+    ///
+    /// A sprite without a texture is drawn as a solid rectangle
+    /// of this colour.
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    pub fn set_rotation(&mut self, radians: f32) {
+        self.rotation = radians;
+    }
+
+    pub fn set_scale(&mut self, scale: [f32; 2]) {
+        self.scale = scale;
+    }
+
+    pub fn set_anchor(&mut self, anchor: [f32; 2]) {
+        self.anchor = anchor;
+    }
+
+    /// Position the sprite relative to a point of the viewport, so it
+    /// stays in place when the viewport is resized.
+    ///
+    /// The position is resolved when the batch is drawn. Anchored sprites
+    /// are not culled. Defaults to `ScreenAnchor::TopLeft`, which is
+    /// absolute positioning.
+    pub fn set_screen_anchor(&mut self, screen_anchor: ScreenAnchor) {
+        self.screen_anchor = screen_anchor;
+    }
+
+    /// Set the layer sampled when the sprite is drawn by a batch with a
+    /// texture array, like the current frame of an animation.
+    ///
+    /// See `SpriteBatch::set_texture_array`.
+    pub fn set_layer(&mut self, layer: u32) {
+        self.layer = layer;
     }
+
+    /// Mirror the texture horizontally, for example so a character
+    /// can face either direction using the same frames.
+    pub fn set_flip_x(&mut self, flip_x: bool) {
+        self.flip_x = flip_x;
+    }
+
+    /// Only draw the part of the sprite inside the rectangle, like a
+    /// health bar revealed from left to right.
+    ///
+    /// The rectangle is in the sprite's local pixels, where `[0.0, 0.0]`
+    /// is the top-left corner of the unscaled sprite, so it moves, scales
+    /// and rotates with the sprite. Both the quad and its texture
+    /// coordinates are cut, so the texture is not squashed. A sprite
+    /// clipped entirely is skipped when added to a batch.
+    ///
+    /// Unlike a scissor test, only this sprite is affected.
+    pub fn set_clip(&mut self, clip: Rect<f32>) {
+        self.clip = Some(clip);
+    }
+
+    pub fn clear_clip(&mut self) {
+        self.clip = None;
+    }
+
+    /// Whether the clip rectangle leaves nothing of the sprite to draw.
+    pub fn is_clipped_out(&self) -> bool {
+        self.visible_rect().is_none()
+    }
+
+    /// Local rectangle left by the clip rectangle, as left, top, right
+    /// and bottom edges in unscaled pixels. `None` when clipped entirely.
+    fn visible_rect(&self) -> Option<[f32; 4]> {
+        let [w, h] = [self.size[0] as f32, self.size[1] as f32];
+        let clip = match self.clip {
+            Some(clip) => clip,
+            None => return Some([0.0, 0.0, w, h]),
+        };
+
+        let [left, top] = [clip.pos[0].max(0.0), clip.pos[1].max(0.0)];
+        let right = (clip.pos[0] + clip.size[0]).min(w);
+        let bottom = (clip.pos[1] + clip.size[1]).min(h);
+
+        if right > left && bottom > top {
+            Some([left, top, right, bottom])
+        } else {
+            None
+        }
+    }
+
+    /// Normalised texture coordinates mapped onto the corners.
+    pub(crate) fn uv_rect(&self) -> Rect<f32> {
+        let full = if self.flip_x {
+            Rect {
+                pos: [1.0, 0.0],
+                size: [-1.0, 1.0],
+            }
+        } else {
+            Rect {
+                pos: [0.0, 0.0],
+                size: [1.0, 1.0],
+            }
+        };
+
+        match (self.clip, self.visible_rect()) {
+            // The visible rectangle is not empty, so neither is the sprite.
+            (Some(_), Some([left, top, right, bottom])) => {
+                let [w, h] = [self.size[0] as f32, self.size[1] as f32];
+                let [u, v] = full.pos;
+                let [uw, vh] = full.size;
+                Rect {
+                    pos: [u + uw * left / w, v + vh * top / h],
+                    size: [uw * (right - left) / w, vh * (bottom - top) / h],
+                }
+            }
+            _ => full,
+        }
+    }
+
+    /// Corner positions of the transformed quad, in the order top-left,
+    /// top-right, bottom-right and bottom-left.
+    pub fn corners(&self) -> [[f32; 2]; 4] {
+        let [x, y] = [self.pos[0] as f32, self.pos[1] as f32];
+        let [w, h] = [self.size[0] as f32, self.size[1] as f32];
+        let [ax, ay] = [self.anchor[0] * w, self.anchor[1] * h];
+        let [sx, sy] = self.scale;
+        let (sin, cos) = self.rotation.sin_cos();
+
+        // A sprite clipped entirely collapses to its top-left corner.
+        let [left, top, right, bottom] = self.visible_rect().unwrap_or([0.0; 4]);
+        let local = [[left, top], [right, top], [right, bottom], [left, bottom]];
+        let mut corners = [[0.0; 2]; 4];
+
+        for (corner, [lx, ly]) in corners.iter_mut().zip(local.iter()) {
+            // Relative to anchor, then scaled.
+            let [px, py] = [(lx - ax) * sx, (ly - ay) * sy];
+            // Rotated around anchor, then translated.
+            *corner = [x + px * cos - py * sin, y + px * sin + py * cos];
+        }
+
+        corners
+    }
+
+    /// Axis-aligned bounding box of the transformed quad.
+    ///
+    /// Rotation expands the box to contain all four corners.
+    pub fn bounds(&self) -> Rect<f32> {
+        let corners = self.corners();
+
+        let mut min = corners[0];
+        let mut max = corners[0];
+        for [cx, cy] in corners.iter().skip(1) {
+            min = [min[0].min(*cx), min[1].min(*cy)];
+            max = [max[0].max(*cx), max[1].max(*cy)];
+        }
+
+        Rect {
+            pos: min,
+            size: [max[0] - min[0], max[1] - min[1]],
+        }
+    }
+}
+
+/// Chainable construction of a batch `Sprite`, created by `Sprite::builder`.
+pub struct SpriteBuilder {
+    sprite: Sprite,
+}
+
+impl SpriteBuilder {
+    pub fn pos(mut self, pos: [i32; 2]) -> Self {
+        self.sprite.pos = pos;
+        self
+    }
+
+    pub fn size(mut self, size: [u32; 2]) -> Self {
+        self.sprite.size = size;
+        self
+    }
+
+    pub fn texture(mut self, texture: Texture) -> Self {
+        self.sprite.texture = Some(texture);
+        self.sprite.texture_cleared = false;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.sprite.color = color;
+        self
+    }
+
+    /// Rotation in radians, clockwise in screen space, around the anchor.
+    pub fn rotation(mut self, radians: f32) -> Self {
+        self.sprite.rotation = radians;
+        self
+    }
+
+    pub fn scale(mut self, scale: [f32; 2]) -> Self {
+        self.sprite.scale = scale;
+        self
+    }
+
+    pub fn anchor(mut self, anchor: [f32; 2]) -> Self {
+        self.sprite.anchor = anchor;
+        self
+    }
+
+    pub fn flip_x(mut self, flip_x: bool) -> Self {
+        self.sprite.flip_x = flip_x;
+        self
+    }
+
+    pub fn build(self) -> Sprite {
+        self.sprite
+    }
+}
+
+/// Corner of the render target where sprite coordinates start.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// y points down, as is usual for 2D and UI.
+    #[default]
+    TopLeft,
+    /// y points up, as in OpenGL's clip space.
+    BottomLeft,
+}
+
+impl Origin {
+    /// Whether the sprite shader must flip the y-axis, given whether
+    /// the bound target needs flipping for a top-left origin.
+    fn flip_y(self, target_flip_y: bool) -> bool {
+        match self {
+            Origin::TopLeft => target_flip_y,
+            Origin::BottomLeft => !target_flip_y,
+        }
+    }
+}
+
+/// Point of the viewport that a sprite is positioned relative to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenAnchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
 }
 
-struct BatchItem {
-    pos: [f32; 2],
-    size: [f32; 2],
+impl ScreenAnchor {
+    /// Position of the anchor point within a viewport of the given size.
+    pub fn offset(self, viewport_size: [f32; 2]) -> [f32; 2] {
+        let [x, y] = match self {
+            ScreenAnchor::TopLeft => [0.0, 0.0],
+            ScreenAnchor::Top => [0.5, 0.0],
+            ScreenAnchor::TopRight => [1.0, 0.0],
+            ScreenAnchor::Left => [0.0, 0.5],
+            ScreenAnchor::Center => [0.5, 0.5],
+            ScreenAnchor::Right => [1.0, 0.5],
+            ScreenAnchor::BottomLeft => [0.0, 1.0],
+            ScreenAnchor::Bottom => [0.5, 1.0],
+            ScreenAnchor::BottomRight => [1.0, 1.0],
+        };
+
+        [x * viewport_size[0], y * viewport_size[1]]
+    }
+}
+
+struct AuxTexture {
+    unit: u32,
+    uniform_name: String,
     texture: Texture,
 }
+
+/// Sprite added to a batch, waiting to be drawn.
+pub struct BatchItem {
+    /// Corner positions, in the order top-left, top-right,
+    /// bottom-right, bottom-left.
+    corners: [[f32; 2]; 4],
+    /// Normalised texture coordinates mapped onto the corners.
+    uv_rect: Rect<f32>,
+    color: Color,
+    /// Sprites without a texture are drawn with the device's white texture.
+    texture: Option<Texture>,
+    screen_anchor: ScreenAnchor,
+    /// Layer of the batch's texture array, if any.
+    layer: u32,
+    /// Colour of each corner, overriding `color`, for shapes that fade
+    /// out at their edges.
+    corner_colors: Option<[Color; 4]>,
+}
+
+impl BatchItem {
+    /// Corner positions, in the order top-left, top-right,
+    /// bottom-right, bottom-left.
+    pub fn corners(&self) -> [[f32; 2]; 4] {
+        self.corners
+    }
+
+    pub fn uv_rect(&self) -> Rect<f32> {
+        self.uv_rect
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn texture(&self) -> Option<&Texture> {
+        self.texture.as_ref()
+    }
+
+    pub fn layer(&self) -> u32 {
+        self.layer
+    }
+}
+
+/// Range of item indices within the number of items.
+fn clamp_range(range: impl RangeBounds<usize>, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(start) => *start,
+        Bound::Excluded(start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(end) => end.saturating_add(1),
+        Bound::Excluded(end) => *end,
+        Bound::Unbounded => len,
+    };
+    let end = end.min(len);
+    start.min(end)..end
+}
+
+/// Fills `order` with the indices of the items, stably sorted by the
+/// user's key, or in submission order without one.
+fn sort_order(order: &mut Vec<usize>, items: &[BatchItem], sort_key: Option<&SortKey>) {
+    order.clear();
+    order.extend(0..items.len());
+    if let Some(sort_key) = sort_key {
+        order.sort_by_key(|index| sort_key(&items[*index]));
+    }
+}
+
+/// Translates corners, positioned relative to the screen anchor, to
+/// absolute positions within the viewport.
+fn anchored_corners(
+    corners: [[f32; 2]; 4],
+    screen_anchor: ScreenAnchor,
+    viewport_size: [f32; 2],
+) -> [[f32; 2]; 4] {
+    let [ox, oy] = screen_anchor.offset(viewport_size);
+    let mut anchored = corners;
+    for corner in anchored.iter_mut() {
+        *corner = [corner[0] + ox, corner[1] + oy];
+    }
+    anchored
+}
+
+/// Indices of the nth quad in the batch. See `primitives::SPRITE_WINDING`.
+fn batch_quad_indices(quad: u16) -> [u16; 6] {
+    primitives::quad_indices_at(primitives::SPRITE_WINDING, quad * 4)
+}
+
+/// Location of the `u_Layer` uniform declared by `sprite_array.frag`.
+const LAYER_LOC: u32 = 3;
+
+/// Location of the `u_LinearColor` uniform declared by `sprite.vert`.
+const LINEAR_COLOR_LOC: u32 = 4;
+
+/// Most indices a quad of the batch takes, with `Primitive::Lines`.
+const MAX_QUAD_INDICES: usize = 8;
+
+/// Appends the indices of a quad of the batch, assembled as the primitive.
+fn extend_quad_indices(indices: &mut Vec<u16>, primitive: Primitive, quad: u16) {
+    let first = quad * 4;
+    let [top_left, top_right, bottom_right, bottom_left] = [first, first + 1, first + 2, first + 3];
+
+    match primitive {
+        Primitive::Triangles => indices.extend_from_slice(&batch_quad_indices(quad)),
+        Primitive::Lines => indices.extend_from_slice(&[
+            top_left,
+            top_right,
+            top_right,
+            bottom_right,
+            bottom_right,
+            bottom_left,
+            bottom_left,
+            top_left,
+        ]),
+        // The repeated first and last corners join consecutive
+        // quads with triangles of no area.
+        Primitive::TriangleStrip => indices.extend_from_slice(&[
+            top_left,
+            top_left,
+            top_right,
+            bottom_left,
+            bottom_right,
+            bottom_right,
+        ]),
+        Primitive::Points => {
+            indices.extend_from_slice(&[top_left, top_right, bottom_right, bottom_left])
+        }
+    }
+}
+
+fn validate_texture_unit(unit: u32, max_units: u32) -> errors::Result<()> {
+    if unit < max_units {
+        Ok(())
+    } else {
+        Err(errors::Error::InvalidTextureUnit {
+            unit,
+            max: max_units,
+        })
+    }
+}
+
+/// Size of the window's framebuffer, or empty when minimized.
+fn viewport_target(device: &GraphicDevice) -> PhysicalSize<u32> {
+    if device.is_viewport_empty() {
+        PhysicalSize::new(0, 0)
+    } else {
+        device.get_viewport_size()
+    }
+}
+
+/// Enum passed to `glActiveTexture` to select a texture unit.
+fn texture_slot(unit: u32) -> u32 {
+    glow::TEXTURE0 + unit
+}
+
+/// Rounds the corners of an axis-aligned quad to whole pixels.
+///
+/// Rotated or skewed quads are returned unchanged, since snapping
+/// their corners independently would distort them.
+fn snap_corners(corners: [[f32; 2]; 4]) -> [[f32; 2]; 4] {
+    let [tl, tr, br, bl] = corners;
+    let axis_aligned = tl[1] == tr[1] && tr[0] == br[0] && br[1] == bl[1] && bl[0] == tl[0];
+    if !axis_aligned {
+        return corners;
+    }
+
+    let mut snapped = corners;
+    for corner in snapped.iter_mut() {
+        *corner = [corner[0].round(), corner[1].round()];
+    }
+    snapped
+}
+
+/// Allocates a vertex buffer in video memory large enough to hold a
+/// full batch of the given vertex type.
+fn create_batch_vertex_buffer<V: BatchVertex>(
+    device: &GraphicDevice,
+) -> errors::Result<VertexBuffer> {
+    // 4 vertices per sprite
+    let vertex = V::from_vertex(Vertex {
+        position: [0.0, 0.0],
+        uv: [0.0, 0.0],
+        color: [1.0, 1.0, 1.0, 1.0],
+    });
+    let vertices = vec![vertex; SpriteBatch::BATCH_SIZE * 4];
+
+    // 2 triangles, 6 indices per sprite, with room for the
+    // outlines drawn with `Primitive::Lines`.
+    let mut indices: Vec<u16> = vec![];
+    for i in 0..SpriteBatch::BATCH_SIZE as u16 {
+        indices.extend_from_slice(&batch_quad_indices(i));
+    }
+    indices.resize(SpriteBatch::BATCH_SIZE * MAX_QUAD_INDICES, 0);
+
+    VertexBuffer::new_static_custom(device, &vertices, &indices)
+}
+
+/// Builds the vertices of a batch item at the given corners, and
+/// passes each through the corner callback.
+fn item_vertices<V: BatchVertex>(
+    corners: [[f32; 2]; 4],
+    item: &BatchItem,
+    corner_fn: Option<&CornerFn<V>>,
+) -> [V; 4] {
+    let mut vertices = quad_vertices(corners, item.uv_rect, item.color);
+    if let Some(corner_colors) = item.corner_colors {
+        for (vertex, color) in vertices.iter_mut().zip(corner_colors.iter()) {
+            vertex.color = *color;
+        }
+    }
+
+    let mut vertices = vertices.map(V::from_vertex);
+    if let Some(corner_fn) = corner_fn {
+        for (index, vertex) in vertices.iter_mut().enumerate() {
+            corner_fn(item, index, vertex);
+        }
+    }
+    vertices
+}
+
+/// Builds the four vertices of a quad, mapping the corners of
+/// the UV rectangle onto the given corner positions.
+pub(crate) fn quad_vertices(
+    corners: [[f32; 2]; 4],
+    uv_rect: Rect<f32>,
+    color: Color,
+) -> [Vertex; 4] {
+    let [u, v] = uv_rect.pos;
+    let [uw, vh] = uv_rect.size;
+
+    [
+        Vertex {
+            position: corners[0],
+            uv: [u, v],
+            color,
+        },
+        Vertex {
+            position: corners[1],
+            uv: [u + uw, v],
+            color,
+        },
+        Vertex {
+            position: corners[2],
+            uv: [u + uw, v + vh],
+            color,
+        },
+        Vertex {
+            position: corners[3],
+            uv: [u, v + vh],
+            color,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quad_vertices() {
+        let corners = [[10.0, 0.0], [30.0, 5.0], [40.0, 20.0], [0.0, 25.0]];
+        let uv_rect = Rect {
+            pos: [0.25, 0.5],
+            size: [0.5, 0.25],
+        };
+        let color = [1.0, 0.5, 0.25, 1.0];
+
+        let vertices = quad_vertices(corners, uv_rect, color);
+
+        for (vertex, corner) in vertices.iter().zip(corners.iter()) {
+            assert_eq!(vertex.position, *corner);
+            assert_eq!(vertex.color, color);
+        }
+        assert_eq!(vertices[0].uv, [0.25, 0.5]);
+        assert_eq!(vertices[1].uv, [0.75, 0.5]);
+        assert_eq!(vertices[2].uv, [0.75, 0.75]);
+        assert_eq!(vertices[3].uv, [0.25, 0.75]);
+    }
+
+    #[test]
+    fn test_sprite_bounds() {
+        let mut sprite = Sprite::with([100, 100], [10, 10]);
+
+        let bounds = sprite.bounds();
+        assert_eq!(bounds.pos, [100.0, 100.0]);
+        assert_eq!(bounds.size, [10.0, 10.0]);
+
+        // Rotating a square around its center grows the box by the diagonal factor.
+        sprite.set_anchor([0.5, 0.5]);
+        sprite.set_rotation(std::f32::consts::FRAC_PI_4);
+
+        let bounds = sprite.bounds();
+        let diagonal = 10.0 * std::f32::consts::SQRT_2;
+        assert!((bounds.size[0] - diagonal).abs() < 1e-4);
+        assert!((bounds.size[1] - diagonal).abs() < 1e-4);
+        assert!((bounds.pos[0] - (100.0 - diagonal / 2.0)).abs() < 1e-4);
+        assert!((bounds.pos[1] - (100.0 - diagonal / 2.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_sprite_builder() {
+        let (_rx, texture) = crate::texture::test::dummy_texture(1, 16, 16);
+        let color = [0.5, 0.25, 1.0, 1.0];
+
+        let sprite = Sprite::builder()
+            .pos([10, 20])
+            .size([16, 8])
+            .texture(texture)
+            .color(color)
+            .rotation(1.5)
+            .flip_x(true)
+            .build();
+
+        assert_eq!(sprite.pos, [10, 20]);
+        assert_eq!(sprite.size, [16, 8]);
+        assert_eq!(sprite.texture.as_ref().map(|t| t.raw_handle()), Some(1));
+        assert_eq!(sprite.color, color);
+        assert_eq!(sprite.rotation, 1.5);
+        assert!(sprite.flip_x);
+        assert_eq!(sprite.uv_rect().pos, [1.0, 0.0]);
+        assert_eq!(sprite.uv_rect().size, [-1.0, 1.0]);
+
+        // Unset properties keep their defaults.
+        assert_eq!(sprite.scale, [1.0, 1.0]);
+        assert_eq!(sprite.anchor, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_sprite_clip() {
+        let mut sprite = Sprite::with([10, 20], [32, 16]);
+        sprite.set_clip(Rect {
+            pos: [0.0, 0.0],
+            size: [16.0, 16.0],
+        });
+
+        // Left half of the quad, sampling the left half of the texture.
+        assert_eq!(
+            sprite.corners(),
+            [[10.0, 20.0], [26.0, 20.0], [26.0, 36.0], [10.0, 36.0]]
+        );
+        let uv_rect = sprite.uv_rect();
+        assert_eq!(uv_rect.pos, [0.0, 0.0]);
+        assert_eq!(uv_rect.size, [0.5, 1.0]);
+
+        // Mirrored sprites keep sampling the texels under the visible part.
+        sprite.set_flip_x(true);
+        let uv_rect = sprite.uv_rect();
+        assert_eq!(uv_rect.pos, [1.0, 0.0]);
+        assert_eq!(uv_rect.size, [-0.5, 1.0]);
+
+        assert!(!sprite.is_clipped_out());
+        sprite.set_clip(Rect {
+            pos: [32.0, 0.0],
+            size: [16.0, 16.0],
+        });
+        assert!(sprite.is_clipped_out());
+
+        sprite.clear_clip();
+        assert_eq!(sprite.corners()[2], [42.0, 36.0]);
+    }
+
+    #[test]
+    fn test_origin_flip_y() {
+        // The window needs flipping for a top-left origin, render targets don't.
+        assert!(Origin::TopLeft.flip_y(true));
+        assert!(!Origin::TopLeft.flip_y(false));
+        assert!(!Origin::BottomLeft.flip_y(true));
+        assert!(Origin::BottomLeft.flip_y(false));
+        assert_eq!(Origin::default(), Origin::TopLeft);
+    }
+
+    #[test]
+    fn test_clamp_range() {
+        assert_eq!(clamp_range(.., 10), 0..10);
+        assert_eq!(clamp_range(2..5, 10), 2..5);
+        assert_eq!(clamp_range(8..20, 10), 8..10);
+        assert_eq!(clamp_range(12..20, 10), 10..10);
+        assert_eq!(clamp_range(2..=4, 10), 2..5);
+    }
+
+    #[test]
+    fn test_batch_pool_reuse() {
+        let (rx, vertex_buffer) = crate::vertex::test::dummy_vertex_buffer();
+        let pool = BatchPool::new();
+
+        let batch = pool.acquire_with(|| Ok(vertex_buffer)).unwrap();
+        let vbo = batch.vertex_buffer.vbo;
+        drop(batch);
+        assert_eq!(rx.len(), 0);
+
+        // The released buffer is taken before allocating a new one.
+        let batch = pool
+            .acquire_with(|| panic!("vertex buffer was not reused"))
+            .unwrap();
+        assert_eq!(batch.vertex_buffer.vbo, vbo);
+
+        // Without the pool, the buffer is deallocated.
+        drop(pool);
+        drop(batch);
+        assert_eq!(rx.len(), 3);
+    }
+
+    #[test]
+    fn test_add_many() {
+        let (_rx, vertex_buffer) = crate::vertex::test::dummy_vertex_buffer();
+        let mut batch: SpriteBatch = SpriteBatch::with_vertex_buffer(vertex_buffer);
+
+        let sprites: Vec<Sprite> = (0..10_000)
+            .map(|i| Sprite::with([i % 640, i / 640], [4, 4]))
+            .collect();
+        batch.add_many(&sprites);
+
+        // Space for every sprite was reserved in one go.
+        assert_eq!(batch.len(), 10_000);
+        assert_eq!(batch.items.capacity(), 10_000);
+
+        batch.clear();
+        batch.add_from_iter(sprites.into_iter().take(100));
+        assert_eq!(batch.len(), 100);
+    }
+
+    #[test]
+    fn test_snap_corners() {
+        // Centred on an odd size, the corners land between pixels.
+        let mut sprite = Sprite::with([10, 10], [5, 5]);
+        sprite.set_anchor([0.5, 0.5]);
+        sprite.set_scale([1.5, 1.5]);
+
+        let snapped = snap_corners(sprite.corners());
+        for corner in snapped.iter() {
+            assert_eq!(corner[0].fract(), 0.0);
+            assert_eq!(corner[1].fract(), 0.0);
+        }
+
+        // Rotated sprites keep their shape.
+        sprite.set_rotation(0.3);
+        assert_eq!(snap_corners(sprite.corners()), sprite.corners());
+    }
+
+    #[test]
+    fn test_quad_indices_match_sprite() {
+        assert_eq!(batch_quad_indices(0), crate::sprite::quad_indices());
+
+        let second: Vec<u16> = crate::sprite::quad_indices()
+            .iter()
+            .map(|i| i + 4)
+            .collect();
+        assert_eq!(&batch_quad_indices(1)[..], &second[..]);
+    }
+
+    #[test]
+    fn test_quad_indices_per_primitive() {
+        let mut indices = vec![];
+        extend_quad_indices(&mut indices, Primitive::Lines, 1);
+        assert_eq!(indices, [4, 5, 5, 6, 6, 7, 7, 4]);
+
+        // Every primitive fits in the batch's index buffer.
+        for primitive in &[
+            Primitive::Triangles,
+            Primitive::Lines,
+            Primitive::TriangleStrip,
+            Primitive::Points,
+        ] {
+            indices.clear();
+            extend_quad_indices(&mut indices, *primitive, 0);
+            assert!(indices.len() <= MAX_QUAD_INDICES);
+        }
+    }
+
+    #[test]
+    fn test_validate_texture_unit() {
+        assert!(validate_texture_unit(15, 16).is_ok());
+        assert!(matches!(
+            validate_texture_unit(16, 16),
+            Err(errors::Error::InvalidTextureUnit { unit: 16, max: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_texture_slot() {
+        assert_eq!(texture_slot(0), glow::TEXTURE0);
+        assert_eq!(texture_slot(2), glow::TEXTURE2);
+        assert_eq!(texture_slot(SpriteBatch::PALETTE_UNIT), glow::TEXTURE1);
+    }
+
+    #[test]
+    fn test_fit_texture() {
+        let (_rx, atlas) = crate::texture::test::dummy_texture(1, 256, 256);
+        let sub_texture = atlas.new_sub([64, 0], [64, 32]).unwrap();
+
+        let mut sprite = Sprite::with([0, 0], [8, 8]);
+        sprite.fit_texture();
+        assert_eq!(sprite.size, [8, 8]);
+
+        sprite.set_texture(sub_texture.clone());
+        sprite.fit_texture();
+        assert_eq!(sprite.size, [64, 32]);
+
+        let sprite = Sprite::with_texture([10, 10], sub_texture);
+        assert_eq!(sprite.size, [64, 32]);
+        assert_eq!(sprite.pos, [10, 10]);
+    }
+
+    #[test]
+    fn test_clear_texture() {
+        let (_rx, texture) = crate::texture::test::dummy_texture(1, 16, 16);
+        let mut sprite = Sprite::builder()
+            .size([16, 16])
+            .color(color::BLACK)
+            .texture(texture)
+            .build();
+        assert_eq!(sprite.texture().map(|t| t.raw_handle()), Some(1));
+
+        sprite.clear_texture();
+        assert!(sprite.texture().is_none());
+        assert_eq!(sprite.color, color::BLACK);
+        assert_eq!(sprite.size, [16, 16]);
+        assert!(sprite.texture_cleared);
+    }
+
+    #[test]
+    fn test_screen_anchor() {
+        // HUD element in the bottom-right corner, with a 10 pixel margin.
+        let mut sprite = Sprite::with([-42, -42], [32, 32]);
+        sprite.set_screen_anchor(ScreenAnchor::BottomRight);
+
+        let corners = anchored_corners(sprite.corners(), sprite.screen_anchor, [800.0, 600.0]);
+        assert_eq!(corners[0], [758.0, 558.0]);
+        assert_eq!(corners[2], [790.0, 590.0]);
+
+        // Viewport was resized between draws.
+        let corners = anchored_corners(sprite.corners(), sprite.screen_anchor, [1024.0, 768.0]);
+        assert_eq!(corners[0], [982.0, 726.0]);
+        assert_eq!(corners[2], [1014.0, 758.0]);
+
+        // Absolute positioning is unaffected by the viewport.
+        let sprite = Sprite::with([10, 20], [32, 32]);
+        let corners = anchored_corners(sprite.corners(), sprite.screen_anchor, [800.0, 600.0]);
+        assert_eq!(corners, sprite.corners());
+    }
+
+    /// Vertex with a phase offset, as used by a waving flag shader.
+    #[derive(Debug, Clone)]
+    #[repr(C)]
+    struct WaveVertex {
+        position: [f32; 2],
+        uv: [f32; 2],
+        color: [f32; 4],
+        phase: f32,
+    }
+
+    unsafe impl BatchVertex for WaveVertex {
+        fn format() -> crate::vertex::VertexFormat {
+            crate::vertex::VertexFormat::Custom {
+                stride: std::mem::size_of::<Self>(),
+                offsets: [
+                    memoffset::offset_of!(Self, position),
+                    memoffset::offset_of!(Self, uv),
+                    memoffset::offset_of!(Self, color),
+                ],
+            }
+        }
+
+        fn extra_attributes() -> Vec<crate::vertex::VertexAttribute> {
+            vec![crate::vertex::VertexAttribute {
+                location: 3,
+                components: 1,
+                offset: memoffset::offset_of!(Self, phase),
+            }]
+        }
+
+        fn from_vertex(vertex: Vertex) -> Self {
+            Self {
+                position: vertex.position,
+                uv: vertex.uv,
+                color: vertex.color,
+                phase: 0.0,
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_vertex_corners() {
+        let sprite = Sprite::with([0, 0], [16, 16]);
+        let item = BatchItem {
+            corners: sprite.corners(),
+            uv_rect: sprite.uv_rect(),
+            color: color::WHITE,
+            texture: None,
+            screen_anchor: ScreenAnchor::TopLeft,
+            layer: 0,
+            corner_colors: None,
+        };
+
+        let corner_fn: Box<CornerFn<WaveVertex>> =
+            Box::new(|_item, index, vertex| vertex.phase = index as f32 * 0.25);
+        let vertices = item_vertices(item.corners, &item, Some(corner_fn.as_ref()));
+
+        let stride = WaveVertex::format().stride();
+        let phase_offset = WaveVertex::extra_attributes()[0].offset;
+        assert_eq!(stride, 36);
+        assert_eq!(phase_offset, 32);
+
+        let bytes = unsafe { utils::as_u8(&vertices) };
+        for index in 0..4 {
+            let start = index * stride + phase_offset;
+            let phase = f32::from_ne_bytes([
+                bytes[start],
+                bytes[start + 1],
+                bytes[start + 2],
+                bytes[start + 3],
+            ]);
+            assert_eq!(phase, index as f32 * 0.25);
+        }
+
+        // Without a callback, the sprite's own attributes are kept.
+        let vertices = item_vertices::<WaveVertex>(item.corners, &item, None);
+        assert_eq!(vertices[2].position, [16.0, 16.0]);
+        assert_eq!(vertices[2].phase, 0.0);
+    }
+
+    #[test]
+    fn test_add_mesh() {
+        let (_rx, vertex_buffer) = crate::vertex::test::dummy_vertex_buffer();
+        let mut batch: SpriteBatch = SpriteBatch::with_vertex_buffer(vertex_buffer);
+
+        // One quad per triangle of the fan.
+        batch.draw_circle_filled([0.0, 0.0], 10.0, 16, color::WHITE);
+        assert_eq!(batch.len(), 16);
+
+        // The fringe of the line fades out at the outer corners.
+        batch.clear();
+        batch.draw_line_aa([0.0, 0.0], [10.0, 0.0], 2.0, color::WHITE);
+        assert_eq!(batch.len(), 6);
+        let item = &batch.items[0];
+        let vertices = item_vertices::<Vertex>(item.corners, item, None);
+        assert_eq!(vertices[0].color[3], 0.0);
+        assert_eq!(vertices[2].position, vertices[3].position);
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let items: Vec<BatchItem> = (0..4)
+            .map(|x| BatchItem {
+                corners: Sprite::with([x, 0], [1, 1]).corners(),
+                uv_rect: Rect {
+                    pos: [0.0, 0.0],
+                    size: [1.0, 1.0],
+                },
+                color: color::WHITE,
+                texture: None,
+                screen_anchor: ScreenAnchor::TopLeft,
+                layer: 0,
+                corner_colors: None,
+            })
+            .collect();
+
+        let mut order = vec![];
+        sort_order(&mut order, &items, None);
+        assert_eq!(order, [0, 1, 2, 3]);
+
+        // Reverse submission order.
+        sort_order(
+            &mut order,
+            &items,
+            Some(&|item| u64::MAX - item.corners()[0][0] as u64),
+        );
+        assert_eq!(order, [3, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,22 @@
+#version 410
+#extension GL_ARB_explicit_uniform_location : enable
+
+precision highp float;
+
+layout(location = 1) uniform sampler2D u_Albedo;
+
+// Normal map, bound as an auxiliary texture.
+uniform sampler2D u_Normal;
+
+// Varyings
+in vec4 v_Color;
+in vec2 v_TexCoord;
+
+// Render target attachments.
+layout(location = 0) out vec4 Color;
+layout(location = 1) out vec4 Normal;
+
+void main() {
+    Color = v_Color * texture(u_Albedo, v_TexCoord);
+    Normal = texture(u_Normal, v_TexCoord);
+}
@@ -0,0 +1,26 @@
+#version 410
+#extension GL_ARB_explicit_uniform_location : enable
+
+precision highp float;
+
+// Index texture. The red channel holds the palette index.
+layout(location = 1) uniform sampler2D u_Albedo;
+
+// 256x1 palette texture, bound to an auxiliary texture unit.
+uniform sampler2D u_Palette;
+
+// Varyings
+in vec4 v_Color;
+in vec2 v_TexCoord;
+
+out vec4 Color;
+
+void main() {
+    float index = texture(u_Albedo, v_TexCoord).r;
+
+    // Sample the center of the palette texel, to avoid bleeding
+    // into neighbours when the palette is filtered.
+    vec2 palette_coord = vec2((index * 255.0 + 0.5) / 256.0, 0.5);
+
+    Color = v_Color * texture(u_Palette, palette_coord);
+}
@@ -0,0 +1,127 @@
+//! Slicing of textures laid out as a uniform grid of frames.
+use crate::{errors, texture::Texture};
+
+/// Texture divided into a grid of equally sized frames.
+///
+/// Frames are laid out like Tiled tilesets. The `margin` is the
+/// space in texels around the edge of the whole grid, and the
+/// `spacing` is the space between adjacent frames.
+pub struct SpriteSheet {
+    texture: Texture,
+    cell_size: [u32; 2],
+    margin: u32,
+    spacing: u32,
+    /// Number of columns and rows that fit in the texture.
+    grid: [u32; 2],
+}
+
+impl SpriteSheet {
+    /// # Errors
+    ///
+    /// Returns `InvalidTextureSize` if a cell dimension is zero.
+    pub fn from_grid(
+        texture: Texture,
+        cell_width: u32,
+        cell_height: u32,
+        margin: u32,
+        spacing: u32,
+    ) -> errors::Result<Self> {
+        if cell_width == 0 || cell_height == 0 {
+            return Err(errors::Error::InvalidTextureSize(cell_width, cell_height));
+        }
+
+        let size = texture.rect().size;
+        let fit = |available: u32, cell: u32| {
+            // The last cell has no spacing after it.
+            (available.saturating_sub(margin * 2) + spacing) / (cell + spacing)
+        };
+
+        Ok(Self {
+            grid: [fit(size[0], cell_width), fit(size[1], cell_height)],
+            texture,
+            cell_size: [cell_width, cell_height],
+            margin,
+            spacing,
+        })
+    }
+
+    /// Number of columns and rows in the grid.
+    pub fn grid_size(&self) -> [u32; 2] {
+        self.grid
+    }
+
+    /// Total number of frames in the grid.
+    pub fn len(&self) -> u32 {
+        self.grid[0] * self.grid[1]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Sub-texture of the frame at the given column and row.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FrameOutOfBounds` if the column or row is outside the grid.
+    pub fn frame_at(&self, col: u32, row: u32) -> errors::Result<Texture> {
+        if col >= self.grid[0] || row >= self.grid[1] {
+            return Err(errors::Error::FrameOutOfBounds {
+                index: [col, row],
+                grid: self.grid,
+            });
+        }
+
+        let origin = self.texture.rect().pos;
+        let [cell_width, cell_height] = self.cell_size;
+        let pos = [
+            origin[0] + self.margin + col * (cell_width + self.spacing),
+            origin[1] + self.margin + row * (cell_height + self.spacing),
+        ];
+
+        self.texture.new_sub(pos, self.cell_size)
+    }
+
+    /// Sub-texture of the frame at the given index, counting
+    /// left to right, then top to bottom.
+    ///
+    /// # Errors
+    ///
+    /// Returns `FrameOutOfBounds` if the index is outside the grid.
+    pub fn frame(&self, index: u32) -> errors::Result<Texture> {
+        let cols = self.grid[0].max(1);
+        self.frame_at(index % cols, index / cols)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::test::dummy_texture;
+
+    #[test]
+    fn test_from_grid() {
+        let (_rx, texture) = dummy_texture(1, 128, 128);
+        let sheet = SpriteSheet::from_grid(texture, 32, 32, 0, 0).unwrap();
+        assert_eq!(sheet.grid_size(), [4, 4]);
+        assert_eq!(sheet.len(), 16);
+
+        let frame = sheet.frame(5).unwrap();
+        assert_eq!(frame.rect().pos, [32, 32]);
+        assert_eq!(frame.rect().size, [32, 32]);
+
+        assert!(sheet.frame(16).is_err());
+        assert!(sheet.frame_at(4, 0).is_err());
+    }
+
+    #[test]
+    fn test_margin_and_spacing() {
+        // 2 + 32 + 4 + 32 + 4 + 32 + 2 = 108
+        let (_rx, texture) = dummy_texture(1, 108, 36);
+        let sheet = SpriteSheet::from_grid(texture, 32, 32, 2, 4).unwrap();
+        assert_eq!(sheet.grid_size(), [3, 1]);
+
+        let frame = sheet.frame(2).unwrap();
+        assert_eq!(frame.rect().pos, [74, 2]);
+    }
+}
@@ -0,0 +1,150 @@
+//! Ring of textures for uploading a new image every frame, like video.
+use crate::{device::GraphicDevice, errors, texture::Texture};
+
+/// Cycles through several equally sized textures, so a frame can be
+/// uploaded into one while the previous frame is drawn from another.
+///
+/// Uploading into a texture that pending draw calls still sample from
+/// makes the driver wait for them, or copy the texture. With a ring,
+/// the texture written by `next_frame_buffer` is never the one returned
+/// by `current_texture`, at the cost of showing each frame one frame late.
+///
+/// Each texture in the ring releases its video memory when the ring is
+/// dropped, like any other texture.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use grok_glow::streaming_texture::StreamingTexture;
+/// # fn decode_frame() -> Vec<u8> { vec![] }
+/// # let device: grok_glow::device::GraphicDevice = unimplemented!();
+/// let mut video = StreamingTexture::new(&device, 640, 360, 2)?;
+///
+/// // Each frame.
+/// video.upload_frame(&device, &decode_frame())?;
+/// let texture = video.current_texture();
+/// # Ok::<(), grok_glow::errors::Error>(())
+/// ```
+pub struct StreamingTexture {
+    textures: Vec<Texture>,
+    /// Index of the latest texture that is done being written.
+    current: usize,
+    /// Index of the texture being written, if any.
+    writing: Option<usize>,
+}
+
+impl StreamingTexture {
+    /// Allocates a ring of RGBA8 textures of the given size.
+    ///
+    /// Two textures are enough when the frame is uploaded once per
+    /// rendered frame. More can absorb uploads that take longer.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidRingLength` if the count is less than 2, and
+    /// `InvalidTextureSize` if a dimension is 0.
+    pub fn new(
+        device: &GraphicDevice,
+        width: u32,
+        height: u32,
+        count: usize,
+    ) -> errors::Result<Self> {
+        validate_ring_length(count)?;
+
+        let textures = (0..count)
+            .map(|_| Texture::new(device, width, height))
+            .collect::<errors::Result<Vec<_>>>()?;
+
+        Ok(Self::from_textures(textures))
+    }
+
+    fn from_textures(textures: Vec<Texture>) -> Self {
+        Self {
+            // Nothing written yet, so the first frame draws the
+            // texture that is written last.
+            current: textures.len() - 1,
+            writing: None,
+            textures,
+        }
+    }
+
+    /// Advance the ring, and return the texture to write the next frame into.
+    ///
+    /// The texture written since the previous call becomes the current
+    /// texture. Write into the returned texture, like with
+    /// `Texture::update_data`, before calling this again.
+    pub fn next_frame_buffer(&mut self) -> &mut Texture {
+        if let Some(written) = self.writing {
+            self.current = written;
+        }
+        let next = (self.current + 1) % self.textures.len();
+        self.writing = Some(next);
+        &mut self.textures[next]
+    }
+
+    /// Advance the ring, and upload the RGBA8 data of the next frame.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidImageData` if the data length does not match the
+    /// size of the textures.
+    pub fn upload_frame(&mut self, device: &GraphicDevice, data: &[u8]) -> errors::Result<()> {
+        self.next_frame_buffer().update_data(device, data)
+    }
+
+    /// Texture of the latest complete frame, to draw.
+    ///
+    /// Returns a cheap clone, which can be set on sprites. The clone keeps
+    /// showing this texture, so fetch the current texture every frame.
+    pub fn current_texture(&self) -> Texture {
+        self.textures[self.current].clone()
+    }
+
+    /// Number of textures in the ring.
+    pub fn count(&self) -> usize {
+        self.textures.len()
+    }
+
+    /// Size of each texture in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        self.textures[0].full_size()
+    }
+}
+
+fn validate_ring_length(count: usize) -> errors::Result<()> {
+    if count < 2 {
+        Err(errors::Error::InvalidRingLength(count))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::test::dummy_texture;
+
+    #[test]
+    fn test_ring_alternates() {
+        let (_rx1, first) = dummy_texture(1, 16, 16);
+        let (_rx2, second) = dummy_texture(2, 16, 16);
+        let mut ring = StreamingTexture::from_textures(vec![first, second]);
+        assert_eq!(ring.count(), 2);
+
+        let mut drawn = vec![];
+        for _ in 0..4 {
+            let written = ring.next_frame_buffer().raw_handle();
+            let current = ring.current_texture().raw_handle();
+            // Uploads and draws never share a texture.
+            assert_ne!(written, current);
+            drawn.push(current);
+        }
+        assert_eq!(drawn, [2, 1, 2, 1]);
+
+        assert!(matches!(
+            validate_ring_length(1),
+            Err(errors::Error::InvalidRingLength(1))
+        ));
+        assert!(validate_ring_length(2).is_ok());
+    }
+}
@@ -1,11 +1,252 @@
 use crate::{
-    device::{Destroy, GraphicDevice},
-    errors::{self, debug_assert_gl, gl_error, gl_result},
+    device::{Destroy, DestroySender, GraphicDevice},
+    errors::{self, debug_assert_gl, gl_error, gl_error_at, gl_result},
     marker::Invariant,
     rect::Rect,
+    sampler::Filter,
 };
 use glow::HasContext;
-use std::{cell::RefCell, rc::Rc, sync::mpsc::Sender};
+#[cfg(feature = "image")]
+use std::borrow::Cow;
+#[cfg(feature = "image")]
+use std::path::Path;
+use std::{cell::RefCell, rc::Rc};
+
+/// Layout of texel data in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Red, green, blue and alpha channels, one byte each.
+    Rgba8,
+    /// Red, green and blue channels, one byte each.
+    Rgb8,
+    /// Single red channel of one byte.
+    R8,
+}
+
+impl PixelFormat {
+    /// Number of bytes each pixel occupies.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Rgba8 => 4,
+            PixelFormat::Rgb8 => 3,
+            PixelFormat::R8 => 1,
+        }
+    }
+
+    /// Number of bytes required to hold an image of the given size.
+    pub fn data_len(self, width: u32, height: u32) -> usize {
+        width as usize * height as usize * self.bytes_per_pixel()
+    }
+
+    /// Sized format of the texture storage.
+    pub(crate) fn internal_format(self) -> u32 {
+        match self {
+            PixelFormat::Rgba8 => glow::RGBA8,
+            PixelFormat::Rgb8 => glow::RGB8,
+            PixelFormat::R8 => glow::R8,
+        }
+    }
+
+    /// Channels of the pixel data in client memory.
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            PixelFormat::Rgba8 => glow::RGBA,
+            PixelFormat::Rgb8 => glow::RGB,
+            PixelFormat::R8 => glow::RED,
+        }
+    }
+
+    /// Format storing an image's colour type without conversion, if any.
+    #[cfg(feature = "image")]
+    pub fn from_color_type(color_type: image::ColorType) -> Option<Self> {
+        match color_type {
+            image::ColorType::Rgba8 => Some(PixelFormat::Rgba8),
+            image::ColorType::Rgb8 => Some(PixelFormat::Rgb8),
+            image::ColorType::L8 => Some(PixelFormat::R8),
+            _ => None,
+        }
+    }
+}
+
+/// Rectangular region of a larger RGBA8 image in memory.
+///
+/// Allows a part of an image to be uploaded without first copying
+/// it into a tightly packed buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView<'a> {
+    data: &'a [u8],
+    /// Size in pixels of the whole source image.
+    size: [u32; 2],
+    /// Region of the source image to view.
+    rect: Rect<u32>,
+}
+
+impl<'a> ImageView<'a> {
+    /// # Errors
+    ///
+    /// Returns `InvalidImageData` if the length of `data` does not match
+    /// the image size.
+    ///
+    /// Returns `InvalidSubTexture` if the region does not fit inside
+    /// the image.
+    pub fn new(data: &'a [u8], width: u32, height: u32, rect: Rect<u32>) -> errors::Result<Self> {
+        let expected_len = PixelFormat::Rgba8.data_len(width, height);
+        if data.len() != expected_len {
+            return Err(errors::Error::InvalidImageData {
+                expected: expected_len,
+                actual: data.len(),
+            });
+        }
+
+        let image_rect = Rect {
+            pos: [0, 0],
+            size: [width, height],
+        };
+        if !Self::contains(&image_rect, &rect) {
+            return Err(errors::Error::InvalidSubTexture {
+                source: image_rect,
+                target: rect,
+            });
+        }
+
+        Ok(Self {
+            data,
+            size: [width, height],
+            rect,
+        })
+    }
+
+    pub fn rect(&self) -> Rect<u32> {
+        self.rect
+    }
+
+    /// Computed in 64 bits, so rectangles at the edge of the `u32`
+    /// range don't overflow.
+    fn contains(outer: &Rect<u32>, inner: &Rect<u32>) -> bool {
+        let end = |rect: &Rect<u32>, axis: usize| rect.pos[axis] as u64 + rect.size[axis] as u64;
+
+        inner.pos[0] >= outer.pos[0]
+            && inner.pos[1] >= outer.pos[1]
+            && end(inner, 0) <= end(outer, 0)
+            && end(inner, 1) <= end(outer, 1)
+    }
+}
+
+/// Block compressed texture formats.
+///
+/// Compressed formats encode blocks of 4x4 texels in a fixed number of bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedFormat {
+    /// S3TC DXT1, RGB with 1-bit alpha. 8 bytes per block.
+    Dxt1,
+    /// S3TC DXT5, RGBA with interpolated alpha. 16 bytes per block.
+    Dxt5,
+    /// ETC2 RGB. 8 bytes per block.
+    Etc2Rgb8,
+    /// ETC2 RGBA with EAC alpha. 16 bytes per block.
+    Etc2Rgba8,
+}
+
+impl CompressedFormat {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            CompressedFormat::Dxt1 => 0x83F1, // GL_COMPRESSED_RGBA_S3TC_DXT1_EXT
+            CompressedFormat::Dxt5 => 0x83F3, // GL_COMPRESSED_RGBA_S3TC_DXT5_EXT
+            CompressedFormat::Etc2Rgb8 => glow::COMPRESSED_RGB8_ETC2,
+            CompressedFormat::Etc2Rgba8 => glow::COMPRESSED_RGBA8_ETC2_EAC,
+        }
+    }
+
+    /// Extension the device must support to use this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            CompressedFormat::Dxt1 | CompressedFormat::Dxt5 => "GL_EXT_texture_compression_s3tc",
+            CompressedFormat::Etc2Rgb8 | CompressedFormat::Etc2Rgba8 => "GL_ARB_ES3_compatibility",
+        }
+    }
+
+    /// Number of bytes each 4x4 block occupies.
+    pub fn block_bytes(self) -> usize {
+        match self {
+            CompressedFormat::Dxt1 | CompressedFormat::Etc2Rgb8 => 8,
+            CompressedFormat::Dxt5 | CompressedFormat::Etc2Rgba8 => 16,
+        }
+    }
+
+    /// Number of bytes required to hold a compressed image of the given size.
+    ///
+    /// Partial blocks at the edges still occupy a whole block.
+    pub fn data_len(self, width: u32, height: u32) -> usize {
+        block_count(width) * block_count(height) * self.block_bytes()
+    }
+}
+
+/// Number of 4 texel wide blocks covering the given number of texels.
+fn block_count(texels: u32) -> usize {
+    (texels as usize).div_ceil(4)
+}
+
+/// OpenGL's initial `GL_TEXTURE_MIN_LOD`, which doesn't restrict the level.
+const DEFAULT_MIN_LOD: f32 = -1000.0;
+
+/// OpenGL's initial `GL_TEXTURE_MAX_LOD`.
+const DEFAULT_MAX_LOD: f32 = 1000.0;
+
+/// Level of detail parameters, selecting which mipmap levels are sampled,
+/// and the anisotropic filtering level.
+///
+/// The defaults are OpenGL's, which don't restrict or bias the selected
+/// level, and don't filter anisotropically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureParams {
+    /// Lowest level of detail sampled. Lower levels are larger and sharper.
+    pub min_lod: f32,
+    /// Highest level of detail sampled.
+    pub max_lod: f32,
+    /// Added to the computed level of detail before sampling. Negative
+    /// values favour sharper mipmaps, positive values blurrier ones.
+    ///
+    /// Not available in OpenGL ES, where it's ignored.
+    pub lod_bias: f32,
+    /// Anisotropic filtering level, improving sharpness when the texture
+    /// is viewed at grazing angles. 1 disables it.
+    ///
+    /// Clamped to the device's `max_anisotropy`. Ignored when the context
+    /// lacks `GL_EXT_texture_filter_anisotropic`.
+    pub anisotropy: f32,
+}
+
+impl Default for TextureParams {
+    fn default() -> Self {
+        Self {
+            min_lod: DEFAULT_MIN_LOD,
+            max_lod: DEFAULT_MAX_LOD,
+            lod_bias: 0.0,
+            anisotropy: 1.0,
+        }
+    }
+}
+
+impl TextureParams {
+    /// Texture parameters and values to set, skipping the bias when the
+    /// context is OpenGL ES, and the anisotropy when it's unsupported.
+    fn gl_parameters(&self, gles: bool, max_anisotropy: Option<f32>) -> Vec<(u32, f32)> {
+        let mut parameters = vec![
+            (glow::TEXTURE_MIN_LOD, self.min_lod),
+            (glow::TEXTURE_MAX_LOD, self.max_lod),
+        ];
+        if !gles {
+            parameters.push((glow::TEXTURE_LOD_BIAS, self.lod_bias));
+        }
+        if let Some(max) = max_anisotropy {
+            parameters.push((
+                glow::TEXTURE_MAX_ANISOTROPY,
+                clamp_anisotropy(self.anisotropy, max),
+            ));
+        }
+        parameters
+    }
+}
 
 /// Handle to a texture located in video memory.
 #[derive(Clone)]
@@ -31,7 +272,35 @@ pub struct Texture {
 }
 
 impl Texture {
+    /// Width and height of the placeholder for textures that failed to load.
+    pub const MISSING_SIZE: u32 = 16;
+
     pub fn new(device: &GraphicDevice, width: u32, height: u32) -> errors::Result<Self> {
+        Self::with_format(device, width, height, PixelFormat::Rgba8)
+    }
+
+    /// Create an RGBA8 texture with the given level of detail parameters.
+    pub fn with_params(
+        device: &GraphicDevice,
+        width: u32,
+        height: u32,
+        params: TextureParams,
+    ) -> errors::Result<Self> {
+        let texture = Self::new(device, width, height)?;
+        texture.set_params(device, params)?;
+        Ok(texture)
+    }
+
+    /// Create a texture storing the given pixel format.
+    ///
+    /// Single channel textures are sampled as grey, with the red channel
+    /// copied to green and blue, and opaque alpha.
+    pub fn with_format(
+        device: &GraphicDevice,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+    ) -> errors::Result<Self> {
         // Upfront validations.
         Self::validate_size(width, height)?;
 
@@ -47,62 +316,198 @@ impl Texture {
         // Important: Non power of two textures may not have mipmaps
 
         unsafe {
+            // Textures can be created lazily while drawing, like the white
+            // texture, so the unit's binding must survive.
+            let _save = TextureSave::new(device);
+
             let handle = gl_result(&device.gl, device.gl.create_texture())?;
             device.gl.bind_texture(glow::TEXTURE_2D, Some(handle));
 
             // Allocate video memory for texture
             device.gl.tex_image_2d(
                 glow::TEXTURE_2D,
-                0,                   // Mip level
-                glow::RGBA8 as i32,  // Internal colour format
-                width as i32,        // Width in pixels
-                height as i32,       // Height in pixels
-                0,                   // Border
-                glow::RGBA,          // Format
-                glow::UNSIGNED_BYTE, // Color data type.
-                None,                // Actual data can be uploaded later.
+                0,                               // Mip level
+                format.internal_format() as i32, // Internal colour format
+                width as i32,                    // Width in pixels
+                height as i32,                   // Height in pixels
+                0,                               // Border
+                format.to_gl(),                  // Format
+                glow::UNSIGNED_BYTE,             // Color data type.
+                None,                            // Actual data can be uploaded later.
             );
-            gl_error(&device.gl, ())?;
+            gl_error_at(&device.gl, "tex_image_2d", ())?;
 
-            device.gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MIN_FILTER,
-                glow::NEAREST as i32,
-            );
-            device.gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_MAG_FILTER,
-                glow::NEAREST as i32,
-            );
-            device.gl.tex_parameter_i32(
-                glow::TEXTURE_2D,
-                glow::TEXTURE_WRAP_S,
-                glow::CLAMP_TO_EDGE as i32,
-            );
-            device.gl.tex_parameter_i32(
+            Self::set_default_parameters(device);
+            if format == PixelFormat::R8 {
+                device.gl.tex_parameter_i32(
+                    glow::TEXTURE_2D,
+                    glow::TEXTURE_SWIZZLE_G,
+                    glow::RED as i32,
+                );
+                device.gl.tex_parameter_i32(
+                    glow::TEXTURE_2D,
+                    glow::TEXTURE_SWIZZLE_B,
+                    glow::RED as i32,
+                );
+            }
+
+            let texture = Self::from_allocated(device, handle, width, height);
+            texture.handle.borrow_mut().format = format;
+            Ok(texture)
+        }
+    }
+
+    /// Create a texture from block compressed image data.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Unsupported` if the device lacks the extension required
+    /// by the format, or the device was not created with a function loader.
+    ///
+    /// Returns `InvalidImageData` if the length of `data` does not match
+    /// the compressed size of the given dimensions.
+    ///
+    /// Returns `UploadBudgetExceeded` when the device's upload budget for
+    /// the frame is spent.
+    pub fn from_compressed(
+        device: &GraphicDevice,
+        format: CompressedFormat,
+        width: u32,
+        height: u32,
+        data: &[u8],
+    ) -> errors::Result<Self> {
+        // Upfront validations.
+        Self::validate_size(width, height)?;
+
+        if !device.has_extension(format.extension()) {
+            return Err(errors::Error::Unsupported(format.extension()));
+        }
+
+        let compressed_tex_image_2d = device
+            .fns
+            .compressed_tex_image_2d
+            .ok_or(errors::Error::Unsupported("glCompressedTexImage2D"))?;
+
+        let expected_len = format.data_len(width, height);
+        if data.len() != expected_len {
+            return Err(errors::Error::InvalidImageData {
+                expected: expected_len,
+                actual: data.len(),
+            });
+        }
+        device.charge_upload(data.len())?;
+
+        unsafe {
+            let handle = gl_result(&device.gl, device.gl.create_texture())?;
+            device.gl.bind_texture(glow::TEXTURE_2D, Some(handle));
+
+            compressed_tex_image_2d(
                 glow::TEXTURE_2D,
-                glow::TEXTURE_WRAP_T,
-                glow::CLAMP_TO_EDGE as i32,
+                0,                 // Mip level
+                format.to_gl(),    // Internal compressed format
+                width as i32,      // Width in pixels
+                height as i32,     // Height in pixels
+                0,                 // Border
+                data.len() as i32, // Size of data in bytes
+                data.as_ptr() as *const _,
             );
+            if let Err(err) = gl_error(&device.gl, ()) {
+                device.gl.bind_texture(glow::TEXTURE_2D, None);
+                device.gl.delete_texture(handle);
+                return Err(err);
+            }
+
+            Self::set_default_parameters(device);
             device.gl.bind_texture(glow::TEXTURE_2D, None);
 
-            // Match the allocated texture.
-            let rect = Rect {
-                pos: [0, 0],
+            Ok(Self::from_allocated(device, handle, width, height))
+        }
+    }
+
+    /// Sets the sampling parameters of the currently bound texture.
+    unsafe fn set_default_parameters(device: &GraphicDevice) {
+        device.gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MIN_FILTER,
+            glow::NEAREST as i32,
+        );
+        device.gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_MAG_FILTER,
+            glow::NEAREST as i32,
+        );
+        device.gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_WRAP_S,
+            glow::CLAMP_TO_EDGE as i32,
+        );
+        device.gl.tex_parameter_i32(
+            glow::TEXTURE_2D,
+            glow::TEXTURE_WRAP_T,
+            glow::CLAMP_TO_EDGE as i32,
+        );
+    }
+
+    /// Wraps a texture already allocated in video memory, taking ownership.
+    fn from_allocated(
+        device: &GraphicDevice,
+        handle: glow::Texture,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self::from_handle(device.destroy_sender(), handle, width, height, true)
+    }
+
+    /// Wraps a texture created outside of this crate, for example by
+    /// another OpenGL library sharing the context, without taking ownership.
+    ///
+    /// The texture is never deleted by this crate. Dropping the last
+    /// reference only releases the staging buffers created by
+    /// `update_data_async`, which are owned by the device.
+    ///
+    /// # Safety
+    ///
+    /// The handle must name a 2D texture of the given size in the device's
+    /// context, and the owner must keep it alive for as long as the returned
+    /// texture, or any sub texture of it, is in use.
+    pub unsafe fn from_raw_borrowed(
+        device: &GraphicDevice,
+        handle: glow::Texture,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        Self::from_handle(device.destroy_sender(), handle, width, height, false)
+    }
+
+    fn from_handle(
+        destroy: DestroySender,
+        handle: glow::Texture,
+        width: u32,
+        height: u32,
+        owned: bool,
+    ) -> Self {
+        // Match the allocated texture.
+        let rect = Rect {
+            pos: [0, 0],
+            size: [width, height],
+        };
+
+        Self {
+            texture: handle,
+            orig_size: [width, height],
+            rect,
+            handle: Rc::new(RefCell::new(TextureHandle {
+                handle,
                 size: [width, height],
-            };
-
-            Ok(Self {
-                texture: handle,
-                orig_size: [width, height],
-                rect,
-                handle: Rc::new(RefCell::new(TextureHandle {
-                    handle,
-                    size: [width, height],
-                    destroy: device.destroy_sender(),
-                    _invariant: Default::default(),
-                })),
-            })
+                format: PixelFormat::Rgba8,
+                params: TextureParams::default(),
+                pixel_buffers: None,
+                owned,
+                #[cfg(debug_assertions)]
+                exclusive_regions: vec![],
+                destroy,
+                _invariant: Default::default(),
+            })),
         }
     }
 
@@ -142,6 +547,38 @@ impl Texture {
         })
     }
 
+    /// Create a sub texture of a region no other exclusive sub texture
+    /// of the same storage was given, like a slot in an atlas.
+    ///
+    /// In debug builds the issued regions are tracked, catching packing
+    /// bugs early. Release builds behave like `new_sub`.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, when the region overlaps one previously issued
+    /// by this method.
+    pub fn new_sub_exclusive(&self, pos: [u32; 2], size: [u32; 2]) -> errors::Result<Self> {
+        let sub_texture = self.new_sub(pos, size)?;
+
+        #[cfg(debug_assertions)]
+        {
+            let mut handle = self.handle.borrow_mut();
+            if let Some(issued) = handle
+                .exclusive_regions
+                .iter()
+                .find(|issued| issued.intersects(&sub_texture.rect))
+            {
+                panic!(
+                    "Sub-texture {} overlaps previously issued sub-texture {} of texture {}",
+                    sub_texture.rect, issued, self.texture
+                );
+            }
+            handle.exclusive_regions.push(sub_texture.rect);
+        }
+
+        Ok(sub_texture)
+    }
+
     fn validate_size(width: u32, height: u32) -> errors::Result<()> {
         if width == 0 || height == 0 {
             return Err(crate::errors::Error::InvalidTextureSize(width, height));
@@ -155,11 +592,213 @@ impl Texture {
         n != 0 && ((n & n - 1) == 0)
     }
 
+    /// Split this texture's view into equal sub-views side by side,
+    /// from left to right, like the frames of an animation strip.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnevenSplit` if the width is not a multiple of the count,
+    /// or the count is 0.
+    pub fn split_horizontal(&self, count: u32) -> errors::Result<Vec<Self>> {
+        let [width, height] = self.rect.size;
+        let part_width = split_length(width, count)?;
+        let [x, y] = self.rect.pos;
+
+        (0..count)
+            .map(|index| self.new_sub([x + index * part_width, y], [part_width, height]))
+            .collect()
+    }
+
+    /// Split this texture's view into equal sub-views stacked from top
+    /// to bottom.
+    ///
+    /// # Errors
+    ///
+    /// Returns `UnevenSplit` if the height is not a multiple of the count,
+    /// or the count is 0.
+    pub fn split_vertical(&self, count: u32) -> errors::Result<Vec<Self>> {
+        let [width, height] = self.rect.size;
+        let part_height = split_length(height, count)?;
+        let [x, y] = self.rect.pos;
+
+        (0..count)
+            .map(|index| self.new_sub([x, y + index * part_height], [width, part_height]))
+            .collect()
+    }
+
     /// Queries the device support for non-power-of-two-textures.
     pub fn is_npot_available(device: &GraphicDevice) -> bool {
         device.has_extension("GL_ARB_texture_non_power_of_two")
     }
 
+    /// Loads an image file into a new texture.
+    #[cfg(feature = "image")]
+    pub fn from_path<P: AsRef<Path>>(device: &GraphicDevice, path: P) -> errors::Result<Self> {
+        let image = image::open(path)
+            .map_err(|err| errors::Error::ImageLoad(err.to_string()))?
+            .to_rgba8();
+
+        let mut texture = Self::new(device, image.width(), image.height())?;
+        texture.update_data(device, image.as_raw())?;
+
+        Ok(texture)
+    }
+
+    /// Uploads a decoded image into a new texture, storing its colour
+    /// type without expanding it to RGBA when possible.
+    ///
+    /// Greyscale images become `R8` textures, and RGB images `Rgb8`
+    /// textures. Other colour types are converted to `Rgba8`.
+    #[cfg(feature = "image")]
+    pub fn from_dynamic_image(
+        device: &GraphicDevice,
+        image: &image::DynamicImage,
+    ) -> errors::Result<Self> {
+        let (format, data) = dynamic_image_data(image);
+        let (width, height) = image::GenericImageView::dimensions(image);
+
+        let mut texture = Self::with_format(device, width, height, format)?;
+        texture.update_data(device, &data)?;
+
+        Ok(texture)
+    }
+
+    /// Loads an image file into a new texture, falling back to the device's
+    /// missing texture when the image can't be loaded or uploaded.
+    ///
+    /// Only fails if the missing texture itself can't be created.
+    #[cfg(feature = "image")]
+    pub fn from_path_or_missing<P: AsRef<Path>>(
+        device: &GraphicDevice,
+        path: P,
+    ) -> errors::Result<Self> {
+        match Self::from_path(device, path.as_ref()) {
+            Ok(texture) => Ok(texture),
+            Err(err) => {
+                warn!(
+                    "Using missing texture for {}: {}",
+                    path.as_ref().display(),
+                    err
+                );
+                device.missing_texture()
+            }
+        }
+    }
+
+    /// Reads back the texels of this texture's view as RGBA8.
+    ///
+    /// Rows are in storage order, where uploaded images have their
+    /// first row at the top.
+    pub fn read_pixels(&self, device: &GraphicDevice) -> errors::Result<Vec<u8>> {
+        let data = self.read_storage_pixels(device)?;
+
+        if self.is_sub_view() {
+            Ok(crop_rgba8(&data, self.orig_size[0], self.rect))
+        } else {
+            Ok(data)
+        }
+    }
+
+    /// Reads back the texels of the whole texture storage as RGBA8,
+    /// regardless of this texture's view.
+    pub(crate) fn read_storage_pixels(&self, device: &GraphicDevice) -> errors::Result<Vec<u8>> {
+        let [full_width, full_height] = self.orig_size;
+        let mut data = vec![0; PixelFormat::Rgba8.data_len(full_width, full_height)];
+
+        unsafe {
+            let _save = TextureSave::new(device);
+
+            device
+                .gl
+                .bind_texture(glow::TEXTURE_2D, Some(self.raw_handle()));
+            device.gl.get_tex_image(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelPackData::Slice(&mut data),
+            );
+            gl_error_at(&device.gl, "get_tex_image", ())?;
+        }
+
+        Ok(data)
+    }
+
+    /// Saves this texture's view to a PNG file, for inspecting atlases
+    /// outside the application.
+    ///
+    /// The image has the same orientation as the sprites drawn on screen.
+    /// Single channel textures are saved as greyscale.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ImageSave` when the file could not be written.
+    #[cfg(feature = "image")]
+    pub fn save_png<P: AsRef<Path>>(&self, device: &GraphicDevice, path: P) -> errors::Result<()> {
+        let data = self.read_pixels(device)?;
+        let [width, height] = self.rect.size;
+
+        match self.format() {
+            // Read back with the value in the red channel only.
+            PixelFormat::R8 => {
+                let grey = data.chunks(4).map(|texel| texel[0]).collect();
+                write_png(path.as_ref(), width, height, image::ColorType::L8, grey)
+            }
+            _ => write_png(path.as_ref(), width, height, image::ColorType::Rgba8, data),
+        }
+    }
+
+    /// Whether this texture is a view into a sub-rectangle of
+    /// a larger texture.
+    pub fn is_sub_view(&self) -> bool {
+        self.rect.pos != [0, 0] || self.rect.size != self.orig_size
+    }
+
+    /// Rectangle of this texture's view into the full texture, in texels.
+    pub fn rect(&self) -> Rect<u32> {
+        self.rect
+    }
+
+    /// Size in texels of the whole texture in video memory.
+    pub fn full_size(&self) -> [u32; 2] {
+        self.orig_size
+    }
+
+    /// Converts a rectangle in texels, relative to this texture's view,
+    /// to normalised texture coordinates of the full texture.
+    ///
+    /// For a sub-texture in an atlas, the result addresses the region
+    /// within the atlas.
+    pub fn pixel_rect_to_uv(&self, pixel_rect: Rect<u32>) -> Rect<f32> {
+        let [full_w, full_h] = [self.orig_size[0] as f32, self.orig_size[1] as f32];
+        let [x, y] = [
+            self.rect.pos[0] + pixel_rect.pos[0],
+            self.rect.pos[1] + pixel_rect.pos[1],
+        ];
+
+        Rect {
+            pos: [x as f32 / full_w, y as f32 / full_h],
+            size: [
+                pixel_rect.size[0] as f32 / full_w,
+                pixel_rect.size[1] as f32 / full_h,
+            ],
+        }
+    }
+
+    /// Key identifying the texture storage in video memory, used to
+    /// group and sort draws by texture.
+    ///
+    /// Combines the OpenGL texture name in the upper 32 bits with
+    /// the array layer in the lower 32 bits. Plain 2D textures are
+    /// always layer 0. Clones and sub-views of the same texture share
+    /// a key.
+    ///
+    /// The key is not derived from an allocation pointer, so it's stable
+    /// across frames for as long as the texture lives.
+    pub fn sort_key(&self) -> u64 {
+        (self.texture as u64) << 32
+    }
+
     pub fn raw_handle(&self) -> glow::Texture {
         self.handle.borrow().handle
     }
@@ -173,7 +812,147 @@ impl Texture {
         self.update_sub_data(device, [0, 0], size, data)
     }
 
+    /// Uploads image data to the texture's storage through a pixel buffer object.
+    ///
+    /// The data is copied into a mapped `GL_PIXEL_UNPACK_BUFFER`, and the
+    /// transfer into the texture is performed by the device without blocking
+    /// the render thread. Two buffers are used in turn, so consecutive uploads
+    /// don't wait on each other.
+    ///
+    /// The data is in the texture's `format`, with tightly packed rows.
+    /// The buffers are allocated on the first call, and deallocated with the texture.
+    ///
+    /// Requires OpenGL 3.0 or later.
+    pub fn update_data_async(
+        &mut self,
+        device: &GraphicDevice,
+        data: &[u8],
+    ) -> crate::errors::Result<()> {
+        let mut handle = self.handle.borrow_mut();
+        let [width, height] = handle.size;
+        let format = handle.format;
+
+        let expected_len = format.data_len(width, height);
+        if data.len() != expected_len {
+            return Err(crate::errors::Error::InvalidImageData {
+                expected: expected_len,
+                actual: data.len(),
+            });
+        }
+        device.charge_upload(data.len())?;
+
+        unsafe {
+            if handle.pixel_buffers.is_none() {
+                let first = gl_result(&device.gl, device.gl.create_buffer())?;
+                let second = match gl_result(&device.gl, device.gl.create_buffer()) {
+                    Ok(buffer) => buffer,
+                    Err(err) => {
+                        device.gl.delete_buffer(first);
+                        return Err(err);
+                    }
+                };
+                handle.pixel_buffers = Some(PixelBuffers::new([first, second]));
+            }
+
+            let texture = handle.handle;
+            let buffer = handle.pixel_buffers.as_mut().unwrap().advance();
+
+            let _save = TextureSave::new(device);
+
+            device
+                .gl
+                .bind_buffer(glow::PIXEL_UNPACK_BUFFER, Some(buffer));
+
+            let result = Self::stream_pixel_buffer(device, texture, format, [width, height], data);
+
+            // While a buffer is bound to the unpack target, every other
+            // texture upload would read from it instead of client memory.
+            device.gl.bind_buffer(glow::PIXEL_UNPACK_BUFFER, None);
+
+            result
+        }
+    }
+
+    /// Writes data into the bound pixel unpack buffer, and starts
+    /// the transfer into the texture.
+    unsafe fn stream_pixel_buffer(
+        device: &GraphicDevice,
+        texture: u32,
+        format: PixelFormat,
+        size: [u32; 2],
+        data: &[u8],
+    ) -> crate::errors::Result<()> {
+        // Orphan the previous storage, so the device can keep
+        // reading from it while we write the new data.
+        device.gl.buffer_data_size(
+            glow::PIXEL_UNPACK_BUFFER,
+            data.len() as i32,
+            glow::STREAM_DRAW,
+        );
+        gl_error(&device.gl, ())?;
+
+        let ptr = device.gl.map_buffer_range(
+            glow::PIXEL_UNPACK_BUFFER,
+            0,
+            data.len() as i32,
+            glow::MAP_WRITE_BIT | glow::MAP_INVALIDATE_BUFFER_BIT,
+        );
+        gl_error(&device.gl, ())?;
+        if ptr.is_null() {
+            return Err(crate::errors::Error::OpenGlMessage(
+                "failed to map pixel unpack buffer".to_string(),
+            ));
+        }
+
+        // SAFETY: The mapped range was allocated with the length of the data.
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+        device.gl.unmap_buffer(glow::PIXEL_UNPACK_BUFFER);
+
+        // Rows of fewer than 4 byte channels may not start on
+        // the default 4-byte boundary.
+        let row_len = format.data_len(size[0], 1);
+        // Alignment is always a power of two.
+        let alignment = device.get_unpack_alignment();
+        let realign = row_len & (alignment as usize - 1) != 0;
+        if realign {
+            device.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+        }
+
+        device.gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+        device.gl.tex_sub_image_2d(
+            glow::TEXTURE_2D,
+            0,                   // level
+            0,                   // x_offset
+            0,                   // y_offset
+            size[0] as i32,      // width
+            size[1] as i32,      // height
+            format.to_gl(),      // pixel format
+            glow::UNSIGNED_BYTE, // color data type
+            glow::PixelUnpackData::BufferOffset(0),
+        );
+        let result = gl_error_at(&device.gl, "tex_sub_image_2d", ());
+
+        if realign {
+            device
+                .gl
+                .pixel_store_i32(glow::UNPACK_ALIGNMENT, alignment as i32);
+        }
+
+        result
+    }
+
+    /// Format of the texture storage, and of the data passed to
+    /// `update_data` and `update_sub_data`.
+    pub fn format(&self) -> PixelFormat {
+        self.handle.borrow().format
+    }
+
     /// Uploads image data to the texture's storage on the GPU device.
+    ///
+    /// The data is in the texture's `format`, with tightly packed rows.
+    ///
+    /// Returns `UploadBudgetExceeded` without uploading when the device's
+    /// upload budget for the frame is spent.
     pub fn update_sub_data(
         &mut self,
         device: &GraphicDevice,
@@ -181,30 +960,39 @@ impl Texture {
         size: [u32; 2],
         data: &[u8],
     ) -> crate::errors::Result<()> {
-        // TODO: Unbind GL_PIXEL_UNPACK_BUFFER
-        //       https://www.khronos.org/opengl/wiki/GLAPI/glTexSubImage2D
-        //       If a non-zero named buffer object is bound to the
-        //       GL_PIXEL_UNPACK_BUFFER target (see glBindBuffer)
-        //       while a texture image is specified, data is
-        //       treated as a byte offset into the buffer object's
-        //       data store.
+        // Note: If a buffer is bound to GL_PIXEL_UNPACK_BUFFER, data is
+        //       treated as an offset into the buffer. The asynchronous
+        //       upload unbinds its buffers when done, so a client slice is safe here.
 
         // TODO: Validate given pos and size against target texture rectangle. Must fit.
 
+        // Borrow mut to enforce runtime borrow rules.
+        let handle = self.handle.borrow_mut();
+        let format = handle.format;
+
         // Upfront validation
-        let expected_len = size[0] as usize * size[1] as usize * 4;
+        let expected_len = format.data_len(size[0], size[1]);
         if data.len() != expected_len {
             return Err(crate::errors::Error::InvalidImageData {
                 expected: expected_len,
                 actual: data.len(),
             });
         }
+        device.charge_upload(data.len())?;
 
-        // Borrow mut to enforce runtime borrow rules.
-        let handle = self.handle.borrow_mut();
+        // Rows of fewer than 4 byte channels may not start on
+        // the default 4-byte boundary.
+        let row_len = format.data_len(size[0], 1);
+        // Alignment is always a power of two.
+        let alignment = device.get_unpack_alignment();
+        let realign = row_len & (alignment as usize - 1) != 0;
 
         unsafe {
-            let _save = TextureSave::new(&device);
+            let _save = TextureSave::new(device);
+
+            if realign {
+                device.gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            }
 
             device
                 .gl
@@ -216,21 +1004,333 @@ impl Texture {
                 pos[1] as i32,       // y_offset
                 size[0] as i32,      // width
                 size[1] as i32,      // height
-                glow::RGBA,          // pixel format
+                format.to_gl(),      // pixel format
                 glow::UNSIGNED_BYTE, // color data type
                 glow::PixelUnpackData::Slice(data),
             );
-            gl_error(&device.gl, ())?;
+            let result = gl_error_at(&device.gl, "tex_sub_image_2d", ());
+
+            if realign {
+                device
+                    .gl
+                    .pixel_store_i32(glow::UNPACK_ALIGNMENT, alignment as i32);
+            }
+
+            result
         }
+    }
+
+    /// Switch both the minifying and magnifying filters between
+    /// `LINEAR` when smooth, and `NEAREST` when crisp.
+    ///
+    /// Affects the whole texture in video memory, including all
+    /// sub-views sharing it.
+    pub fn set_smooth(&self, device: &GraphicDevice, smooth: bool) {
+        let filter = if smooth {
+            Filter::Linear
+        } else {
+            Filter::Nearest
+        };
+
+        unsafe {
+            let _save = TextureSave::new(device);
 
+            device
+                .gl
+                .bind_texture(glow::TEXTURE_2D, Some(self.raw_handle()));
+            device.gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                filter.to_gl() as i32,
+            );
+            device.gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                filter.to_gl() as i32,
+            );
+            debug_assert_gl(&device.gl, ());
+        }
+    }
+
+    /// Set the level of detail parameters used when sampling mipmaps, and
+    /// the anisotropic filtering level.
+    ///
+    /// The bias is not set on OpenGL ES contexts, which lack it. The
+    /// anisotropy kept is the level applied, after clamping, or 1 when
+    /// the context doesn't support anisotropic filtering.
+    ///
+    /// Affects the whole texture in video memory, including all
+    /// sub-views sharing it.
+    pub fn set_params(
+        &self,
+        device: &GraphicDevice,
+        mut params: TextureParams,
+    ) -> errors::Result<()> {
+        let max_anisotropy = device.max_anisotropy();
+        params.anisotropy =
+            max_anisotropy.map_or(1.0, |max| clamp_anisotropy(params.anisotropy, max));
+
+        unsafe {
+            let _save = TextureSave::new(device);
+
+            device
+                .gl
+                .bind_texture(glow::TEXTURE_2D, Some(self.raw_handle()));
+            for (parameter, value) in params.gl_parameters(device.is_gles(), max_anisotropy) {
+                device
+                    .gl
+                    .tex_parameter_f32(glow::TEXTURE_2D, parameter, value);
+            }
+            gl_error_at(&device.gl, "tex_parameter_f32", ())?;
+        }
+
+        self.handle.borrow_mut().params = params;
         Ok(())
     }
 
+    /// Level of detail and anisotropy parameters last set with `set_params`.
+    pub fn params(&self) -> TextureParams {
+        self.handle.borrow().params
+    }
+
+    /// Copies a region of the source texture into this texture, without
+    /// reading the texels back to the CPU.
+    ///
+    /// The source rectangle is relative to the source texture's view, and
+    /// the destination position is relative to this texture's view. Both
+    /// regions must fit within their views.
+    ///
+    /// Uses `glCopyImageSubData` when `GL_ARB_copy_image` is available,
+    /// otherwise blits between two temporary framebuffers.
+    pub fn copy_region(
+        &self,
+        device: &GraphicDevice,
+        src: &Texture,
+        src_rect: Rect<u32>,
+        dst_pos: [u32; 2],
+    ) -> crate::errors::Result<()> {
+        let (src_abs, dst_abs) = Self::copy_rects(src.rect, src_rect, self.rect, dst_pos)?;
+        let [width, height] = [src_abs.size[0] as i32, src_abs.size[1] as i32];
+        let (src_handle, dst_handle) = (src.raw_handle(), self.raw_handle());
+
+        unsafe {
+            match device.fns.copy_image_sub_data {
+                Some(copy_image_sub_data) if device.has_extension("GL_ARB_copy_image") => {
+                    copy_image_sub_data(
+                        src_handle,
+                        glow::TEXTURE_2D,
+                        0,
+                        src_abs.pos[0] as i32,
+                        src_abs.pos[1] as i32,
+                        0,
+                        dst_handle,
+                        glow::TEXTURE_2D,
+                        0,
+                        dst_abs.pos[0] as i32,
+                        dst_abs.pos[1] as i32,
+                        0,
+                        width,
+                        height,
+                        1,
+                    );
+                    gl_error_at(&device.gl, "copy_image_sub_data", ())
+                }
+                _ => Self::blit_region(device, src_handle, dst_handle, src_abs, dst_abs),
+            }
+        }
+    }
+
+    /// Fallback copy, attaching each texture to a framebuffer and blitting between them.
+    unsafe fn blit_region(
+        device: &GraphicDevice,
+        src_handle: u32,
+        dst_handle: u32,
+        src_abs: Rect<u32>,
+        dst_abs: Rect<u32>,
+    ) -> crate::errors::Result<()> {
+        let gl = &device.gl;
+        let read_binding = gl.get_parameter_i32(glow::READ_FRAMEBUFFER_BINDING) as u32;
+        let draw_binding = gl.get_parameter_i32(glow::DRAW_FRAMEBUFFER_BINDING) as u32;
+
+        let read_fbo = gl_result(gl, gl.create_framebuffer())?;
+        let draw_fbo = match gl_result(gl, gl.create_framebuffer()) {
+            Ok(fbo) => fbo,
+            Err(err) => {
+                gl.delete_framebuffer(read_fbo);
+                return Err(err);
+            }
+        };
+
+        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(read_fbo));
+        gl.framebuffer_texture_2d(
+            glow::READ_FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(src_handle),
+            0,
+        );
+        gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(draw_fbo));
+        gl.framebuffer_texture_2d(
+            glow::DRAW_FRAMEBUFFER,
+            glow::COLOR_ATTACHMENT0,
+            glow::TEXTURE_2D,
+            Some(dst_handle),
+            0,
+        );
+
+        let [sx, sy] = [src_abs.pos[0] as i32, src_abs.pos[1] as i32];
+        let [dx, dy] = [dst_abs.pos[0] as i32, dst_abs.pos[1] as i32];
+        let [w, h] = [src_abs.size[0] as i32, src_abs.size[1] as i32];
+        gl.blit_framebuffer(
+            sx,
+            sy,
+            sx + w,
+            sy + h,
+            dx,
+            dy,
+            dx + w,
+            dy + h,
+            glow::COLOR_BUFFER_BIT,
+            glow::NEAREST,
+        );
+        let result = gl_error_at(gl, "blit_framebuffer", ());
+
+        gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(read_binding));
+        gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(draw_binding));
+        gl.delete_framebuffer(draw_fbo);
+        gl.delete_framebuffer(read_fbo);
+
+        result
+    }
+
+    /// Validates a copy between two views, returning the source and
+    /// destination rectangles in texels of the full textures.
+    fn copy_rects(
+        src_view: Rect<u32>,
+        src_rect: Rect<u32>,
+        dst_view: Rect<u32>,
+        dst_pos: [u32; 2],
+    ) -> crate::errors::Result<(Rect<u32>, Rect<u32>)> {
+        let dst_rect = Rect {
+            pos: dst_pos,
+            size: src_rect.size,
+        };
+
+        for (view, rect) in [(src_view, src_rect), (dst_view, dst_rect)].iter() {
+            let fits = (0..2).all(|i| {
+                matches!(rect.pos[i].checked_add(rect.size[i]), Some(end) if end <= view.size[i])
+            });
+
+            if !fits {
+                return Err(errors::Error::InvalidSubTexture {
+                    source: *view,
+                    target: *rect,
+                });
+            }
+        }
+
+        let to_abs = |view: Rect<u32>, rect: Rect<u32>| Rect {
+            pos: [view.pos[0] + rect.pos[0], view.pos[1] + rect.pos[1]],
+            size: rect.size,
+        };
+
+        Ok((to_abs(src_view, src_rect), to_abs(dst_view, dst_rect)))
+    }
+
+    /// Uploads a region of a larger image to the texture's storage.
+    ///
+    /// The destination position is relative to this texture's view.
+    ///
+    /// The pixel unpack state tells OpenGL where the region's rows are
+    /// in the source image, so no intermediate copy is made. The unpack
+    /// state is reset to its defaults afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidSubTexture` if the region does not fit inside
+    /// the view at the destination position.
+    ///
+    /// Returns `InvalidPixelFormat` if the texture isn't stored as RGBA8,
+    /// the format of image views.
+    ///
+    /// Returns `UploadBudgetExceeded` without uploading when the device's
+    /// upload budget for the frame is spent.
+    pub fn update_sub_from_region(
+        &mut self,
+        device: &GraphicDevice,
+        dst_pos: [u32; 2],
+        src: &ImageView,
+    ) -> crate::errors::Result<()> {
+        let [src_x, src_y] = src.rect.pos;
+        let [width, height] = src.rect.size;
+
+        let view = Rect {
+            pos: [0, 0],
+            size: self.rect.size,
+        };
+        let target = Rect {
+            pos: dst_pos,
+            size: src.rect.size,
+        };
+        if !ImageView::contains(&view, &target) {
+            return Err(crate::errors::Error::InvalidSubTexture {
+                source: view,
+                target,
+            });
+        }
+        let [x, y] = [self.rect.pos[0] + dst_pos[0], self.rect.pos[1] + dst_pos[1]];
+
+        // Borrow mut to enforce runtime borrow rules.
+        let handle = self.handle.borrow_mut();
+        if handle.format != PixelFormat::Rgba8 {
+            return Err(crate::errors::Error::InvalidPixelFormat {
+                expected: PixelFormat::Rgba8,
+                actual: handle.format,
+            });
+        }
+        device.charge_upload(PixelFormat::Rgba8.data_len(width, height))?;
+
+        unsafe {
+            let _save = TextureSave::new(device);
+
+            device
+                .gl
+                .bind_texture(glow::TEXTURE_2D, Some(handle.handle));
+            device
+                .gl
+                .pixel_store_i32(glow::UNPACK_ROW_LENGTH, src.size[0] as i32);
+            device
+                .gl
+                .pixel_store_i32(glow::UNPACK_SKIP_PIXELS, src_x as i32);
+            device
+                .gl
+                .pixel_store_i32(glow::UNPACK_SKIP_ROWS, src_y as i32);
+            device.gl.tex_sub_image_2d(
+                glow::TEXTURE_2D,
+                0,                   // level
+                x as i32,            // x_offset
+                y as i32,            // y_offset
+                width as i32,        // width
+                height as i32,       // height
+                glow::RGBA,          // pixel format
+                glow::UNSIGNED_BYTE, // color data type
+                glow::PixelUnpackData::Slice(src.data),
+            );
+            let result = gl_error_at(&device.gl, "tex_sub_image_2d", ());
+
+            // Restore defaults so later uploads aren't affected.
+            device.gl.pixel_store_i32(glow::UNPACK_ROW_LENGTH, 0);
+            device.gl.pixel_store_i32(glow::UNPACK_SKIP_PIXELS, 0);
+            device.gl.pixel_store_i32(glow::UNPACK_SKIP_ROWS, 0);
+
+            result
+        }
+    }
+
     /// Returns the number of bytes contained in the texture's storage.
     pub fn data_len(&self) -> usize {
-        let size = self.handle.borrow().size;
-        // Each pixel is 4 bytes, RGBA
-        size[0] as usize * size[1] as usize * 4
+        let handle = self.handle.borrow();
+        handle.format.data_len(handle.size[0], handle.size[1])
     }
 }
 
@@ -243,17 +1343,125 @@ impl Drop for Texture {
 /// Wrapper for a handle to a texture in video memory.
 ///
 /// This wrapper is considered the owner of the video memory, and
-/// is responsible for triggering a deallocate on drop.
+/// is responsible for triggering a deallocate on drop, unless the
+/// texture is borrowed from elsewhere.
 struct TextureHandle {
     handle: glow::Texture,
     size: [u32; 2],
-    destroy: Sender<Destroy>,
+    format: PixelFormat,
+    params: TextureParams,
+    /// Staging buffers for asynchronous uploads, created on first use.
+    pixel_buffers: Option<PixelBuffers>,
+    /// Borrowed textures are deleted by their actual owner.
+    owned: bool,
+    /// Regions given out by `Texture::new_sub_exclusive`.
+    #[cfg(debug_assertions)]
+    exclusive_regions: Vec<Rect<u32>>,
+    destroy: DestroySender,
     _invariant: Invariant,
 }
 
 impl Drop for TextureHandle {
     fn drop(&mut self) {
-        self.destroy.send(Destroy::Texture(self.handle)).expect("TextureHandle dropped, but channel closed. OpenGL context was possibly terminated with dangling resources.");
+        if let Some(pixel_buffers) = self.pixel_buffers.take() {
+            for buffer in pixel_buffers.buffers.iter() {
+                self.destroy.send(Destroy::Buffer(*buffer));
+            }
+        }
+        if self.owned {
+            self.destroy.send(Destroy::Texture(self.handle));
+        }
+    }
+}
+
+/// Copies a rectangle out of a tightly packed RGBA8 image.
+pub(crate) fn crop_rgba8(data: &[u8], width: u32, rect: Rect<u32>) -> Vec<u8> {
+    let bpp = PixelFormat::Rgba8.bytes_per_pixel();
+    let row_len = rect.size[0] as usize * bpp;
+    let mut cropped = Vec::with_capacity(row_len * rect.size[1] as usize);
+
+    for y in rect.pos[1]..rect.pos[1] + rect.size[1] {
+        let start = (y as usize * width as usize + rect.pos[0] as usize) * bpp;
+        cropped.extend_from_slice(&data[start..start + row_len]);
+    }
+
+    cropped
+}
+
+/// Pixel format and data to upload for an image, converting
+/// to RGBA8 only when its colour type has no matching format.
+#[cfg(feature = "image")]
+fn dynamic_image_data(image: &image::DynamicImage) -> (PixelFormat, Cow<'_, [u8]>) {
+    match PixelFormat::from_color_type(image.color()) {
+        Some(format) => (format, Cow::Borrowed(image.as_bytes())),
+        None => (PixelFormat::Rgba8, Cow::Owned(image.to_rgba8().into_raw())),
+    }
+}
+
+#[cfg(feature = "image")]
+fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    color_type: image::ColorType,
+    data: Vec<u8>,
+) -> errors::Result<()> {
+    let expected_len = width as usize * height as usize * color_type.bytes_per_pixel() as usize;
+    if data.len() != expected_len {
+        return Err(errors::Error::InvalidImageData {
+            expected: expected_len,
+            actual: data.len(),
+        });
+    }
+
+    image::save_buffer_with_format(
+        path,
+        &data,
+        width,
+        height,
+        color_type,
+        image::ImageFormat::Png,
+    )
+    .map_err(|err| errors::Error::ImageSave(err.to_string()))
+}
+
+/// RGBA8 pixels of a magenta and black checkerboard, `Texture::MISSING_SIZE`
+/// texels square, with cells of 4 texels.
+pub(crate) fn missing_texture_data() -> Vec<u8> {
+    const CELL: u32 = 4;
+    const MAGENTA: [u8; 4] = [255, 0, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    let size = Texture::MISSING_SIZE;
+    let mut data = Vec::with_capacity(PixelFormat::Rgba8.data_len(size, size));
+    for y in 0..size {
+        for x in 0..size {
+            let magenta = (x / CELL + y / CELL) & 1 == 0;
+            data.extend_from_slice(if magenta { &MAGENTA } else { &BLACK });
+        }
+    }
+    data
+}
+
+/// Pair of pixel unpack buffers used in turn for streaming texture data.
+///
+/// While the device is still copying from one buffer into the texture,
+/// the next upload is written into the other.
+struct PixelBuffers {
+    buffers: [u32; 2],
+    next: usize,
+}
+
+impl PixelBuffers {
+    fn new(buffers: [u32; 2]) -> Self {
+        Self { buffers, next: 0 }
+    }
+
+    /// Returns the buffer to write the next upload into.
+    fn advance(&mut self) -> u32 {
+        let buffer = self.buffers[self.next];
+        self.next = (self.next + 1) % self.buffers.len();
+        buffer
     }
 }
 
@@ -281,6 +1489,22 @@ impl<'a> TextureSave<'a> {
     }
 }
 
+/// Length of each part when splitting the length into equal parts.
+fn split_length(length: u32, count: u32) -> errors::Result<u32> {
+    match length.checked_rem(count) {
+        Some(0) => Ok(length / count),
+        _ => Err(errors::Error::UnevenSplit { length, count }),
+    }
+}
+
+/// Limits an anisotropic filtering level to between 1 and the device's maximum.
+fn clamp_anisotropy(anisotropy: f32, max: f32) -> f32 {
+    if anisotropy.is_nan() {
+        return 1.0;
+    }
+    anisotropy.max(1.0).min(max.max(1.0))
+}
+
 impl<'a> Drop for TextureSave<'a> {
     fn drop(&mut self) {
         unsafe {
@@ -289,3 +1513,339 @@ impl<'a> Drop for TextureSave<'a> {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::device::DestroyQueue;
+
+    /// Creates a texture without allocating video memory.
+    ///
+    /// The texture's destroy request is pushed onto the returned
+    /// queue on drop, as long as the queue is alive.
+    pub(crate) fn dummy_texture(handle: u32, width: u32, height: u32) -> (DestroyQueue, Texture) {
+        let (tx, rx) = DestroySender::channel();
+        (rx, Texture::from_handle(tx, handle, width, height, true))
+    }
+
+    #[test]
+    fn test_sub_view() {
+        let (_rx, texture) = dummy_texture(1, 64, 32);
+        assert!(!texture.is_sub_view());
+        assert_eq!(texture.full_size(), [64, 32]);
+
+        let sub_texture = texture.new_sub([8, 4], [16, 16]).unwrap();
+        assert!(sub_texture.is_sub_view());
+        assert_eq!(sub_texture.rect().pos, [8, 4]);
+        assert_eq!(sub_texture.rect().size, [16, 16]);
+        assert_eq!(sub_texture.full_size(), [64, 32]);
+    }
+
+    #[test]
+    fn test_pixel_rect_to_uv() {
+        let (_rx, atlas) = dummy_texture(1, 256, 128);
+        let sub_texture = atlas.new_sub([64, 32], [64, 64]).unwrap();
+
+        let uv = sub_texture.pixel_rect_to_uv(Rect {
+            pos: [16, 32],
+            size: [32, 16],
+        });
+        assert_eq!(uv.pos, [0.3125, 0.5]);
+        assert_eq!(uv.size, [0.125, 0.125]);
+
+        let uv = atlas.pixel_rect_to_uv(atlas.rect());
+        assert_eq!(uv.pos, [0.0, 0.0]);
+        assert_eq!(uv.size, [1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_crop_rgba8() {
+        // 3x2 image, where each pixel's red channel is its index.
+        let data: Vec<u8> = (0..6).flat_map(|i| vec![i, 0, 0, 255]).collect();
+        let rect = Rect {
+            pos: [1, 0],
+            size: [2, 2],
+        };
+        let cropped = crop_rgba8(&data, 3, rect);
+        let reds: Vec<u8> = cropped.chunks(4).map(|pixel| pixel[0]).collect();
+        assert_eq!(reds, [1, 2, 4, 5]);
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_write_png() {
+        let data: Vec<u8> = (0..4 * 2 * 4).map(|i| (i * 8) as u8).collect();
+        let path = std::env::temp_dir().join("grok_glow_test_write_png.png");
+
+        write_png(&path, 4, 2, image::ColorType::Rgba8, data.clone()).unwrap();
+        let reloaded = image::open(&path).unwrap().to_rgba8();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(reloaded.dimensions(), (4, 2));
+        assert_eq!(reloaded.into_raw(), data);
+
+        assert!(matches!(
+            write_png(&path, 4, 2, image::ColorType::L8, data),
+            Err(errors::Error::InvalidImageData {
+                expected: 8,
+                actual: 32
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "image")]
+    fn test_dynamic_image_data() {
+        let luma: Vec<u8> = (0..3 * 2).map(|i| (i * 40) as u8).collect();
+        let path = std::env::temp_dir().join("grok_glow_test_dynamic_image_data.png");
+        image::GrayImage::from_raw(3, 2, luma.clone())
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let grey = image::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // Uploaded as a single channel, without expanding to RGBA.
+        let (format, data) = dynamic_image_data(&grey);
+        assert_eq!(format, PixelFormat::R8);
+        assert_eq!(data.as_ref(), luma.as_slice());
+
+        let rgb = image::DynamicImage::new_rgb8(3, 2);
+        let (format, data) = dynamic_image_data(&rgb);
+        assert_eq!(format, PixelFormat::Rgb8);
+        assert_eq!(data.len(), 3 * 2 * 3);
+
+        // Colour types without a matching format fall back to RGBA8.
+        let luma_alpha = image::DynamicImage::new_luma_a8(3, 2);
+        let (format, data) = dynamic_image_data(&luma_alpha);
+        assert_eq!(format, PixelFormat::Rgba8);
+        assert_eq!(data.len(), 3 * 2 * 4);
+    }
+
+    #[test]
+    fn test_missing_texture_data() {
+        let data = missing_texture_data();
+        let size = Texture::MISSING_SIZE as usize;
+        assert_eq!(data.len(), size * size * 4);
+
+        let pixel = |x: usize, y: usize| &data[(y * size + x) * 4..(y * size + x) * 4 + 4];
+        assert_eq!(pixel(0, 0), &[255, 0, 255, 255]);
+        assert_eq!(pixel(3, 3), &[255, 0, 255, 255]);
+        assert_eq!(pixel(4, 0), &[0, 0, 0, 255]);
+        assert_eq!(pixel(0, 4), &[0, 0, 0, 255]);
+        assert_eq!(pixel(4, 4), &[255, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_copy_rects() {
+        let src_view = Rect {
+            pos: [64, 0],
+            size: [32, 32],
+        };
+        let dst_view = Rect {
+            pos: [0, 128],
+            size: [16, 16],
+        };
+        let src_rect = Rect {
+            pos: [8, 8],
+            size: [16, 8],
+        };
+
+        let (src_abs, dst_abs) = Texture::copy_rects(src_view, src_rect, dst_view, [0, 4]).unwrap();
+        assert_eq!(src_abs.pos, [72, 8]);
+        assert_eq!(src_abs.size, [16, 8]);
+        assert_eq!(dst_abs.pos, [0, 132]);
+        assert_eq!(dst_abs.size, [16, 8]);
+
+        // Destination overflows its view horizontally.
+        assert!(Texture::copy_rects(src_view, src_rect, dst_view, [1, 0]).is_err());
+
+        // Source overflows its view.
+        let src_rect = Rect {
+            pos: [24, 0],
+            size: [16, 8],
+        };
+        assert!(Texture::copy_rects(src_view, src_rect, dst_view, [0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_drop_after_device() {
+        let (rx, texture) = dummy_texture(1, 8, 8);
+        let sub_texture = texture.new_sub([0, 0], [4, 4]).unwrap();
+
+        // Queue is owned by the device, which is gone.
+        drop(rx);
+        drop(texture);
+        drop(sub_texture);
+    }
+
+    #[test]
+    fn test_borrowed_not_destroyed() {
+        let (tx, rx) = DestroySender::channel();
+        let texture = Texture::from_handle(tx.clone(), 3, 32, 32, false);
+        let sub_texture = texture.new_sub([0, 0], [16, 16]).unwrap();
+        drop(texture);
+        drop(sub_texture);
+        assert!(rx.pop().is_none());
+
+        // Owned textures are still destroyed.
+        drop(Texture::from_handle(tx, 4, 32, 32, true));
+        assert!(matches!(rx.pop(), Some(Destroy::Texture(4))));
+    }
+
+    #[test]
+    fn test_exclusive_sub_textures() {
+        let (_rx, texture) = dummy_texture(1, 64, 64);
+        texture.new_sub_exclusive([0, 0], [32, 32]).unwrap();
+        texture.new_sub_exclusive([32, 0], [32, 32]).unwrap();
+
+        // Plain views may overlap.
+        texture.new_sub([16, 16], [32, 32]).unwrap();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overlaps")]
+    fn test_exclusive_sub_textures_overlap() {
+        let (_rx, texture) = dummy_texture(1, 64, 64);
+        texture.new_sub_exclusive([0, 0], [32, 32]).unwrap();
+        texture.new_sub_exclusive([16, 16], [32, 32]).unwrap();
+    }
+
+    #[test]
+    fn test_pixel_buffers_alternate() {
+        let mut pixel_buffers = PixelBuffers::new([7, 9]);
+        assert_eq!(pixel_buffers.advance(), 7);
+        assert_eq!(pixel_buffers.advance(), 9);
+        assert_eq!(pixel_buffers.advance(), 7);
+    }
+
+    #[test]
+    fn test_split() {
+        let (_rx, texture) = dummy_texture(1, 120, 40);
+
+        let frames = texture.split_horizontal(3).unwrap();
+        let rects: Vec<([u32; 2], [u32; 2])> = frames
+            .iter()
+            .map(|frame| (frame.rect().pos, frame.rect().size))
+            .collect();
+        assert_eq!(
+            rects,
+            [([0, 0], [40, 40]), ([40, 0], [40, 40]), ([80, 0], [40, 40])]
+        );
+
+        // Splitting a sub-view stays within it.
+        let rows = frames[1].split_vertical(2).unwrap();
+        assert_eq!(rows[1].rect().pos, [40, 20]);
+        assert_eq!(rows[1].rect().size, [40, 20]);
+
+        assert!(matches!(
+            texture.split_horizontal(7),
+            Err(errors::Error::UnevenSplit {
+                length: 120,
+                count: 7
+            })
+        ));
+        assert!(texture.split_vertical(0).is_err());
+    }
+
+    #[test]
+    fn test_clamp_anisotropy() {
+        assert_eq!(clamp_anisotropy(8.0, 16.0), 8.0);
+        assert_eq!(clamp_anisotropy(32.0, 16.0), 16.0);
+        assert_eq!(clamp_anisotropy(0.0, 16.0), 1.0);
+        assert_eq!(clamp_anisotropy(f32::NAN, 16.0), 1.0);
+    }
+
+    #[test]
+    fn test_params_gl_parameters() {
+        let params = TextureParams {
+            min_lod: 0.0,
+            max_lod: 4.0,
+            lod_bias: -0.5,
+            anisotropy: 32.0,
+        };
+        assert_eq!(
+            params.gl_parameters(false, Some(16.0)),
+            [
+                (glow::TEXTURE_MIN_LOD, 0.0),
+                (glow::TEXTURE_MAX_LOD, 4.0),
+                (glow::TEXTURE_LOD_BIAS, -0.5),
+                (glow::TEXTURE_MAX_ANISOTROPY, 16.0),
+            ]
+        );
+        // OpenGL ES has no bias, and anisotropic filtering is an extension.
+        assert_eq!(params.gl_parameters(true, None).len(), 2);
+
+        let (_rx, texture) = dummy_texture(1, 16, 16);
+        assert_eq!(texture.params(), TextureParams::default());
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let (_rx_a, texture_a) = dummy_texture(1, 64, 64);
+        let (_rx_b, texture_b) = dummy_texture(2, 64, 64);
+
+        assert_eq!(texture_a.sort_key(), texture_a.clone().sort_key());
+        assert_eq!(
+            texture_a.sort_key(),
+            texture_a.new_sub([0, 0], [8, 8]).unwrap().sort_key()
+        );
+        assert_ne!(texture_a.sort_key(), texture_b.sort_key());
+    }
+
+    #[test]
+    fn test_compressed_data_len() {
+        // A single 4x4 block.
+        assert_eq!(CompressedFormat::Dxt1.data_len(4, 4), 8);
+        assert_eq!(CompressedFormat::Dxt5.data_len(4, 4), 16);
+
+        // Partial blocks are rounded up.
+        assert_eq!(CompressedFormat::Dxt1.data_len(5, 5), 4 * 8);
+        assert_eq!(CompressedFormat::Etc2Rgba8.data_len(64, 32), 16 * 8 * 16);
+    }
+
+    #[test]
+    fn test_image_view() {
+        let data = vec![0; 8 * 8 * 4];
+        let center = Rect {
+            pos: [2, 2],
+            size: [4, 4],
+        };
+        assert!(ImageView::new(&data, 8, 8, center).is_ok());
+
+        let overflowing = Rect {
+            pos: [6, 6],
+            size: [4, 4],
+        };
+        assert!(matches!(
+            ImageView::new(&data, 8, 8, overflowing),
+            Err(errors::Error::InvalidSubTexture { .. })
+        ));
+
+        // The end of the region is past the `u32` range.
+        let wrapping = Rect {
+            pos: [u32::MAX, 0],
+            size: [2, 1],
+        };
+        assert!(matches!(
+            ImageView::new(&data, 8, 8, wrapping),
+            Err(errors::Error::InvalidSubTexture { .. })
+        ));
+
+        assert!(matches!(
+            ImageView::new(&data[1..], 8, 8, center),
+            Err(errors::Error::InvalidImageData { .. })
+        ));
+    }
+
+    #[test]
+    fn test_bytes_per_pixel() {
+        assert_eq!(PixelFormat::Rgba8.bytes_per_pixel(), 4);
+        assert_eq!(PixelFormat::Rgb8.bytes_per_pixel(), 3);
+        assert_eq!(PixelFormat::R8.bytes_per_pixel(), 1);
+
+        let (_rx, texture) = dummy_texture(1, 64, 32);
+        assert_eq!(texture.data_len(), 64 * 32 * 4);
+    }
+}
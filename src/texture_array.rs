@@ -0,0 +1,217 @@
+//! Arrays of equally sized texture layers.
+use crate::{
+    device::{Destroy, DestroySender, GraphicDevice},
+    errors::{self, gl_error_at, gl_result},
+    marker::Invariant,
+    texture::PixelFormat,
+};
+use glow::HasContext;
+
+/// Handle to a `GL_TEXTURE_2D_ARRAY` located in video memory.
+///
+/// Each layer is a separate image of the same size, selected by index
+/// when sampling. Suitable for animation frames, since sampling near the
+/// edge of a layer can't bleed into its neighbours like in an atlas.
+pub struct TextureArray {
+    handle: glow::Texture,
+    frame_size: [u32; 2],
+    layers: u32,
+    destroy: DestroySender,
+    _invariant: Invariant,
+}
+
+impl TextureArray {
+    /// Allocates an array with a layer for each frame, and uploads
+    /// the frames' RGBA8 data in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidTextureSize` if a dimension is 0, and `InvalidImageData`
+    /// if there are no frames, or a frame's length does not match the frame size.
+    ///
+    /// Returns `UploadBudgetExceeded` when the device's upload budget for
+    /// the frame is spent.
+    pub fn from_frames(
+        device: &GraphicDevice,
+        frame_width: u32,
+        frame_height: u32,
+        frames: &[&[u8]],
+    ) -> errors::Result<Self> {
+        // Upfront validations.
+        Self::validate_frames(frame_width, frame_height, frames)?;
+        device.charge_upload(frames.iter().map(|frame| frame.len()).sum())?;
+
+        let [width, height] = [frame_width as i32, frame_height as i32];
+
+        unsafe {
+            let handle = gl_result(&device.gl, device.gl.create_texture())?;
+            // Take ownership right away, so the texture is deleted on error.
+            let array = Self {
+                handle,
+                frame_size: [frame_width, frame_height],
+                layers: frames.len() as u32,
+                destroy: device.destroy_sender(),
+                _invariant: Default::default(),
+            };
+
+            let previous = device.gl.get_parameter_i32(glow::TEXTURE_BINDING_2D_ARRAY) as u32;
+            device.gl.bind_texture(glow::TEXTURE_2D_ARRAY, Some(handle));
+
+            let result = Self::upload_frames(device, width, height, frames);
+
+            device
+                .gl
+                .bind_texture(glow::TEXTURE_2D_ARRAY, Some(previous));
+
+            result.map(|_| array)
+        }
+    }
+
+    /// Allocates storage for the bound array, and uploads each frame to its layer.
+    unsafe fn upload_frames(
+        device: &GraphicDevice,
+        width: i32,
+        height: i32,
+        frames: &[&[u8]],
+    ) -> errors::Result<()> {
+        device.gl.tex_image_3d(
+            glow::TEXTURE_2D_ARRAY,
+            0,                   // Mip level
+            glow::RGBA8 as i32,  // Internal colour format
+            width,               // Width in pixels
+            height,              // Height in pixels
+            frames.len() as i32, // Number of layers
+            0,                   // Border
+            glow::RGBA,          // Format
+            glow::UNSIGNED_BYTE, // Color data type.
+            None,                // Frames are uploaded per layer.
+        );
+        gl_error_at(&device.gl, "tex_image_3d", ())?;
+
+        for (layer, frame) in frames.iter().enumerate() {
+            device.gl.tex_sub_image_3d(
+                glow::TEXTURE_2D_ARRAY,
+                0,            // level
+                0,            // x_offset
+                0,            // y_offset
+                layer as i32, // z_offset
+                width,
+                height,
+                1, // depth
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                glow::PixelUnpackData::Slice(frame),
+            );
+            gl_error_at(&device.gl, "tex_sub_image_3d", ())?;
+        }
+
+        for (parameter, value) in &[
+            (glow::TEXTURE_MIN_FILTER, glow::NEAREST),
+            (glow::TEXTURE_MAG_FILTER, glow::NEAREST),
+            (glow::TEXTURE_WRAP_S, glow::CLAMP_TO_EDGE),
+            (glow::TEXTURE_WRAP_T, glow::CLAMP_TO_EDGE),
+        ] {
+            device
+                .gl
+                .tex_parameter_i32(glow::TEXTURE_2D_ARRAY, *parameter, *value as i32);
+        }
+        gl_error_at(&device.gl, "tex_parameter_i32", ())
+    }
+
+    fn validate_frames(
+        frame_width: u32,
+        frame_height: u32,
+        frames: &[&[u8]],
+    ) -> errors::Result<()> {
+        if frame_width == 0 || frame_height == 0 {
+            return Err(errors::Error::InvalidTextureSize(frame_width, frame_height));
+        }
+
+        let expected = PixelFormat::Rgba8.data_len(frame_width, frame_height);
+        if frames.is_empty() {
+            return Err(errors::Error::InvalidImageData {
+                expected,
+                actual: 0,
+            });
+        }
+
+        for frame in frames {
+            if frame.len() != expected {
+                return Err(errors::Error::InvalidImageData {
+                    expected,
+                    actual: frame.len(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Size in texels of each layer.
+    pub fn frame_size(&self) -> [u32; 2] {
+        self.frame_size
+    }
+
+    /// Number of layers.
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    /// Key identifying a layer of the array, used to group and sort
+    /// draws. See `Texture::sort_key`.
+    pub fn sort_key(&self, layer: u32) -> u64 {
+        (self.handle as u64) << 32 | layer as u64
+    }
+
+    pub fn raw_handle(&self) -> glow::Texture {
+        self.handle
+    }
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        self.destroy.send(Destroy::Texture(self.handle));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_frames() {
+        let frames: Vec<Vec<u8>> = (0..3).map(|i| vec![i; 16 * 16 * 4]).collect();
+        let frames: Vec<&[u8]> = frames.iter().map(|frame| frame.as_slice()).collect();
+        assert!(TextureArray::validate_frames(16, 16, &frames).is_ok());
+
+        assert!(matches!(
+            TextureArray::validate_frames(16, 8, &frames),
+            Err(errors::Error::InvalidImageData {
+                expected: 512,
+                actual: 1024
+            })
+        ));
+        assert!(matches!(
+            TextureArray::validate_frames(0, 16, &frames),
+            Err(errors::Error::InvalidTextureSize(0, 16))
+        ));
+        assert!(TextureArray::validate_frames(16, 16, &[]).is_err());
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let (destroy, _rx) = DestroySender::channel();
+        let array = TextureArray {
+            handle: 7,
+            frame_size: [16, 16],
+            layers: 3,
+            destroy,
+            _invariant: Default::default(),
+        };
+
+        let keys: Vec<u64> = (0..array.layers())
+            .map(|layer| array.sort_key(layer))
+            .collect();
+        assert_eq!(keys, [7 << 32, (7 << 32) | 1, (7 << 32) | 2]);
+    }
+}
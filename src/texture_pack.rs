@@ -1,7 +1,12 @@
-use crate::{device::GraphicDevice, errors, texture::Texture};
+use crate::{
+    device::GraphicDevice,
+    errors,
+    texture::{self, PixelFormat, Texture},
+};
 use glow::HasContext;
 use std::borrow::Borrow;
 use std::cell::RefCell;
+use std::collections::{hash_map::Entry, HashMap};
 use std::convert::TryInto;
 use std::rc::Rc;
 
@@ -14,6 +19,9 @@ pub struct TexturePack {
     /// Minimum size of newly allocated textures.
     min_size: [u32; 2],
     padding: u32,
+    /// Sub-textures handed out and not yet removed, moved by `repack`.
+    entries: Vec<Texture>,
+    strategy: PackingStrategy,
 }
 
 impl TexturePack {
@@ -28,20 +36,28 @@ impl TexturePack {
         // Doesn't mean the device has enough memory to allocate
         // such a texture, though.
         let max_size = unsafe { device.gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE) };
-        println!("GL_MAX_TEXTURE_SIZE: {}", max_size);
+        debug!("GL_MAX_TEXTURE_SIZE: {}", max_size);
 
         Self::with_size(device, Self::DEFAULT_DIM, Self::DEFAULT_DIM)
     }
 
+    /// Creates a texture pack with atlases of the given size.
+    ///
+    /// When the device does not support non-power-of-two textures, the
+    /// dimensions are rounded up to the next power of two.
     pub fn with_size(device: &GraphicDevice, width: u32, height: u32) -> errors::Result<Self> {
+        let [width, height] = Self::atlas_size(width, height, Texture::is_npot_available(device));
+
         Ok(Self {
             open: vec![(
                 Texture::new(device, width, height)?,
-                Packer::new(width, width),
+                Packer::new(width, height),
             )],
             closed: vec![],
             min_size: [width, height],
             padding: 1,
+            entries: vec![],
+            strategy: PackingStrategy::default(),
         })
     }
 
@@ -57,8 +73,8 @@ impl TexturePack {
             return Err(crate::errors::Error::InvalidTextureSize(width, height));
         }
 
-        let expected_len = width as usize * height as usize * 4;
-        println!("expected {}, actual {}", expected_len, data.len());
+        let expected_len = PixelFormat::Rgba8.data_len(width, height);
+        debug!("expected {}, actual {}", expected_len, data.len());
         if expected_len != data.len() {
             return Err(crate::errors::Error::InvalidImageData {
                 expected: expected_len,
@@ -73,17 +89,23 @@ impl TexturePack {
             if let Some(slot_pos) = packer.try_insert(padded_width, padded_height) {
                 let [padded_x, padded_y] = [slot_pos[0] + self.padding, slot_pos[1] + self.padding];
                 texture.update_sub_data(device, [padded_x, padded_y], [width, height], data)?;
-                return Ok(texture.new_sub([padded_x, padded_y], [width, height])?);
+                let sub_texture =
+                    texture.new_sub_exclusive([padded_x, padded_y], [width, height])?;
+                self.entries.push(sub_texture.clone());
+                return Ok(sub_texture);
             }
         }
 
         // No available space left in open set.
         // TODO: validate device requirements that dimensions be a factor of 2
-        let new_tex_width = padded_width.min(Self::DEFAULT_DIM);
-        let new_tex_height = padded_height.min(Self::DEFAULT_DIM);
+        let [new_tex_width, new_tex_height] = Self::atlas_size(
+            padded_width.min(Self::DEFAULT_DIM),
+            padded_height.min(Self::DEFAULT_DIM),
+            Texture::is_npot_available(device),
+        );
         self.open.push((
             Texture::new(device, new_tex_width, new_tex_height)?,
-            Packer::new(new_tex_width, new_tex_height),
+            Packer::with_strategy(new_tex_width, new_tex_height, self.strategy),
         ));
         let maybe_new = self.open.last_mut().and_then(|(texture, packer)| {
             packer
@@ -99,13 +121,224 @@ impl TexturePack {
         let [padded_x, padded_y] = [slot_pos[0] + self.padding, slot_pos[1] + self.padding];
         texture.update_sub_data(device, [padded_x, padded_y], [width, height], data)?;
 
-        Ok(texture.new_sub([padded_x, padded_y], [width, height])?)
+        let sub_texture = texture.new_sub_exclusive([padded_x, padded_y], [width, height])?;
+        self.entries.push(sub_texture.clone());
+        Ok(sub_texture)
+    }
+
+    /// Stop tracking a sub-texture returned by `add_image_data`, so
+    /// `repack` leaves it behind.
+    ///
+    /// The packer can't reuse the region, so the space is only
+    /// reclaimed by the next `repack`. Returns `false` when the
+    /// texture is not part of this pack.
+    pub fn remove(&mut self, texture: &Texture) -> bool {
+        let position = self.entries.iter().position(|entry| {
+            let [a, b] = [entry.rect(), texture.rect()];
+            entry.sort_key() == texture.sort_key() && a.pos == b.pos && a.size == b.size
+        });
+
+        match position {
+            Some(index) => {
+                self.entries.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copy the sub-textures still in the pack into fresh atlases,
+    /// reclaiming the space of removed ones.
+    ///
+    /// Returns each old sub-texture paired with its replacement, so
+    /// callers can update their references.
+    ///
+    /// This is expensive. Every atlas is read back from video memory, and
+    /// all images are uploaded again. The old atlases are released by the
+    /// pack, and old sub-textures keep them alive until they are dropped,
+    /// but they are no longer updated or tracked. Use the new ones.
+    pub fn repack(&mut self, device: &GraphicDevice) -> errors::Result<Vec<(Texture, Texture)>> {
+        let sizes: Vec<[u32; 2]> = self.entries.iter().map(|entry| entry.rect().size).collect();
+        let (packers, placements) = plan_repack(&sizes, self.min_size, self.padding, self.strategy);
+
+        // Read each old atlas once.
+        let mut atlas_data = HashMap::new();
+        for entry in &self.entries {
+            if let Entry::Vacant(slot) = atlas_data.entry(entry.sort_key()) {
+                slot.insert(entry.read_storage_pixels(device)?);
+            }
+        }
+
+        let mut atlases = Vec::with_capacity(packers.len());
+        for packer in &packers {
+            atlases.push(Texture::new(device, packer.size[0], packer.size[1])?);
+        }
+
+        let mut mapping = Vec::with_capacity(self.entries.len());
+        for (entry, (atlas_index, slot)) in self.entries.drain(..).zip(placements) {
+            let rect = entry.rect();
+            let [width, height] = rect.size;
+            let data =
+                texture::crop_rgba8(&atlas_data[&entry.sort_key()], entry.full_size()[0], rect);
+
+            let atlas = &mut atlases[atlas_index];
+            let pos = [slot[0] + self.padding, slot[1] + self.padding];
+            atlas.update_sub_data(device, pos, [width, height], &data)?;
+            mapping.push((entry, atlas.new_sub_exclusive(pos, [width, height])?));
+        }
+
+        self.entries = mapping.iter().map(|(_, new)| new.clone()).collect();
+        self.closed.clear();
+        self.open = atlases.into_iter().zip(packers).collect();
+
+        Ok(mapping)
+    }
+
+    /// Choose where images are placed in atlases, from now on.
+    ///
+    /// Images already in the pack stay where they are until the next
+    /// `repack`, which places them again with the new strategy.
+    pub fn set_packing_strategy(&mut self, strategy: PackingStrategy) {
+        self.strategy = strategy;
+        for (_, packer) in &mut self.open {
+            packer.strategy = strategy;
+        }
+    }
+
+    pub fn packing_strategy(&self) -> PackingStrategy {
+        self.strategy
+    }
+
+    /// Number of sub-textures tracked by the pack.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Allocates empty atlases upfront, so loading a known set of
+    /// images does not stall to allocate textures midway.
+    ///
+    /// The atlases are added to the open set, and are the size
+    /// the pack was created with.
+    pub fn reserve_atlases(&mut self, device: &GraphicDevice, count: usize) -> errors::Result<()> {
+        self.reserve_with(count, |[width, height]| Texture::new(device, width, height))
+    }
+
+    fn reserve_with<F>(&mut self, count: usize, mut allocate: F) -> errors::Result<()>
+    where
+        F: FnMut([u32; 2]) -> errors::Result<Texture>,
+    {
+        let [width, height] = self.min_size;
+        self.open.reserve(count);
+
+        for _ in 0..count {
+            self.open.push((
+                allocate([width, height])?,
+                Packer::with_strategy(width, height, self.strategy),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Number of atlases with space available.
+    pub fn open_count(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Number of full atlases.
+    pub fn closed_count(&self) -> usize {
+        self.closed.len()
+    }
+
+    /// Size of an atlas texture that can be allocated on the device.
+    fn atlas_size(width: u32, height: u32, npot_available: bool) -> [u32; 2] {
+        if npot_available {
+            [width, height]
+        } else {
+            [width.next_power_of_two(), height.next_power_of_two()]
+        }
     }
 }
 
+/// Places images of the given sizes into as few new atlases as the
+/// packer manages, tallest first.
+///
+/// Returns the packers of the new atlases, and for each image in the
+/// given order the index of its atlas and the position of its padded slot.
+fn plan_repack(
+    sizes: &[[u32; 2]],
+    min_size: [u32; 2],
+    padding: u32,
+    strategy: PackingStrategy,
+) -> (Vec<Packer>, Vec<(usize, [u32; 2])>) {
+    // Tallest first packs the rows of the tree packer more tightly.
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse((sizes[index][1], sizes[index][0])));
+
+    let mut packers: Vec<Packer> = vec![];
+    let mut placements = vec![(0, [0, 0]); sizes.len()];
+
+    for index in order {
+        let [width, height] = sizes[index];
+        let padded = [width + padding * 2, height + padding * 2];
+
+        let existing = packers.iter_mut().enumerate().find_map(|(atlas, packer)| {
+            packer
+                .try_insert(padded[0], padded[1])
+                .map(|slot| (atlas, slot))
+        });
+
+        placements[index] = match existing {
+            Some(placement) => placement,
+            None => {
+                let mut packer = Packer::with_strategy(
+                    padded[0].max(min_size[0]),
+                    padded[1].max(min_size[1]),
+                    strategy,
+                );
+                let slot = packer
+                    .try_insert(padded[0], padded[1])
+                    .expect("Empty atlas is large enough for the image");
+                packers.push(packer);
+                (packers.len() - 1, slot)
+            }
+        };
+    }
+
+    (packers, placements)
+}
+
+/// Order in which the packer looks for free space to place an image.
+///
+/// Placement only depends on the atlas size, the strategy, and the
+/// sizes of the images inserted so far, in order. The same insertion
+/// sequence always produces the same atlas layout, which keeps golden
+/// image tests stable.
+///
+/// Each placed image splits its free rectangle in two: the space to
+/// its right, as tall as the image, and the space below it, as wide
+/// as the free rectangle was.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PackingStrategy {
+    /// Search depth first, trying the space to the right of a placed
+    /// image before the space below it. Fills rows left to right.
+    #[default]
+    RightFirst,
+    /// Search depth first, trying the space below a placed image before
+    /// the space to its right. Fills columns top to bottom.
+    BottomFirst,
+    /// Pick the free rectangle with the smallest area that fits the
+    /// image. Ties go to the top-most, then left-most rectangle.
+    BestAreaFit,
+}
+
 /// Rectangle based bin packer.
 ///
-/// # Examples
+/// See `PackingStrategy` for the order images are placed in.
 ///
 /// # Implementation
 ///
@@ -123,13 +356,20 @@ impl TexturePack {
 /// |____________________________|
 /// ```
 struct Packer {
+    /// Size of the whole packed area.
+    size: [u32; 2],
     rects: Vec<RectNode>,
     available: u32,
     padding: u32,
+    strategy: PackingStrategy,
 }
 
 impl Packer {
     fn new(width: u32, height: u32) -> Self {
+        Self::with_strategy(width, height, PackingStrategy::default())
+    }
+
+    fn with_strategy(width: u32, height: u32, strategy: PackingStrategy) -> Self {
         // Packer starts with a root node that covers the
         // entire available space.
         let root = RectNode::Leaf(Rectangle {
@@ -138,9 +378,11 @@ impl Packer {
         });
 
         Self {
+            size: [width, height],
             rects: vec![root],
             available: 1,
             padding: 0,
+            strategy,
         }
     }
 
@@ -153,79 +395,103 @@ impl Packer {
             return None;
         }
 
-        self.insert_internal([width, height], 0)
+        let target = [width, height];
+        let index = match self.strategy {
+            PackingStrategy::RightFirst => self.find_depth_first(target, 0, true),
+            PackingStrategy::BottomFirst => self.find_depth_first(target, 0, false),
+            PackingStrategy::BestAreaFit => self.find_best_area(target),
+        }?;
+
+        Some(self.split(index, target))
     }
 
-    /// Internal recursive insert.
-    fn insert_internal(&mut self, target: [u32; 2], index: usize) -> Option<[u32; 2]> {
-        // Clone needed to avoid double borrow when splitting
-        // a leaf into a branch. Not optimal, but the enum is
-        // relatively small and shouldn't incur too much of
-        // a performance penalty.
-        match self.rects[index].clone() {
+    /// Internal recursive search for a leaf that fits the target.
+    fn find_depth_first(&self, target: [u32; 2], index: usize, right_first: bool) -> Option<usize> {
+        match &self.rects[index] {
             RectNode::Vacant => unreachable!("Recursion followed leaf to non-existing node."),
             RectNode::Closed => {
                 // Node's rectangle is considered too small to contain anything.
                 None
             }
             RectNode::Leaf(rect) => {
-                if rect.can_fit(target) {
-                    // Success. Claim this node as an available slot
-                    // for the target, and split the remaining area
-                    // into a rectangle to the right, and a rectangle
-                    // to the bottom.
-                    // TODO: Padding
-                    let slot = rect.pos;
-
-                    // Claim node for the target.
-                    self.rects[index] = RectNode::Branch(Rectangle {
-                        pos: rect.pos,
-                        size: target,
-                    });
-
-                    // Split into an implicit branch.
-                    let right = index * 2 + 1;
-                    let bottom = index * 2 + 2;
-
-                    // Ensure that vector can contain the
-                    // children at the expected indices.
-                    if bottom >= self.rects.len() {
-                        self.rects.resize_with(bottom + 1, || RectNode::Vacant);
-                    }
-
-                    self.set_child_rect(
-                        right,
-                        Rectangle {
-                            pos: [slot[0] + target[1], slot[1]],
-                            size: [rect.size[0] - target[0], target[1]],
-                        },
-                    );
-                    self.set_child_rect(
-                        bottom,
-                        Rectangle {
-                            pos: [slot[0], slot[1] + target[1]],
-                            size: [rect.size[0], rect.size[1] - target[1]],
-                        },
-                    );
-
-                    self.available -= 1;
-                    Some(slot)
-                } else {
-                    // Vacant node is too small for what
-                    // we're trying to insert.
-                    None
-                }
+                // Leaf may be too small for what we're trying to insert.
+                Some(index).filter(|_| rect.can_fit(target))
             }
             RectNode::Branch(_) => {
                 // Recursive search into right and bottom branches.
-                // Right node takes precedent.
-                self.insert_internal(target, index * 2 + 1)
-                    // Try bottom node if right fails.
-                    .or_else(|| self.insert_internal(target, index * 2 + 2))
+                let (first, second) = if right_first {
+                    (index * 2 + 1, index * 2 + 2)
+                } else {
+                    (index * 2 + 2, index * 2 + 1)
+                };
+                self.find_depth_first(target, first, right_first)
+                    .or_else(|| self.find_depth_first(target, second, right_first))
             }
         }
     }
 
+    /// Leaf with the smallest area that fits the target.
+    fn find_best_area(&self, target: [u32; 2]) -> Option<usize> {
+        self.rects
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| match node {
+                RectNode::Leaf(rect) if rect.can_fit(target) => Some((index, rect)),
+                _ => None,
+            })
+            .min_by_key(|(_, rect)| {
+                let area = rect.size[0] as u64 * rect.size[1] as u64;
+                (area, rect.pos[1], rect.pos[0])
+            })
+            .map(|(index, _)| index)
+    }
+
+    /// Claim the leaf at the index as a slot for the target, and split the
+    /// remaining area into a rectangle to the right, and a rectangle to
+    /// the bottom.
+    fn split(&mut self, index: usize, target: [u32; 2]) -> [u32; 2] {
+        let rect = match &self.rects[index] {
+            RectNode::Leaf(rect) => rect.clone(),
+            _ => unreachable!("Only leaves can be split."),
+        };
+        // TODO: Padding
+        let slot = rect.pos;
+
+        // Claim node for the target.
+        self.rects[index] = RectNode::Branch(Rectangle {
+            pos: rect.pos,
+            size: target,
+        });
+
+        // Split into an implicit branch.
+        let right = index * 2 + 1;
+        let bottom = index * 2 + 2;
+
+        // Ensure that vector can contain the
+        // children at the expected indices.
+        if bottom >= self.rects.len() {
+            self.rects.resize_with(bottom + 1, || RectNode::Vacant);
+        }
+
+        self.set_child_rect(
+            right,
+            Rectangle {
+                pos: [slot[0] + target[0], slot[1]],
+                size: [rect.size[0] - target[0], target[1]],
+            },
+        );
+        self.set_child_rect(
+            bottom,
+            Rectangle {
+                pos: [slot[0], slot[1] + target[1]],
+                size: [rect.size[0], rect.size[1] - target[1]],
+            },
+        );
+
+        self.available -= 1;
+        slot
+    }
+
     fn set_child_rect(&mut self, index: usize, rect: Rectangle) {
         // TODO: Configurable minimum
         if rect.size[0] > 0 && rect.size[1] > 0 {
@@ -300,6 +566,93 @@ impl Rectangle {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_atlas_size() {
+        assert_eq!(TexturePack::atlas_size(1000, 1000, true), [1000, 1000]);
+        assert_eq!(TexturePack::atlas_size(1000, 1000, false), [1024, 1024]);
+        assert_eq!(TexturePack::atlas_size(512, 300, false), [512, 512]);
+    }
+
+    #[test]
+    fn test_reserve_atlases() {
+        let (_rx, texture) = crate::texture::test::dummy_texture(1, 256, 256);
+        let mut pack = TexturePack {
+            open: vec![(texture, Packer::new(256, 256))],
+            closed: vec![],
+            min_size: [256, 256],
+            padding: 1,
+            entries: vec![],
+            strategy: PackingStrategy::default(),
+        };
+
+        let mut receivers = vec![];
+        pack.reserve_with(3, |[width, height]| {
+            let (rx, texture) = crate::texture::test::dummy_texture(2, width, height);
+            receivers.push(rx);
+            Ok(texture)
+        })
+        .unwrap();
+
+        assert_eq!(pack.open_count(), 4);
+        assert_eq!(pack.closed_count(), 0);
+        assert!(pack
+            .open
+            .iter()
+            .all(|(texture, _)| texture.full_size() == [256, 256]));
+    }
+
+    #[test]
+    fn test_plan_repack() {
+        // Four images left of the twelve that filled three 64x64 atlases
+        // fit in a single one.
+        let live = [[30, 30]; 4];
+        let (packers, placements) = plan_repack(&live, [64, 64], 1, PackingStrategy::RightFirst);
+        assert_eq!(packers.len(), 1);
+
+        // Each slot is distinct.
+        let mut slots: Vec<[u32; 2]> = placements.iter().map(|(_, slot)| *slot).collect();
+        slots.sort();
+        slots.dedup();
+        assert_eq!(slots.len(), 4);
+
+        // Images larger than the minimum get their own atlas.
+        let (packers, placements) = plan_repack(
+            &[[100, 10], [10, 10]],
+            [64, 64],
+            1,
+            PackingStrategy::RightFirst,
+        );
+        assert_eq!(packers[0].size, [102, 64]);
+        assert_eq!(placements, vec![(0, [0, 0]), (0, [0, 12])]);
+    }
+
+    #[test]
+    fn test_packing_strategy() {
+        fn place(strategy: PackingStrategy) -> Vec<Option<[u32; 2]>> {
+            let mut packer = Packer::with_strategy(100, 100, strategy);
+            [[90, 40], [10, 10], [50, 50], [40, 10]]
+                .iter()
+                .map(|[width, height]| packer.try_insert(*width, *height))
+                .collect()
+        }
+
+        assert_eq!(PackingStrategy::default(), PackingStrategy::RightFirst);
+        assert_eq!(
+            place(PackingStrategy::RightFirst),
+            [Some([0, 0]), Some([90, 0]), Some([0, 40]), Some([50, 40])]
+        );
+        assert_eq!(
+            place(PackingStrategy::BottomFirst),
+            [Some([0, 0]), Some([0, 40]), Some([0, 50]), Some([50, 50])]
+        );
+        // The 40x10 goes into the 100x10 strip along the bottom, rather
+        // than the 50x50 square next to the previous image.
+        assert_eq!(
+            place(PackingStrategy::BestAreaFit),
+            [Some([0, 0]), Some([90, 0]), Some([0, 40]), Some([0, 90])]
+        );
+    }
+
     #[test]
     fn test_pack() {
         let mut packer = Packer::new(100, 100);
@@ -320,4 +673,15 @@ mod test {
         assert_eq!(packer.available, 0);
         assert!(!packer.has_space());
     }
+
+    #[test]
+    fn test_pack_non_square() {
+        let mut packer = Packer::new(100, 100);
+
+        // Space to the right starts after the width of the first image.
+        assert_eq!(packer.try_insert(60, 20), Some([0, 0]));
+        assert_eq!(packer.try_insert(40, 20), Some([60, 0]));
+        assert_eq!(packer.try_insert(100, 80), Some([0, 20]));
+        assert!(!packer.has_space());
+    }
 }
@@ -0,0 +1,264 @@
+//! Grids of tiles drawn from a sprite sheet.
+use crate::{
+    errors,
+    rect::Rect,
+    sprite_batch::{Sprite, SpriteBatch},
+    sprite_sheet::SpriteSheet,
+    texture::Texture,
+};
+use std::ops::Range;
+
+/// Grid of tiles, each a frame of a sprite sheet or empty.
+///
+/// Tiles are stored row by row, starting at the top-left, and refer to
+/// frames by their index in the sheet, counting left to right, then top
+/// to bottom.
+pub struct TileMap {
+    /// Sub-texture of every frame in the sheet, by index.
+    frames: Vec<Texture>,
+    tiles: Vec<Option<u16>>,
+    /// Number of columns and rows.
+    size: [u32; 2],
+}
+
+impl TileMap {
+    /// Create an empty map with the given number of columns and rows.
+    pub fn new(sheet: &SpriteSheet, columns: u32, rows: u32) -> errors::Result<Self> {
+        let tiles = vec![None; columns as usize * rows as usize];
+        Self::from_tiles(sheet, columns, rows, tiles)
+    }
+
+    /// Create a map from tile indices, stored row by row.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidTileCount` if the number of tiles is not the number
+    /// of cells, and `FrameOutOfBounds` if a tile is not a frame of the sheet.
+    pub fn from_tiles(
+        sheet: &SpriteSheet,
+        columns: u32,
+        rows: u32,
+        tiles: Vec<Option<u16>>,
+    ) -> errors::Result<Self> {
+        let expected = columns as usize * rows as usize;
+        if tiles.len() != expected {
+            return Err(errors::Error::InvalidTileCount {
+                expected,
+                actual: tiles.len(),
+            });
+        }
+
+        let frames = (0..sheet.len())
+            .map(|index| sheet.frame(index))
+            .collect::<errors::Result<Vec<_>>>()?;
+
+        let map = Self {
+            frames,
+            tiles,
+            size: [columns, rows],
+        };
+        for tile in map.tiles.iter().flatten() {
+            map.validate_tile(*tile, sheet)?;
+        }
+
+        Ok(map)
+    }
+
+    fn validate_tile(&self, tile: u16, sheet: &SpriteSheet) -> errors::Result<()> {
+        if (tile as usize) < self.frames.len() {
+            Ok(())
+        } else {
+            let columns = sheet.grid_size()[0].max(1);
+            let tile = tile as u32;
+            Err(errors::Error::FrameOutOfBounds {
+                index: [tile % columns, tile / columns],
+                grid: sheet.grid_size(),
+            })
+        }
+    }
+
+    /// Number of columns and rows.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Tile at the given column and row. `None` when the cell is empty,
+    /// or outside the map.
+    pub fn get(&self, col: u32, row: u32) -> Option<u16> {
+        self.cell_index(col, row)
+            .and_then(|index| self.tiles[index])
+    }
+
+    /// Set the tile at the given column and row, or clear it with `None`.
+    ///
+    /// Returns `false` if the cell is outside the map, or the tile is
+    /// not a frame of the sheet the map was created with.
+    pub fn set(&mut self, col: u32, row: u32, tile: Option<u16>) -> bool {
+        // Kept as `map_or`, since `Option::is_none_or` needs Rust 1.82.
+        #[allow(clippy::unnecessary_map_or)]
+        let in_sheet = tile.map_or(true, |tile| (tile as usize) < self.frames.len());
+        match self.cell_index(col, row) {
+            Some(index) if in_sheet => {
+                self.tiles[index] = tile;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn cell_index(&self, col: u32, row: u32) -> Option<usize> {
+        if col < self.size[0] && row < self.size[1] {
+            Some(row as usize * self.size[0] as usize + col as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Add a sprite for every tile to the batch, with the map's top-left
+    /// corner at the origin, and each tile drawn at the given size.
+    ///
+    /// Empty cells are skipped. When the batch has a cull rectangle, only
+    /// the cells overlapping it are visited, so large maps cost as much as
+    /// the visible part. Returns the number of sprites added.
+    pub fn draw(&self, batch: &mut SpriteBatch, origin: [i32; 2], tile_size: [u32; 2]) -> usize {
+        let [cols, rows] = visible_cells(origin, tile_size, self.size, batch.cull_rect());
+        let mut count = 0;
+
+        for row in rows {
+            for col in cols.clone() {
+                let tile = match self.tiles[row as usize * self.size[0] as usize + col as usize] {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+
+                let pos = [
+                    cell_pos(origin[0], col, tile_size[0]),
+                    cell_pos(origin[1], row, tile_size[1]),
+                ];
+                let mut sprite = Sprite::with(pos, tile_size);
+                sprite.set_texture(self.frames[tile as usize].clone());
+                batch.add(&sprite);
+                count += 1;
+            }
+        }
+
+        count
+    }
+}
+
+/// Position along an axis of the cell with the given index, computed
+/// without overflow, and saturated to the range of `i32`.
+fn cell_pos(origin: i32, cell: u32, tile_size: u32) -> i32 {
+    let pos = origin as i64 + cell as i64 * tile_size as i64;
+    pos.max(i32::MIN as i64).min(i32::MAX as i64) as i32
+}
+
+/// Range of columns and rows of a map's cells that overlap the cull
+/// rectangle. Every cell when there is none.
+fn visible_cells(
+    origin: [i32; 2],
+    tile_size: [u32; 2],
+    map_size: [u32; 2],
+    cull_rect: Option<Rect<f32>>,
+) -> [Range<u32>; 2] {
+    let cull_rect = match cull_rect {
+        Some(cull_rect) => cull_rect,
+        None => return [0..map_size[0], 0..map_size[1]],
+    };
+
+    let axis = |axis: usize| {
+        if tile_size[axis] == 0 {
+            return 0..0;
+        }
+        let tile = tile_size[axis] as f32;
+        let start = cull_rect.pos[axis] - origin[axis] as f32;
+        let end = start + cull_rect.size[axis];
+
+        // Cells only touching the edge of the rectangle are not visible.
+        let first = (start / tile).floor().max(0.0) as u32;
+        let last = ((end / tile).ceil().max(0.0) as u32).min(map_size[axis]);
+        first.min(last)..last
+    };
+
+    [axis(0), axis(1)]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::texture::test::dummy_texture;
+
+    #[test]
+    fn test_visible_cells() {
+        assert_eq!(
+            visible_cells([0, 0], [16, 16], [10, 8], None),
+            [0..10, 0..8]
+        );
+
+        let screen = Rect {
+            pos: [0.0, 0.0],
+            size: [64.0, 48.0],
+        };
+        assert_eq!(
+            visible_cells([-20, 0], [16, 16], [10, 8], Some(screen)),
+            [1..6, 0..3]
+        );
+
+        // Map entirely off-screen.
+        let [cols, _] = visible_cells([100, 0], [16, 16], [10, 8], Some(screen));
+        assert!(cols.is_empty());
+    }
+
+    #[test]
+    fn test_cell_pos() {
+        assert_eq!(cell_pos(-20, 3, 16), 28);
+        // Past the range of `u32` and `i32`.
+        assert_eq!(cell_pos(0, 70_000, 70_000), i32::MAX);
+        assert_eq!(cell_pos(i32::MIN, 1, 16), i32::MIN + 16);
+    }
+
+    #[test]
+    fn test_draw_tiles() {
+        let (_rx, texture) = dummy_texture(1, 64, 64);
+        let sheet = SpriteSheet::from_grid(texture, 16, 16, 0, 0).unwrap();
+
+        // 4x3 map, with 2 empty cells in the first two columns.
+        #[rustfmt::skip]
+        let tiles = vec![
+            Some(0), None,    Some(2), Some(3),
+            Some(4), Some(5), Some(6), Some(7),
+            None,    Some(9), Some(10), Some(11),
+        ];
+        let map = TileMap::from_tiles(&sheet, 4, 3, tiles).unwrap();
+        assert_eq!(map.get(1, 0), None);
+        assert_eq!(map.get(1, 2), Some(9));
+
+        let (_vrx, vertex_buffer) = crate::vertex::test::dummy_vertex_buffer();
+        let mut batch: SpriteBatch = SpriteBatch::with_vertex_buffer(vertex_buffer);
+
+        // Everything is drawn without culling.
+        assert_eq!(map.draw(&mut batch, [0, 0], [16, 16]), 10);
+        assert_eq!(batch.len(), 10);
+
+        // Only the first two columns are on-screen.
+        batch.clear();
+        batch.set_cull_rect(Some(Rect {
+            pos: [0.0, 0.0],
+            size: [32.0, 100.0],
+        }));
+        assert_eq!(map.draw(&mut batch, [0, 0], [16, 16]), 4);
+        assert_eq!(batch.len(), 4);
+
+        assert!(matches!(
+            TileMap::from_tiles(&sheet, 1, 1, vec![Some(16)]),
+            Err(errors::Error::FrameOutOfBounds { .. })
+        ));
+        assert!(matches!(
+            TileMap::from_tiles(&sheet, 2, 2, vec![None]),
+            Err(errors::Error::InvalidTileCount {
+                expected: 4,
+                actual: 1
+            })
+        ));
+    }
+}
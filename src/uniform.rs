@@ -0,0 +1,78 @@
+//! Uniform buffer objects, for sharing uniform data between shaders.
+use crate::{
+    device::{Destroy, DestroySender, GraphicDevice},
+    errors::{self, gl_error, gl_result},
+};
+use bytemuck::Pod;
+use glow::HasContext;
+use std::marker::PhantomData;
+
+/// Handle to a uniform buffer object located in video memory.
+///
+/// The buffer holds a single value of `T`, and is bound to a binding
+/// point. Shaders read it by binding their uniform block to the same
+/// point with `Shader::bind_uniform_block`.
+///
+/// `T` is uploaded as raw bytes, so it must be plain old data, and its
+/// fields laid out to match the block's `std140` layout in the shader.
+pub struct UniformBuffer<T: Pod> {
+    buffer: glow::Buffer,
+    binding: u32,
+    destroy: DestroySender,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> UniformBuffer<T> {
+    pub fn new(device: &GraphicDevice, binding: u32, value: &T) -> errors::Result<Self> {
+        unsafe {
+            let buffer = gl_result(&device.gl, device.gl.create_buffer())?;
+            device.gl.bind_buffer(glow::UNIFORM_BUFFER, Some(buffer));
+            device.gl.buffer_data_u8_slice(
+                glow::UNIFORM_BUFFER,
+                bytemuck::bytes_of(value),
+                glow::DYNAMIC_DRAW,
+            );
+            device
+                .gl
+                .bind_buffer_base(glow::UNIFORM_BUFFER, binding, Some(buffer));
+            device.gl.bind_buffer(glow::UNIFORM_BUFFER, None);
+
+            if let Err(err) = gl_error(&device.gl, ()) {
+                device.gl.delete_buffer(buffer);
+                return Err(err);
+            }
+
+            Ok(Self {
+                buffer,
+                binding,
+                destroy: device.destroy_sender(),
+                _marker: PhantomData,
+            })
+        }
+    }
+
+    /// Binding point the buffer is attached to.
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    /// Uploads a new value to the buffer.
+    pub fn update(&self, device: &GraphicDevice, value: &T) -> errors::Result<()> {
+        unsafe {
+            device
+                .gl
+                .bind_buffer(glow::UNIFORM_BUFFER, Some(self.buffer));
+            device
+                .gl
+                .buffer_sub_data_u8_slice(glow::UNIFORM_BUFFER, 0, bytemuck::bytes_of(value));
+            device.gl.bind_buffer(glow::UNIFORM_BUFFER, None);
+            gl_error(&device.gl, ())
+        }
+    }
+}
+
+impl<T: Pod> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        self.destroy.send(Destroy::Buffer(self.buffer));
+    }
+}
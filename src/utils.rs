@@ -1,5 +1,5 @@
 //! Miscellaneous utilities.
-use std::{mem, slice, time};
+use std::{mem, slice, thread, time};
 
 /// Cast a slice to a slice of bytes.
 ///
@@ -20,33 +20,64 @@ pub(crate) unsafe fn as_u8<T>(buf: &[T]) -> &[u8] {
     slice::from_raw_parts(ptr, size)
 }
 
+/// Reverses the order of the rows in an image buffer, in place.
+///
+/// OpenGL stores images with the first row at the bottom, while
+/// most image formats expect the first row at the top.
+pub(crate) fn flip_rows(data: &mut [u8], row_len: usize) {
+    if row_len == 0 {
+        return;
+    }
+
+    let rows = data.len() / row_len;
+    for y in 0..rows / 2 {
+        let (top, bottom) = data.split_at_mut((rows - y - 1) * row_len);
+        top[y * row_len..(y + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+    }
+}
+
 /// Utility for measuring frame rate per second.
 ///
 /// It takes periodic snapshots of the measured
 /// fps to slow down the value being printed.
 /// This makes it easier to read when presented to
 /// the user.
+///
+/// A smoothed value that follows changes more
+/// closely is available via `instantaneous_fps`.
 pub struct FpsCounter {
     dt: [f32; 60 * 1],
     snapshot: f32,
     cursor: usize,
+    /// Exponential moving average of the frame time.
+    smoothed_dt: Option<f32>,
 }
 
 impl FpsCounter {
+    /// Weight of the newest frame time in the moving average.
+    const SMOOTHING: f32 = 0.1;
+
     pub fn new() -> Self {
         Self {
             dt: [0.0; 60 * 1],
             snapshot: 0.0,
             cursor: 0,
+            smoothed_dt: None,
         }
     }
 
     pub fn add(&mut self, delta_time: time::Duration) {
-        self.dt[self.cursor] = delta_time.as_secs_f32();
+        let dt = delta_time.as_secs_f32();
+        self.dt[self.cursor] = dt;
         if self.cursor == 0 {
             self.take_snapshot();
         }
         self.cursor = (self.cursor + 1) % self.dt.len();
+
+        self.smoothed_dt = Some(match self.smoothed_dt {
+            Some(smoothed) => smoothed + (dt - smoothed) * Self::SMOOTHING,
+            None => dt,
+        });
     }
 
     fn take_snapshot(&mut self) {
@@ -58,9 +89,88 @@ impl FpsCounter {
         }
     }
 
+    /// Average fps over the last full cycle of frames.
+    ///
+    /// Updated once per cycle, so the value is stable
+    /// but lags behind changes.
     pub fn fps(&self) -> f32 {
         self.snapshot
     }
+
+    /// Fps from a moving average of frame times, updated every frame.
+    ///
+    /// Responds to changes within a few frames, while still
+    /// smoothing out jitter.
+    pub fn instantaneous_fps(&self) -> f32 {
+        match self.smoothed_dt {
+            Some(dt) if dt.abs() > f32::EPSILON => 1.0 / dt,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Caps the frame rate by sleeping out the rest of each frame.
+///
+/// Useful when vsync is off, where an event loop in `ControlFlow::Poll`
+/// would otherwise redraw as fast as possible and keep a core busy.
+///
+/// Call `wait` once per frame, after the buffers are swapped.
+pub struct FrameLimiter {
+    frame_time: time::Duration,
+    frame_start: time::Instant,
+}
+
+impl FrameLimiter {
+    /// Remaining time below which `wait` spins instead of sleeping.
+    ///
+    /// The operating system may oversleep by a millisecond or more, so
+    /// sleeping the whole remainder tends to miss the target.
+    const SPIN_THRESHOLD: time::Duration = time::Duration::from_millis(2);
+
+    pub fn new(target_fps: f32) -> Self {
+        Self {
+            frame_time: Self::frame_time(target_fps),
+            frame_start: time::Instant::now(),
+        }
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: f32) {
+        self.frame_time = Self::frame_time(target_fps);
+    }
+
+    fn frame_time(target_fps: f32) -> time::Duration {
+        assert!(target_fps > 0.0, "Target fps must be positive");
+        time::Duration::from_secs_f32(1.0 / target_fps)
+    }
+
+    /// Time left to wait after a frame that took `elapsed`.
+    ///
+    /// Zero when the frame already took longer than the target.
+    pub fn remaining(&self, elapsed: time::Duration) -> time::Duration {
+        self.frame_time.checked_sub(elapsed).unwrap_or_default()
+    }
+
+    /// Block until the target frame time has passed since the
+    /// previous call, and start timing the next frame.
+    ///
+    /// Returns the duration of the whole frame, including the wait,
+    /// suitable for `FpsCounter::add`.
+    pub fn wait(&mut self) -> time::Duration {
+        let deadline = self.frame_start + self.frame_time;
+        let remaining = self.remaining(self.frame_start.elapsed());
+
+        if remaining > Self::SPIN_THRESHOLD {
+            thread::sleep(remaining - Self::SPIN_THRESHOLD);
+        }
+        while time::Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+
+        let now = time::Instant::now();
+        let frame_time = now - self.frame_start;
+        self.frame_start = now;
+        frame_time
+    }
 }
 
 #[cfg(test)]
@@ -70,4 +180,58 @@ mod test {
     fn test_as_u8() {
         todo!()
     }
+
+    #[test]
+    fn test_frame_limiter_remaining() {
+        let limiter = FrameLimiter::new(100.0);
+
+        // Fast frame sleeps out the rest of the 10ms.
+        let remaining = limiter.remaining(time::Duration::from_millis(3));
+        assert!((remaining.as_secs_f32() - 0.007).abs() < 1e-6);
+
+        // Slow frame doesn't wait at all.
+        assert_eq!(
+            limiter.remaining(time::Duration::from_millis(12)),
+            time::Duration::from_secs(0)
+        );
+    }
+
+    #[test]
+    fn test_frame_limiter_wait() {
+        let mut limiter = FrameLimiter::new(200.0);
+        let frame_time = limiter.wait();
+        assert!(frame_time >= time::Duration::from_secs_f32(1.0 / 200.0));
+    }
+
+    #[test]
+    fn test_instantaneous_fps() {
+        let mut fps = FpsCounter::new();
+        assert_eq!(fps.instantaneous_fps(), 0.0);
+
+        for _ in 0..10 {
+            fps.add(time::Duration::from_secs_f32(1.0 / 60.0));
+        }
+        assert!((fps.instantaneous_fps() - 60.0).abs() < 0.01);
+
+        // Frame time doubles. The average moves towards 30 fps every frame.
+        let mut previous = fps.instantaneous_fps();
+        for _ in 0..60 {
+            fps.add(time::Duration::from_secs_f32(1.0 / 30.0));
+            let current = fps.instantaneous_fps();
+            assert!(current < previous);
+            previous = current;
+        }
+        assert!((fps.instantaneous_fps() - 30.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_flip_rows() {
+        let mut data = [1, 1, 2, 2, 3, 3];
+        flip_rows(&mut data, 2);
+        assert_eq!(data, [3, 3, 2, 2, 1, 1]);
+
+        let mut data = [1, 1, 2, 2, 3, 3, 4, 4];
+        flip_rows(&mut data, 2);
+        assert_eq!(data, [4, 4, 3, 3, 2, 2, 1, 1]);
+    }
 }
@@ -1,24 +1,186 @@
 use crate::{
-    device::{Destroy, GraphicDevice},
-    errors::assert_gl,
+    color::{self, Color},
+    device::{Destroy, DestroySender, GraphicDevice},
+    errors::{self, debug_assert_gl_at, gl_error, gl_result},
+    primitives::{Primitive, Winding},
+    shader::{AttributeInfo, Shader},
     utils,
 };
 use glow::HasContext;
-use std::{mem, sync::mpsc::Sender};
+use std::mem;
 
 #[derive(Debug, Clone)]
+#[repr(C)]
 pub struct Vertex {
     pub position: [f32; 2],
     pub uv: [f32; 2],
     pub color: [f32; 4],
 }
 
+/// Compact vertex with the colour packed into 8-bit channels.
+///
+/// Takes 20 bytes, instead of the 32 bytes of `Vertex`. The colour
+/// is normalised back to the 0.0 to 1.0 range when read by the shader.
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct PackedVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    /// RGBA8 colour, with red in the first byte in memory.
+    pub color: u32,
+}
+
+impl PackedVertex {
+    pub fn new(position: [f32; 2], uv: [f32; 2], color: Color) -> Self {
+        Self {
+            position,
+            uv,
+            color: color::pack_rgba8(color),
+        }
+    }
+}
+
+/// Vertex type a `SpriteBatch` builds for each sprite corner.
+///
+/// Implemented by `Vertex` and `PackedVertex`. Custom vertex types
+/// carry extra attributes for effects, like the phase of a waving
+/// flag, and are filled in per corner by the batch's corner callback.
+///
+/// # Safety
+///
+/// Vertices are uploaded to video memory as raw bytes. Implementors must
+/// be `#[repr(C)]`, without padding bytes, and made only of plain numbers
+/// like `f32` and `u32`, with no pointers or references. The offsets
+/// given by `format` and `extra_attributes` must lie within the vertex.
+pub unsafe trait BatchVertex: Clone {
+    /// Layout of the position, UV and colour attributes.
+    fn format() -> VertexFormat;
+
+    /// Attributes following position, UV and colour.
+    fn extra_attributes() -> Vec<VertexAttribute> {
+        vec![]
+    }
+
+    /// Converts the vertex built from a sprite corner. Extra
+    /// attributes are left to the corner callback.
+    fn from_vertex(vertex: Vertex) -> Self;
+}
+
+unsafe impl BatchVertex for Vertex {
+    fn format() -> VertexFormat {
+        VertexFormat::Float
+    }
+
+    fn from_vertex(vertex: Vertex) -> Self {
+        vertex
+    }
+}
+
+unsafe impl BatchVertex for PackedVertex {
+    fn format() -> VertexFormat {
+        VertexFormat::Packed
+    }
+
+    fn from_vertex(vertex: Vertex) -> Self {
+        PackedVertex::new(vertex.position, vertex.uv, vertex.color)
+    }
+}
+
+/// Extra floating point attribute of a custom vertex type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    /// Attribute location in the shader program. Locations 0 to 2
+    /// are taken by position, UV and colour.
+    pub location: u32,
+    /// Number of `f32` components, from 1 to 4.
+    pub components: i32,
+    /// Bytes from the start of the vertex.
+    pub offset: usize,
+}
+
+/// Type of the indices stored in a `VertexBuffer`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum IndexType {
+    /// 16-bit indices, enough for 65536 vertices.
+    #[default]
+    U16,
+    /// 32-bit indices, for larger meshes.
+    U32,
+}
+
+impl IndexType {
+    pub(crate) fn to_gl(self) -> u32 {
+        match self {
+            IndexType::U16 => glow::UNSIGNED_SHORT,
+            IndexType::U32 => glow::UNSIGNED_INT,
+        }
+    }
+
+    /// Size of an index in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            IndexType::U16 => mem::size_of::<u16>(),
+            IndexType::U32 => mem::size_of::<u32>(),
+        }
+    }
+}
+
+/// Layout of the vertices stored in a `VertexBuffer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VertexFormat {
+    /// `Vertex`, with a floating point colour.
+    Float,
+    /// `PackedVertex`, with the colour packed into an integer.
+    Packed,
+    /// Custom vertex type with a floating point colour, like `Vertex`,
+    /// at the given byte offsets. The type should be `#[repr(C)]`.
+    Custom {
+        stride: usize,
+        /// Byte offsets of the position, UV and colour attributes.
+        offsets: [usize; 3],
+    },
+}
+
+impl VertexFormat {
+    /// Size in bytes of one vertex.
+    pub fn stride(self) -> usize {
+        match self {
+            VertexFormat::Float => mem::size_of::<Vertex>(),
+            VertexFormat::Packed => mem::size_of::<PackedVertex>(),
+            VertexFormat::Custom { stride, .. } => stride,
+        }
+    }
+
+    /// Byte offsets of the position, UV and colour attributes.
+    fn offsets(self) -> [usize; 3] {
+        match self {
+            VertexFormat::Float => [
+                memoffset::offset_of!(Vertex, position),
+                memoffset::offset_of!(Vertex, uv),
+                memoffset::offset_of!(Vertex, color),
+            ],
+            VertexFormat::Packed => [
+                memoffset::offset_of!(PackedVertex, position),
+                memoffset::offset_of!(PackedVertex, uv),
+                memoffset::offset_of!(PackedVertex, color),
+            ],
+            VertexFormat::Custom { offsets, .. } => offsets,
+        }
+    }
+}
+
 /// Handle to a vertex buffer object located in video memory.
 pub struct VertexBuffer {
     pub(crate) vbo: u32,
     pub(crate) vertex_buffer: u32,
     pub(crate) index_buffer: u32,
-    destroy: Sender<Destroy>,
+    /// Winding of the triangles described by the index buffer.
+    winding: Winding,
+    index_type: IndexType,
+    format: VertexFormat,
+    /// Attributes of custom vertex types following position, UV and colour.
+    extra_attributes: Vec<VertexAttribute>,
+    destroy: DestroySender,
 }
 
 impl VertexBuffer {
@@ -27,93 +189,532 @@ impl VertexBuffer {
     const UV_LOC: u32 = 1;
     const COLOR_LOC: u32 = 2;
 
-    pub fn new_static(device: &GraphicDevice, vertices: &[Vertex], indices: &[u16]) -> Self {
-        unsafe {
-            // Vertex Buffer Object
-            let vertex_array = device.gl.create_vertex_array().unwrap();
-            device.gl.bind_vertex_array(Some(vertex_array));
+    /// Creates a vertex buffer with counter-clockwise winding.
+    pub fn new_static(
+        device: &GraphicDevice,
+        vertices: &[Vertex],
+        indices: &[u16],
+    ) -> errors::Result<Self> {
+        Self::new_static_with_winding(device, vertices, indices, Winding::Ccw)
+    }
 
-            // Attached buffer space
-            let vertex_buffer = device.gl.create_buffer().unwrap();
-            device
-                .gl
-                .bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
-            device.gl.buffer_data_u8_slice(
-                glow::ARRAY_BUFFER,
-                utils::as_u8(vertices),
-                glow::DYNAMIC_DRAW,
-            );
-            assert_gl(&device.gl);
+    /// Creates a vertex buffer with the given index order.
+    ///
+    /// The winding determines which side of the triangles is
+    /// considered the front face when drawing.
+    pub fn new_static_with_winding(
+        device: &GraphicDevice,
+        vertices: &[Vertex],
+        indices: &[u16],
+        winding: Winding,
+    ) -> errors::Result<Self> {
+        let (vertex_data, index_data) = unsafe { (utils::as_u8(vertices), utils::as_u8(indices)) };
+        Self::new_static_internal(
+            device,
+            vertex_data,
+            VertexFormat::Float,
+            &[],
+            index_data,
+            IndexType::U16,
+            winding,
+        )
+    }
 
-            // Vertex data is interleaved.
-            // Attribute layout positions are determined by shader.
-            // Positions
-            device.gl.enable_vertex_attrib_array(Self::POSITION_LOC);
-            device.gl.vertex_attrib_pointer_f32(
-                Self::POSITION_LOC,              // Attribute location in shader program.
-                2,                               // Size. Components per iteration.
-                glow::FLOAT,                     // Type to get from buffer.
-                false,                           // Normalize.
-                mem::size_of::<Vertex>() as i32, // Stride. Bytes to advance each iteration.
-                memoffset::offset_of!(Vertex, position) as i32, // Offset. Bytes from start of buffer.
-            );
-            assert_gl(&device.gl);
+    /// Creates a vertex buffer with 32-bit indices, and counter-clockwise
+    /// winding, for meshes with more vertices than `u16` can address.
+    pub fn new_static_u32(
+        device: &GraphicDevice,
+        vertices: &[Vertex],
+        indices: &[u32],
+    ) -> errors::Result<Self> {
+        let (vertex_data, index_data) = unsafe { (utils::as_u8(vertices), utils::as_u8(indices)) };
+        Self::new_static_internal(
+            device,
+            vertex_data,
+            VertexFormat::Float,
+            &[],
+            index_data,
+            IndexType::U32,
+            Winding::Ccw,
+        )
+    }
 
-            // UVs
-            device.gl.enable_vertex_attrib_array(Self::UV_LOC);
-            device.gl.vertex_attrib_pointer_f32(
-                Self::UV_LOC,                             // Attribute location in shader program.
-                2,                                        // Size. Components per iteration.
-                glow::FLOAT,                              // Type to get from buffer.
-                false,                                    // Normalize.
-                mem::size_of::<Vertex>() as i32, // Stride. Bytes to advance each iteration.
-                memoffset::offset_of!(Vertex, uv) as i32, // Offset. Bytes from start of buffer.
-            );
-            assert_gl(&device.gl);
+    /// Creates a vertex buffer of compact vertices, with counter-clockwise winding.
+    pub fn new_static_packed(
+        device: &GraphicDevice,
+        vertices: &[PackedVertex],
+        indices: &[u16],
+    ) -> errors::Result<Self> {
+        let (vertex_data, index_data) = unsafe { (utils::as_u8(vertices), utils::as_u8(indices)) };
+        Self::new_static_internal(
+            device,
+            vertex_data,
+            VertexFormat::Packed,
+            &[],
+            index_data,
+            IndexType::U16,
+            Winding::Ccw,
+        )
+    }
 
-            // Colors
-            device.gl.enable_vertex_attrib_array(Self::COLOR_LOC);
-            device.gl.vertex_attrib_pointer_f32(
-                Self::COLOR_LOC,                             // Attribute location in shader program.
-                4,                                           // Size. Components per iteration.
-                glow::FLOAT,                                 // Type to get from buffer.
-                false,                                       // Normalize.
-                mem::size_of::<Vertex>() as i32, // Stride. Bytes to advance each iteration.
-                memoffset::offset_of!(Vertex, color) as i32, // Offset. Bytes from start of buffer.
-            );
-            assert_gl(&device.gl);
+    /// Creates a vertex buffer of any batch vertex type, including its
+    /// extra attributes, with counter-clockwise winding.
+    pub fn new_static_custom<V: BatchVertex>(
+        device: &GraphicDevice,
+        vertices: &[V],
+        indices: &[u16],
+    ) -> errors::Result<Self> {
+        let (vertex_data, index_data) = unsafe { (utils::as_u8(vertices), utils::as_u8(indices)) };
+        Self::new_static_internal(
+            device,
+            vertex_data,
+            V::format(),
+            &V::extra_attributes(),
+            index_data,
+            IndexType::U16,
+            Winding::Ccw,
+        )
+    }
+
+    fn new_static_internal(
+        device: &GraphicDevice,
+        vertex_data: &[u8],
+        format: VertexFormat,
+        extra_attributes: &[VertexAttribute],
+        index_data: &[u8],
+        index_type: IndexType,
+        winding: Winding,
+    ) -> errors::Result<Self> {
+        unsafe {
+            // Vertex Buffer Object
+            let vertex_array = gl_result(&device.gl, device.gl.create_vertex_array())?;
+
+            // Attached buffer space
+            let vertex_buffer = match gl_result(&device.gl, device.gl.create_buffer()) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    device.gl.delete_vertex_array(vertex_array);
+                    return Err(err);
+                }
+            };
 
             // Indices
-            let index_buffer = device.gl.create_buffer().unwrap();
-            device
-                .gl
-                .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(index_buffer));
-            device.gl.buffer_data_u8_slice(
-                glow::ELEMENT_ARRAY_BUFFER,
-                utils::as_u8(indices),
-                glow::DYNAMIC_DRAW,
-            );
+            let index_buffer = match gl_result(&device.gl, device.gl.create_buffer()) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    device.gl.delete_buffer(vertex_buffer);
+                    device.gl.delete_vertex_array(vertex_array);
+                    return Err(err);
+                }
+            };
+
+            let result = Self::upload_static(
+                device,
+                vertex_array,
+                vertex_buffer,
+                index_buffer,
+                vertex_data,
+                format,
+                index_data,
+            )
+            .and_then(|_| {
+                // The vertex array and buffer are still bound.
+                Self::describe_extra_attributes(device, format.stride(), extra_attributes)
+            });
 
             device.gl.bind_buffer(glow::ARRAY_BUFFER, None);
             device.gl.bind_vertex_array(None);
 
-            Self {
+            // Don't leak the objects when they could not be initialised.
+            if let Err(err) = result {
+                device.gl.delete_buffer(index_buffer);
+                device.gl.delete_buffer(vertex_buffer);
+                device.gl.delete_vertex_array(vertex_array);
+                return Err(err);
+            }
+
+            Ok(Self {
                 vbo: vertex_array,
                 vertex_buffer,
                 index_buffer,
+                winding,
+                index_type,
+                format,
+                extra_attributes: extra_attributes.to_vec(),
                 destroy: device.destroy_sender(),
-            }
+            })
+        }
+    }
+
+    /// Uploads the initial data, and describes the vertex layout to the vertex array.
+    unsafe fn upload_static(
+        device: &GraphicDevice,
+        vertex_array: u32,
+        vertex_buffer: u32,
+        index_buffer: u32,
+        vertex_data: &[u8],
+        format: VertexFormat,
+        index_data: &[u8],
+    ) -> errors::Result<()> {
+        device.gl.bind_vertex_array(Some(vertex_array));
+
+        device
+            .gl
+            .bind_buffer(glow::ARRAY_BUFFER, Some(vertex_buffer));
+        device
+            .gl
+            .buffer_data_u8_slice(glow::ARRAY_BUFFER, vertex_data, glow::DYNAMIC_DRAW);
+        gl_error(&device.gl, ())?;
+
+        let stride = format.stride() as i32;
+        let [position_offset, uv_offset, color_offset] = format.offsets();
+
+        // Vertex data is interleaved.
+        // Attribute layout positions are determined by shader.
+        // Positions
+        device.gl.enable_vertex_attrib_array(Self::POSITION_LOC);
+        device.gl.vertex_attrib_pointer_f32(
+            Self::POSITION_LOC,     // Attribute location in shader program.
+            2,                      // Size. Components per iteration.
+            glow::FLOAT,            // Type to get from buffer.
+            false,                  // Normalize.
+            stride,                 // Stride. Bytes to advance each iteration.
+            position_offset as i32, // Offset. Bytes from start of buffer.
+        );
+        gl_error(&device.gl, ())?;
+
+        // UVs
+        device.gl.enable_vertex_attrib_array(Self::UV_LOC);
+        device.gl.vertex_attrib_pointer_f32(
+            Self::UV_LOC,     // Attribute location in shader program.
+            2,                // Size. Components per iteration.
+            glow::FLOAT,      // Type to get from buffer.
+            false,            // Normalize.
+            stride,           // Stride. Bytes to advance each iteration.
+            uv_offset as i32, // Offset. Bytes from start of buffer.
+        );
+        gl_error(&device.gl, ())?;
+
+        // Colors
+        // Packed colours are normalised from bytes to the 0.0 to 1.0 range.
+        let (color_type, color_normalized) = match format {
+            VertexFormat::Float | VertexFormat::Custom { .. } => (glow::FLOAT, false),
+            VertexFormat::Packed => (glow::UNSIGNED_BYTE, true),
+        };
+        device.gl.enable_vertex_attrib_array(Self::COLOR_LOC);
+        device.gl.vertex_attrib_pointer_f32(
+            Self::COLOR_LOC,     // Attribute location in shader program.
+            4,                   // Size. Components per iteration.
+            color_type,          // Type to get from buffer.
+            color_normalized,    // Normalize.
+            stride,              // Stride. Bytes to advance each iteration.
+            color_offset as i32, // Offset. Bytes from start of buffer.
+        );
+        gl_error(&device.gl, ())?;
+
+        // Indices
+        device
+            .gl
+            .bind_buffer(glow::ELEMENT_ARRAY_BUFFER, Some(index_buffer));
+        device
+            .gl
+            .buffer_data_u8_slice(glow::ELEMENT_ARRAY_BUFFER, index_data, glow::DYNAMIC_DRAW);
+        gl_error(&device.gl, ())
+    }
+
+    /// Describes the attributes of a custom vertex type following position,
+    /// UV and colour to the bound vertex array.
+    unsafe fn describe_extra_attributes(
+        device: &GraphicDevice,
+        stride: usize,
+        extra_attributes: &[VertexAttribute],
+    ) -> errors::Result<()> {
+        for attribute in extra_attributes {
+            device.gl.enable_vertex_attrib_array(attribute.location);
+            device.gl.vertex_attrib_pointer_f32(
+                attribute.location,
+                attribute.components,
+                glow::FLOAT,
+                false,
+                stride as i32,
+                attribute.offset as i32,
+            );
+            gl_error(&device.gl, ())?;
         }
+        Ok(())
+    }
+
+    pub fn winding(&self) -> Winding {
+        self.winding
+    }
+
+    pub fn format(&self) -> VertexFormat {
+        self.format
+    }
+
+    /// Type of the indices in the index buffer.
+    pub fn index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    /// Every attribute of the buffer's layout, starting with
+    /// position, UV and colour.
+    pub fn attributes(&self) -> Vec<VertexAttribute> {
+        let [position_offset, uv_offset, color_offset] = self.format.offsets();
+        let mut attributes = vec![
+            VertexAttribute {
+                location: Self::POSITION_LOC,
+                components: 2,
+                offset: position_offset,
+            },
+            VertexAttribute {
+                location: Self::UV_LOC,
+                components: 2,
+                offset: uv_offset,
+            },
+            VertexAttribute {
+                location: Self::COLOR_LOC,
+                components: 4,
+                offset: color_offset,
+            },
+        ];
+        attributes.extend_from_slice(&self.extra_attributes);
+        attributes
+    }
+
+    /// Checks that every attribute read by the shader is provided by
+    /// this buffer, at the same location and with as many components.
+    ///
+    /// A mismatch otherwise renders garbage without any OpenGL error.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AttributeMismatch` for the first attribute that doesn't match.
+    pub fn validate_layout(&self, device: &GraphicDevice, shader: &Shader) -> errors::Result<()> {
+        validate_attribute_layout(&self.attributes(), &shader.active_attributes(device))
     }
 
     /// Draw a subset of the vertex array.
     pub fn draw(&self, device: &GraphicDevice, start: usize, count: usize) {
-        todo!()
+        self.draw_range(device, start, count, 0)
+    }
+
+    /// Draw a range of indices as triangles, offset by a base vertex.
+    ///
+    /// The `base_vertex` is added to each index before the vertex is
+    /// fetched. This allows multiple meshes to share one buffer while
+    /// keeping their own 0-based indices.
+    ///
+    /// Requires OpenGL 3.2 or later.
+    pub fn draw_range(
+        &self,
+        device: &GraphicDevice,
+        index_offset: usize,
+        count: usize,
+        base_vertex: i32,
+    ) {
+        self.draw_primitives(
+            device,
+            Primitive::Triangles,
+            index_offset,
+            count,
+            base_vertex,
+        )
+    }
+
+    /// Draw a range of indices, assembled as the given primitive.
+    ///
+    /// See `draw_range` for the meaning of `base_vertex`.
+    pub fn draw_primitives(
+        &self,
+        device: &GraphicDevice,
+        primitive: Primitive,
+        index_offset: usize,
+        count: usize,
+        base_vertex: i32,
+    ) {
+        let (index_type, byte_offset) = self.index_elements(index_offset);
+
+        unsafe {
+            device.gl.front_face(self.winding.to_gl());
+            device.gl.bind_vertex_array(Some(self.vbo));
+            device.gl.draw_elements_base_vertex(
+                primitive.to_gl(),
+                count as i32,
+                index_type,
+                byte_offset,
+                base_vertex,
+            );
+            debug_assert_gl_at(&device.gl, "draw_elements_base_vertex", ());
+        }
+    }
+
+    /// Index type passed to the draw call, and the offset in bytes
+    /// into the index buffer of the first index drawn.
+    fn index_elements(&self, index_offset: usize) -> (u32, i32) {
+        (
+            self.index_type.to_gl(),
+            (index_offset * self.index_type.size()) as i32,
+        )
     }
 }
 
 impl Drop for VertexBuffer {
     fn drop(&mut self) {
-        self.destroy.send(Destroy::VertexArray(self.vbo)).unwrap();
+        self.destroy.send(Destroy::VertexArray(self.vbo));
+        self.destroy.send(Destroy::Buffer(self.vertex_buffer));
+        self.destroy.send(Destroy::Buffer(self.index_buffer));
+    }
+}
+
+fn validate_attribute_layout(
+    buffer_attributes: &[VertexAttribute],
+    shader_attributes: &[AttributeInfo],
+) -> errors::Result<()> {
+    for attribute in shader_attributes {
+        let provided = buffer_attributes
+            .iter()
+            .find(|provided| provided.location == attribute.location);
+
+        let mismatch = match provided {
+            Some(provided) => {
+                // Components of matrices and other types aren't counted.
+                attribute.components != 0 && attribute.components != provided.components
+            }
+            None => true,
+        };
+
+        if mismatch {
+            return Err(errors::Error::AttributeMismatch {
+                name: attribute.name.clone(),
+                location: attribute.location,
+                components: attribute.components,
+                buffer_components: provided.map(|provided| provided.components),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::device::DestroyQueue;
+
+    /// Creates a vertex buffer without allocating video memory.
+    ///
+    /// The buffer's destroy requests are pushed onto the
+    /// returned queue on drop, as long as the queue is alive.
+    pub(crate) fn dummy_vertex_buffer() -> (DestroyQueue, VertexBuffer) {
+        let (destroy, rx) = DestroySender::channel();
+        let vertex_buffer = VertexBuffer {
+            vbo: 1,
+            vertex_buffer: 2,
+            index_buffer: 3,
+            winding: Winding::Ccw,
+            index_type: IndexType::U16,
+            format: VertexFormat::Float,
+            extra_attributes: vec![],
+            destroy,
+        };
+        (rx, vertex_buffer)
+    }
+
+    #[test]
+    fn test_index_type() {
+        let (_rx, mut vertex_buffer) = dummy_vertex_buffer();
+        assert_eq!(vertex_buffer.index_type(), IndexType::U16);
+        assert_eq!(vertex_buffer.index_elements(6), (glow::UNSIGNED_SHORT, 12));
+
+        vertex_buffer.index_type = IndexType::U32;
+        assert_eq!(vertex_buffer.index_elements(6), (glow::UNSIGNED_INT, 24));
+    }
+
+    fn sprite_attributes() -> Vec<AttributeInfo> {
+        [("a_Pos", 0, 2), ("a_UV", 1, 2), ("a_Color", 2, 4)]
+            .iter()
+            .map(|(name, location, components)| AttributeInfo {
+                name: name.to_string(),
+                location: *location,
+                gl_type: glow::FLOAT,
+                size: 1,
+                components: *components,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_validate_attribute_layout() {
+        let buffer_attributes: Vec<VertexAttribute> = [(0, 2), (1, 2), (2, 4)]
+            .iter()
+            .map(|(location, components)| VertexAttribute {
+                location: *location,
+                components: *components,
+                offset: 0,
+            })
+            .collect();
+
+        assert!(validate_attribute_layout(&buffer_attributes, &sprite_attributes()).is_ok());
+
+        // Colour read from the location of the UVs.
+        let mut swapped = sprite_attributes();
+        swapped[2].location = 1;
+        let err = validate_attribute_layout(&buffer_attributes, &swapped).unwrap_err();
+        assert!(matches!(
+            err,
+            errors::Error::AttributeMismatch {
+                location: 1,
+                components: 4,
+                buffer_components: Some(2),
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Shader attribute 'a_Color' at location 1 has 4 components, but the vertex buffer provides 2."
+        );
+
+        // Attribute the buffer doesn't provide at all.
+        let mut extra = sprite_attributes();
+        extra.push(AttributeInfo {
+            name: "a_Phase".to_owned(),
+            location: 3,
+            gl_type: glow::FLOAT,
+            size: 1,
+            components: 1,
+        });
+        assert!(matches!(
+            validate_attribute_layout(&buffer_attributes, &extra),
+            Err(errors::Error::AttributeMismatch {
+                location: 3,
+                buffer_components: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_packed_vertex_layout() {
+        assert_eq!(VertexFormat::Packed.stride(), 20);
+        assert_eq!(VertexFormat::Packed.offsets(), [0, 8, 16]);
+
+        let vertices = [
+            PackedVertex::new([1.0, 2.0], [0.0, 1.0], [1.0, 0.5, 0.0, 0.25]),
+            PackedVertex::new([3.0, 4.0], [1.0, 0.0], color::WHITE),
+        ];
+        let bytes = unsafe { utils::as_u8(&vertices) };
+        assert_eq!(bytes.len(), 40);
+
+        // Channels are laid out as red, green, blue, alpha, which the
+        // shader receives normalised by 255.
+        let color_bytes = &bytes[16..20];
+        assert_eq!(color_bytes, &[255, 128, 0, 64]);
+        let normalized: Vec<f32> = color_bytes.iter().map(|b| *b as f32 / 255.0).collect();
+        for (channel, expected) in normalized.iter().zip([1.0, 0.5, 0.0, 0.25].iter()) {
+            assert!((channel - expected).abs() < 1.0 / 255.0);
+        }
+
+        assert_eq!(&bytes[36..40], &[255, 255, 255, 255]);
     }
 }
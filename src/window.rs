@@ -0,0 +1,52 @@
+//! Window and OpenGL context creation.
+use crate::{device::GraphicDevice, errors};
+use glutin::{
+    dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder, Api, ContextBuilder, GlProfile,
+    GlRequest, PossiblyCurrent, WindowedContext,
+};
+
+/// Creates a window with a current OpenGL context, and a graphics device
+/// drawing to it.
+///
+/// The context is OpenGL 3.3 core, with vsync, and a 24-bit depth
+/// buffer and 8-bit stencil buffer.
+///
+/// # Examples
+///
+/// ```no_run
+/// let (device, event_loop, windowed_context) = grok_glow::init_window("Grok", 1024, 768)?;
+/// # Ok::<(), grok_glow::errors::Error>(())
+/// ```
+pub fn init_window(
+    title: &str,
+    width: u32,
+    height: u32,
+) -> errors::Result<(
+    GraphicDevice,
+    EventLoop<()>,
+    WindowedContext<PossiblyCurrent>,
+)> {
+    let event_loop = EventLoop::new();
+    let window_builder = WindowBuilder::new()
+        .with_title(title)
+        .with_inner_size(LogicalSize::new(width, height));
+
+    let windowed_context = ContextBuilder::new()
+        .with_vsync(true)
+        .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+        .with_gl_profile(GlProfile::Core)
+        .with_depth_buffer(24)
+        .with_stencil_buffer(8)
+        .build_windowed(window_builder, &event_loop)
+        .map_err(|err| errors::Error::ContextCreation(err.to_string()))?;
+
+    let windowed_context = unsafe {
+        windowed_context
+            .make_current()
+            .map_err(|(_, err)| errors::Error::ContextCreation(err.to_string()))?
+    };
+
+    let device = unsafe { GraphicDevice::from_windowed_context(&windowed_context) };
+
+    Ok((device, event_loop, windowed_context))
+}
@@ -0,0 +1,407 @@
+//! Helpers shared by tests that need an OpenGL context.
+//!
+//! Contexts are created without a window server, through EGL's surfaceless
+//! platform, and draw into an off-screen pbuffer standing in for the window.
+//! Tests return early when no context can be created, like on machines
+//! without EGL or an OpenGL 3.3 driver.
+#![allow(dead_code)]
+
+use grok_glow::{device::GraphicDevice, rect::Rect, shader::Shader};
+
+/// Headless context and a device drawing to it, or return from the
+/// calling test when none can be created.
+///
+/// The context must outlive the device, so bind it first:
+///
+/// ```ignore
+/// let (_context, device) = headless_device!(64, 64);
+/// ```
+macro_rules! headless_device {
+    ($width:expr, $height:expr) => {
+        match common::headless_device($width, $height) {
+            Some(pair) => pair,
+            None => {
+                eprintln!("skipped: no headless OpenGL 3.3 context");
+                return;
+            }
+        }
+    };
+}
+
+/// Headless context without a device, or return from the calling test
+/// when none can be created.
+#[allow(unused_macros)]
+macro_rules! headless_context {
+    ($width:expr, $height:expr) => {
+        match common::headless_context($width, $height) {
+            Some(context) => context,
+            None => {
+                eprintln!("skipped: no headless OpenGL 3.3 context");
+                return;
+            }
+        }
+    };
+}
+
+/// Headless context, current on the calling thread.
+pub fn headless_context(width: u32, height: u32) -> Option<HeadlessContext> {
+    HeadlessContext::new(width, height)
+}
+
+/// Headless context and a device drawing to its pbuffer.
+pub fn headless_device(width: u32, height: u32) -> Option<(HeadlessContext, GraphicDevice)> {
+    let context = HeadlessContext::new(width, height)?;
+
+    let device = unsafe { GraphicDevice::from_loader_function(|s| context.get_proc_address(s)) };
+    device.set_viewport_size(grok_glow::dpi::PhysicalSize::new(width, height));
+
+    Some((context, device))
+}
+
+/// Shader drawing batched sprites.
+pub fn sprite_shader(device: &GraphicDevice) -> Shader {
+    Shader::try_from_source(
+        device,
+        include_str!("../../src/sprite.vert"),
+        include_str!("../../src/sprite.frag"),
+    )
+    .expect("sprite shader")
+}
+
+/// Shader drawing vertices in clip space with their vertex colour.
+pub fn color_shader(device: &GraphicDevice) -> Shader {
+    const VERTEX: &str = "#version 330 core
+layout(location = 0) in vec2 a_Pos;
+layout(location = 2) in vec4 a_Color;
+out vec4 v_Color;
+void main() {
+    v_Color = a_Color;
+    gl_Position = vec4(a_Pos, 0.0, 1.0);
+}";
+    const FRAGMENT: &str = "#version 330 core
+in vec4 v_Color;
+out vec4 Color;
+void main() {
+    Color = v_Color;
+}";
+    Shader::try_from_source(device, VERTEX, FRAGMENT).expect("colour shader")
+}
+
+/// Colour of a pixel of the bound framebuffer, with the origin in the top-left.
+pub fn pixel(device: &GraphicDevice, x: u32, y: u32) -> [u8; 4] {
+    let data = device
+        .read_framebuffer(Rect {
+            pos: [x, y],
+            size: [1, 1],
+        })
+        .expect("read pixel");
+    [data[0], data[1], data[2], data[3]]
+}
+
+/// Values of a floating point state variable, which glow can't query.
+pub fn get_floats(context: &HeadlessContext, parameter: u32, count: usize) -> Vec<f32> {
+    type GetFloatv = unsafe extern "system" fn(parameter: u32, data: *mut f32);
+
+    let get_float_v = context.get_proc_address("glGetFloatv");
+    assert!(!get_float_v.is_null(), "glGetFloatv");
+    let mut values = vec![0.0; count.max(16)];
+    unsafe {
+        let get_float_v = std::mem::transmute::<*const std::ffi::c_void, GetFloatv>(get_float_v);
+        get_float_v(parameter, values.as_mut_ptr());
+    }
+    values.truncate(count);
+    values
+}
+
+/// Values of a floating point parameter of the texture bound to
+/// `GL_TEXTURE_2D`, which glow can only query as integers.
+pub fn get_tex_parameter_floats(
+    context: &HeadlessContext,
+    parameter: u32,
+    count: usize,
+) -> Vec<f32> {
+    type GetTexParameterfv = unsafe extern "system" fn(target: u32, parameter: u32, data: *mut f32);
+
+    let get_tex_parameter_fv = context.get_proc_address("glGetTexParameterfv");
+    assert!(!get_tex_parameter_fv.is_null(), "glGetTexParameterfv");
+    let mut values = vec![0.0; count.max(4)];
+    unsafe {
+        let get_tex_parameter_fv =
+            std::mem::transmute::<*const std::ffi::c_void, GetTexParameterfv>(get_tex_parameter_fv);
+        get_tex_parameter_fv(glow::TEXTURE_2D, parameter, values.as_mut_ptr());
+    }
+    values.truncate(count);
+    values
+}
+
+#[cfg(feature = "log")]
+pub mod logger {
+    //! Logger capturing the messages of each test thread, so tests
+    //! running in parallel don't see each other's.
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use std::cell::RefCell;
+
+    thread_local! {
+        static MESSAGES: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            let message = (record.level(), record.args().to_string());
+            MESSAGES.with(|messages| messages.borrow_mut().push(message));
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CaptureLogger = CaptureLogger;
+
+    /// Install the capturing logger, if not installed yet.
+    pub fn install() {
+        if log::set_logger(&LOGGER).is_ok() {
+            log::set_max_level(LevelFilter::Trace);
+        }
+    }
+
+    /// Messages logged on the calling thread since the last call.
+    pub fn take() -> Vec<(Level, String)> {
+        MESSAGES.with(|messages| messages.borrow_mut().split_off(0))
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use self::egl::HeadlessContext;
+
+#[cfg(not(target_os = "linux"))]
+pub struct HeadlessContext;
+
+#[cfg(not(target_os = "linux"))]
+impl HeadlessContext {
+    fn new(_width: u32, _height: u32) -> Option<Self> {
+        None
+    }
+
+    pub fn get_proc_address(&self, _name: &str) -> *const std::ffi::c_void {
+        std::ptr::null()
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod egl {
+    //! Minimal EGL bindings, loaded at runtime so the tests link without it.
+    use std::{
+        ffi::{c_void, CString},
+        os::raw::{c_char, c_int, c_uint},
+        ptr,
+    };
+
+    type Display = *mut c_void;
+    type Config = *mut c_void;
+    type Surface = *mut c_void;
+    type Context = *mut c_void;
+
+    const PLATFORM_SURFACELESS_MESA: c_uint = 0x31DD;
+    const OPENGL_API: c_uint = 0x30A2;
+    const SURFACE_TYPE: c_int = 0x3033;
+    const PBUFFER_BIT: c_int = 0x0001;
+    const RENDERABLE_TYPE: c_int = 0x3040;
+    const OPENGL_BIT: c_int = 0x0008;
+    const RED_SIZE: c_int = 0x3024;
+    const GREEN_SIZE: c_int = 0x3023;
+    const BLUE_SIZE: c_int = 0x3022;
+    const ALPHA_SIZE: c_int = 0x3021;
+    const DEPTH_SIZE: c_int = 0x3025;
+    const STENCIL_SIZE: c_int = 0x3026;
+    const WIDTH: c_int = 0x3057;
+    const HEIGHT: c_int = 0x3056;
+    const CONTEXT_MAJOR_VERSION: c_int = 0x3098;
+    const CONTEXT_MINOR_VERSION: c_int = 0x30FB;
+    const CONTEXT_OPENGL_PROFILE_MASK: c_int = 0x30FD;
+    const CONTEXT_OPENGL_CORE_PROFILE_BIT: c_int = 0x0001;
+    const NONE: c_int = 0x3038;
+    const RTLD_NOW: c_int = 2;
+
+    extern "C" {
+        fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+
+    /// Function pointer of the given type to a symbol of the library.
+    ///
+    /// # Safety
+    ///
+    /// The type must be the symbol's function signature.
+    unsafe fn symbol<F>(library: *mut c_void, name: &[u8]) -> Option<F> {
+        let symbol = dlsym(library, name.as_ptr() as *const c_char);
+        if symbol.is_null() {
+            None
+        } else {
+            Some(std::mem::transmute_copy::<*mut c_void, F>(&symbol))
+        }
+    }
+
+    struct Egl {
+        get_proc_address: unsafe extern "C" fn(*const c_char) -> *const c_void,
+        initialize: unsafe extern "C" fn(Display, *mut c_int, *mut c_int) -> c_uint,
+        bind_api: unsafe extern "C" fn(c_uint) -> c_uint,
+        choose_config:
+            unsafe extern "C" fn(Display, *const c_int, *mut Config, c_int, *mut c_int) -> c_uint,
+        create_pbuffer_surface: unsafe extern "C" fn(Display, Config, *const c_int) -> Surface,
+        create_context: unsafe extern "C" fn(Display, Config, Context, *const c_int) -> Context,
+        make_current: unsafe extern "C" fn(Display, Surface, Surface, Context) -> c_uint,
+        destroy_surface: unsafe extern "C" fn(Display, Surface) -> c_uint,
+        destroy_context: unsafe extern "C" fn(Display, Context) -> c_uint,
+    }
+
+    impl Egl {
+        fn load() -> Option<Self> {
+            unsafe {
+                let library = dlopen(b"libEGL.so.1\0".as_ptr() as *const c_char, RTLD_NOW);
+                if library.is_null() {
+                    return None;
+                }
+
+                Some(Self {
+                    get_proc_address: symbol(library, b"eglGetProcAddress\0")?,
+                    initialize: symbol(library, b"eglInitialize\0")?,
+                    bind_api: symbol(library, b"eglBindAPI\0")?,
+                    choose_config: symbol(library, b"eglChooseConfig\0")?,
+                    create_pbuffer_surface: symbol(library, b"eglCreatePbufferSurface\0")?,
+                    create_context: symbol(library, b"eglCreateContext\0")?,
+                    make_current: symbol(library, b"eglMakeCurrent\0")?,
+                    destroy_surface: symbol(library, b"eglDestroySurface\0")?,
+                    destroy_context: symbol(library, b"eglDestroyContext\0")?,
+                })
+            }
+        }
+
+        fn proc_address(&self, name: &str) -> *const c_void {
+            let name = CString::new(name).unwrap();
+            unsafe { (self.get_proc_address)(name.as_ptr()) }
+        }
+    }
+
+    /// OpenGL 3.3 core context, current on the thread that created it.
+    pub struct HeadlessContext {
+        egl: Egl,
+        display: Display,
+        surface: Surface,
+        context: Context,
+    }
+
+    impl HeadlessContext {
+        pub(super) fn new(width: u32, height: u32) -> Option<Self> {
+            let egl = Egl::load()?;
+
+            unsafe {
+                type GetPlatformDisplay =
+                    unsafe extern "C" fn(c_uint, *mut c_void, *const c_int) -> Display;
+                let get_platform_display = egl.proc_address("eglGetPlatformDisplayEXT");
+                if get_platform_display.is_null() {
+                    return None;
+                }
+                let get_platform_display =
+                    std::mem::transmute::<*const c_void, GetPlatformDisplay>(get_platform_display);
+
+                let display =
+                    get_platform_display(PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null());
+                if display.is_null()
+                    || (egl.initialize)(display, ptr::null_mut(), ptr::null_mut()) == 0
+                    || (egl.bind_api)(OPENGL_API) == 0
+                {
+                    return None;
+                }
+
+                #[rustfmt::skip]
+                let config_attributes = [
+                    SURFACE_TYPE, PBUFFER_BIT,
+                    RENDERABLE_TYPE, OPENGL_BIT,
+                    RED_SIZE, 8,
+                    GREEN_SIZE, 8,
+                    BLUE_SIZE, 8,
+                    ALPHA_SIZE, 8,
+                    DEPTH_SIZE, 24,
+                    STENCIL_SIZE, 8,
+                    NONE,
+                ];
+                let mut config = ptr::null_mut();
+                let mut count = 0;
+                if (egl.choose_config)(
+                    display,
+                    config_attributes.as_ptr(),
+                    &mut config,
+                    1,
+                    &mut count,
+                ) == 0
+                    || count == 0
+                {
+                    return None;
+                }
+
+                let surface_attributes = [WIDTH, width as c_int, HEIGHT, height as c_int, NONE];
+                let surface =
+                    (egl.create_pbuffer_surface)(display, config, surface_attributes.as_ptr());
+                if surface.is_null() {
+                    return None;
+                }
+
+                #[rustfmt::skip]
+                let context_attributes = [
+                    CONTEXT_MAJOR_VERSION, 3,
+                    CONTEXT_MINOR_VERSION, 3,
+                    CONTEXT_OPENGL_PROFILE_MASK, CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                    NONE,
+                ];
+                let context = (egl.create_context)(
+                    display,
+                    config,
+                    ptr::null_mut(),
+                    context_attributes.as_ptr(),
+                );
+                if context.is_null() {
+                    (egl.destroy_surface)(display, surface);
+                    return None;
+                }
+
+                let headless = Self {
+                    egl,
+                    display,
+                    surface,
+                    context,
+                };
+                if (headless.egl.make_current)(display, surface, surface, context) == 0 {
+                    return None;
+                }
+
+                Some(headless)
+            }
+        }
+
+        pub fn get_proc_address(&self, name: &str) -> *const c_void {
+            self.egl.proc_address(name)
+        }
+    }
+
+    impl Drop for HeadlessContext {
+        fn drop(&mut self) {
+            // The display is shared by every context of the process,
+            // so it's left initialized.
+            unsafe {
+                (self.egl.make_current)(
+                    self.display,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                );
+                (self.egl.destroy_surface)(self.display, self.surface);
+                (self.egl.destroy_context)(self.display, self.context);
+            }
+        }
+    }
+}
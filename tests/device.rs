@@ -0,0 +1,505 @@
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{
+    device::{BlendEquation, BlendFactor, BlendMode, GraphicDevice},
+    dpi::PhysicalSize,
+    rect::Rect,
+    shader::Shader,
+    sprite_batch::{Sprite, SpriteBatch},
+    texture::{ImageView, Texture},
+    texture_array::TextureArray,
+};
+
+#[test]
+fn test_clear_region() {
+    let (_context, device) = headless_device!(64, 64);
+    let pixel = |x, y| {
+        device
+            .read_framebuffer(Rect {
+                pos: [x, y],
+                size: [1, 1],
+            })
+            .unwrap()
+    };
+
+    device.clear_screen([0.0, 0.0, 1.0, 1.0]);
+    device.clear_region(
+        Rect {
+            pos: [8, 8],
+            size: [16, 16],
+        },
+        [1.0, 0.0, 0.0, 1.0],
+    );
+
+    assert_eq!(pixel(8, 8), [255, 0, 0, 255]);
+    assert_eq!(pixel(23, 23), [255, 0, 0, 255]);
+    assert_eq!(pixel(24, 8), [0, 0, 255, 255]);
+    assert_eq!(pixel(8, 40), [0, 0, 255, 255]);
+
+    // Scissor state is restored.
+    assert_eq!(device.scissor(), None);
+}
+
+#[test]
+fn test_upload_budget() {
+    let (_context, device) = headless_device!(64, 64);
+    let mut texture = Texture::new(&device, 16, 16).unwrap();
+    let data = vec![255; 16 * 16 * 4];
+
+    device.set_upload_budget(Some(1024));
+    device.begin_frame().unwrap();
+
+    // The first upload overruns the budget of 1KiB.
+    texture.update_data(&device, &data).unwrap();
+    assert_eq!(device.upload_bytes_this_frame(), 1024);
+    assert!(matches!(
+        texture.update_data(&device, &data),
+        Err(grok_glow::errors::Error::UploadBudgetExceeded {
+            used: 1024,
+            budget: 1024
+        })
+    ));
+    device.end_frame().unwrap();
+
+    // Deferred to the next frame.
+    device.begin_frame().unwrap();
+    assert_eq!(device.upload_bytes_this_frame(), 0);
+    texture.update_data(&device, &data).unwrap();
+    device.end_frame().unwrap();
+}
+
+/// Uploads from an image region and of array layers count against the
+/// budget, and are refused once it's spent.
+#[test]
+fn test_upload_budget_region_and_array() {
+    let (_context, device) = headless_device!(64, 64);
+    let mut texture = Texture::new(&device, 16, 16).unwrap();
+    let data = vec![255; 16 * 16 * 4];
+    let region = Rect {
+        pos: [4, 4],
+        size: [8, 8],
+    };
+    let src = ImageView::new(&data, 16, 16, region).unwrap();
+
+    device.set_upload_budget(Some(1024));
+    device.begin_frame().unwrap();
+
+    // Only the region's pixels are counted.
+    texture
+        .update_sub_from_region(&device, [0, 0], &src)
+        .unwrap();
+    assert_eq!(device.upload_bytes_this_frame(), 8 * 8 * 4);
+    TextureArray::from_frames(&device, 16, 16, &[&data]).unwrap();
+    assert_eq!(device.upload_bytes_this_frame(), 8 * 8 * 4 + 1024);
+
+    assert!(matches!(
+        texture.update_sub_from_region(&device, [0, 0], &src),
+        Err(grok_glow::errors::Error::UploadBudgetExceeded { .. })
+    ));
+    assert!(matches!(
+        TextureArray::from_frames(&device, 16, 16, &[&data]),
+        Err(grok_glow::errors::Error::UploadBudgetExceeded { .. })
+    ));
+    device.end_frame().unwrap();
+}
+
+#[test]
+fn test_blend_color() {
+    let (context, device) = headless_device!(16, 16);
+
+    device.set_blend_color([0.25, 0.5, 0.75, 1.0]);
+    assert_eq!(device.get_blend_color(), [0.25, 0.5, 0.75, 1.0]);
+    assert_eq!(
+        common::get_floats(&context, glow::BLEND_COLOR, 4),
+        [0.25, 0.5, 0.75, 1.0]
+    );
+}
+
+/// Once the context is lost, dropping resources doesn't panic, and
+/// draws and clears leave the framebuffer untouched.
+#[test]
+fn test_context_lost() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+    let texture = Texture::new(&device, 4, 4).unwrap();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    device.clear_screen([0.0, 0.0, 1.0, 1.0]);
+    device.mark_context_lost();
+    assert!(!device.is_context_valid());
+
+    device.clear_screen([1.0, 0.0, 0.0, 1.0]);
+    batch.add(
+        &Sprite::builder()
+            .size([16, 16])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+    batch.draw(&device, &shader);
+    assert!(batch.is_empty());
+
+    drop(texture);
+    device.maintain().unwrap();
+    assert_eq!(common::pixel(&device, 8, 8), [0, 0, 255, 255]);
+}
+
+/// Recreating the device with a loader reloads the entry points glow
+/// doesn't wrap, while recreating it from a context alone drops them.
+#[test]
+fn test_recreate_reloads_functions() {
+    let (context, mut device) = headless_device!(16, 16);
+    let range = device.line_width_range();
+    // Any index other than the fixed one needs `glPrimitiveRestartIndex`.
+    let restart = Some(1);
+    device.set_primitive_restart(restart).unwrap();
+
+    device.mark_context_lost();
+    unsafe { device.recreate_with_loader(|s| context.get_proc_address(s)) };
+    assert!(device.is_context_valid());
+    assert_eq!(device.line_width_range(), range);
+    device.set_primitive_restart(restart).unwrap();
+
+    device.mark_context_lost();
+    device
+        .recreate(unsafe { glow::Context::from_loader_function(|s| context.get_proc_address(s)) });
+    assert!(matches!(
+        device.set_primitive_restart(restart),
+        Err(grok_glow::errors::Error::Unsupported(
+            "glPrimitiveRestartIndex"
+        ))
+    ));
+    device.set_primitive_restart(None).unwrap();
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// After shutdown, batches and the device draw nothing.
+#[test]
+fn test_draw_after_shutdown() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    device.clear_screen([0.0, 0.0, 1.0, 1.0]);
+    device.shutdown();
+    assert!(device.is_shutting_down());
+
+    let sprite = Sprite::builder()
+        .size([16, 16])
+        .color([1.0, 0.0, 0.0, 1.0])
+        .build();
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    let texture = Texture::new(&device, 4, 4).unwrap();
+    device.draw_fullscreen(&texture, &shader).unwrap();
+
+    assert_eq!(common::pixel(&device, 8, 8), [0, 0, 255, 255]);
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// Solid textures are shared, and a sprite without a texture is drawn
+/// with the white one.
+#[test]
+fn test_solid_textures() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+
+    let white = device.white_texture().unwrap();
+    assert_eq!(
+        white.raw_handle(),
+        device.white_texture().unwrap().raw_handle()
+    );
+    let red = device.solid_texture([1.0, 0.0, 0.0, 1.0]).unwrap();
+    assert_ne!(red.raw_handle(), white.raw_handle());
+    assert_eq!(red.read_pixels(&device).unwrap(), [255, 0, 0, 255]);
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(&Sprite::builder().size([16, 16]).build());
+    batch.draw(&device, &shader);
+    assert_eq!(common::pixel(&device, 8, 8), [255, 255, 255, 255]);
+}
+
+/// Queries state the device doesn't wrap through the raw context.
+#[test]
+fn test_gl_context() {
+    let (_context, device) = headless_device!(16, 16);
+
+    let version = unsafe { device.gl_context().get_parameter_string(glow::VERSION) };
+    assert_eq!(version, device.opengl_info().version);
+    assert!(device.gl_version() >= (3, 3));
+}
+
+/// A dropped texture whose destroy message was never processed is
+/// reported as a leak, until the device is maintained.
+#[test]
+#[cfg(debug_assertions)]
+fn test_debug_assert_no_leaks() {
+    let (_context, device) = headless_device!(16, 16);
+    drop(Texture::new(&device, 4, 4).unwrap());
+
+    let leaked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        device.debug_assert_no_leaks();
+    }));
+    assert!(leaked.is_err());
+
+    device.maintain().unwrap();
+    device.debug_assert_no_leaks();
+}
+
+/// Textures dropped between frames are deleted by `begin_frame`, and
+/// those dropped during a frame by `end_frame`.
+#[test]
+fn test_frame_drains_destroy_queue() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+
+    let texture = Texture::new(&device, 4, 4).unwrap();
+    let handle = texture.raw_handle();
+    drop(texture);
+    assert!(unsafe { gl.is_texture(handle) });
+    device.begin_frame().unwrap();
+    assert!(unsafe { !gl.is_texture(handle) });
+
+    let texture = Texture::new(&device, 4, 4).unwrap();
+    let handle = texture.raw_handle();
+    drop(texture);
+    device.end_frame().unwrap();
+    assert!(unsafe { !gl.is_texture(handle) });
+    device.debug_assert_no_leaks();
+}
+
+/// The range is queried from the context, and `LINE_WIDTH` follows
+/// the width set, clamped to the range.
+#[test]
+fn test_line_width() {
+    let (context, device) = headless_device!(16, 16);
+
+    let range = common::get_floats(&context, glow::ALIASED_LINE_WIDTH_RANGE, 2);
+    assert_eq!(device.line_width_range(), [range[0], range[1]]);
+
+    let width = range[1].min(2.0);
+    device.set_line_width(width);
+    assert_eq!(device.get_line_width(), width);
+    assert_eq!(common::get_floats(&context, glow::LINE_WIDTH, 1), [width]);
+
+    device.set_line_width(range[1] + 1.0);
+    assert_eq!(
+        common::get_floats(&context, glow::LINE_WIDTH, 1),
+        [range[1]]
+    );
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// State bound through the raw context is cleared, and blending and the
+/// scissor test are disabled when asked.
+#[test]
+fn test_reset_gl_state() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+    let shader = common::sprite_shader(&device);
+    let texture = Texture::new(&device, 4, 4).unwrap();
+
+    unsafe {
+        let vertex_array = gl.create_vertex_array().unwrap();
+        let buffer = gl.create_buffer().unwrap();
+        let sampler = gl.create_sampler().unwrap();
+        gl.bind_vertex_array(Some(vertex_array));
+        gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+        gl.use_program(Some(shader.raw_handle()));
+        gl.active_texture(glow::TEXTURE0);
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+        gl.bind_sampler(0, Some(sampler));
+        gl.active_texture(glow::TEXTURE3);
+        gl.enable(glow::BLEND);
+        gl.enable(glow::SCISSOR_TEST);
+
+        device.reset_gl_state(false);
+        assert!(gl.is_enabled(glow::BLEND));
+        assert!(gl.is_enabled(glow::SCISSOR_TEST));
+
+        device.reset_gl_state(true);
+        assert!(!gl.is_enabled(glow::BLEND));
+        assert!(!gl.is_enabled(glow::SCISSOR_TEST));
+        for &binding in &[
+            glow::VERTEX_ARRAY_BINDING,
+            glow::ARRAY_BUFFER_BINDING,
+            glow::PIXEL_PACK_BUFFER_BINDING,
+            glow::PIXEL_UNPACK_BUFFER_BINDING,
+            glow::CURRENT_PROGRAM,
+            glow::TEXTURE_BINDING_2D,
+            glow::SAMPLER_BINDING,
+        ] {
+            assert_eq!(gl.get_parameter_i32(binding), 0, "binding 0x{:x}", binding);
+        }
+        assert_eq!(
+            gl.get_parameter_i32(glow::ACTIVE_TEXTURE),
+            glow::TEXTURE0 as i32
+        );
+
+        gl.delete_vertex_array(vertex_array);
+        gl.delete_buffer(buffer);
+        gl.delete_sampler(sampler);
+    }
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// With a 0x0 viewport, like a minimized window, clearing and drawing
+/// leave both the framebuffer and the viewport state untouched.
+#[test]
+fn test_empty_viewport() {
+    let (context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+    let texture = device.white_texture().unwrap();
+    device.clear_screen([1.0, 0.0, 0.0, 1.0]);
+
+    device.set_viewport_size(PhysicalSize::new(0, 0));
+    assert!(device.is_viewport_empty());
+    assert_eq!(device.get_viewport_size(), PhysicalSize::new(1, 1));
+
+    device.clear_screen([0.0, 0.0, 1.0, 1.0]);
+    device.clear_region(
+        Rect {
+            pos: [0, 0],
+            size: [8, 8],
+        },
+        [0.0, 0.0, 1.0, 1.0],
+    );
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.add(&Sprite::builder().size([16, 16]).build());
+    batch.draw(&device, &shader);
+    device.draw_fullscreen(&texture, &shader).unwrap();
+
+    assert_eq!(
+        common::get_floats(&context, glow::VIEWPORT, 4),
+        [0.0, 0.0, 16.0, 16.0]
+    );
+    assert_eq!(
+        common::get_floats(&context, glow::COLOR_CLEAR_VALUE, 4),
+        [1.0, 0.0, 0.0, 1.0]
+    );
+    assert_eq!(device.take_gl_error(), None);
+
+    device.set_viewport_size(PhysicalSize::new(16, 16));
+    assert!(!device.is_viewport_empty());
+    for &(x, y) in &[(0, 0), (4, 4), (15, 15)] {
+        assert_eq!(common::pixel(&device, x, y), [255, 0, 0, 255]);
+    }
+}
+
+/// A custom blend mode sets all four factors and both equations, and the
+/// alpha channel is blended with its own equation.
+#[test]
+fn test_custom_blend_mode() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+    let shader = common::sprite_shader(&device);
+
+    device.set_blend_mode(BlendMode::Custom {
+        src_rgb: BlendFactor::One,
+        dst_rgb: BlendFactor::OneMinusSrcAlpha,
+        src_alpha: BlendFactor::One,
+        dst_alpha: BlendFactor::One,
+        equation_rgb: BlendEquation::Add,
+        equation_alpha: BlendEquation::Max,
+    });
+    assert_eq!(
+        device.blend_factors(),
+        [glow::ONE, glow::ONE_MINUS_SRC_ALPHA, glow::ONE, glow::ONE]
+    );
+    unsafe {
+        assert_eq!(
+            gl.get_parameter_i32(glow::BLEND_EQUATION_RGB) as u32,
+            glow::FUNC_ADD
+        );
+        assert_eq!(
+            gl.get_parameter_i32(glow::BLEND_EQUATION_ALPHA) as u32,
+            glow::MAX
+        );
+    }
+
+    // Premultiplied half red over a quarter opaque blue. Adding the alpha
+    // would give 0.75, the maximum keeps 0.5.
+    device.clear_screen([0.0, 0.0, 1.0, 0.25]);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.add(
+        &Sprite::builder()
+            .size([16, 16])
+            .color([0.5, 0.0, 0.0, 0.5])
+            .build(),
+    );
+    batch.draw(&device, &shader);
+
+    let pixel = common::pixel(&device, 8, 8);
+    let expected: [u8; 4] = [128, 0, 128, 128];
+    for (channel, (actual, expected)) in pixel.iter().zip(expected.iter()).enumerate() {
+        assert!(
+            (*actual as i32 - *expected as i32).abs() <= 1,
+            "channel {} of {:?}",
+            channel,
+            pixel
+        );
+    }
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// The first row of the texture covers the top half of the window, and
+/// the second row the bottom half.
+#[test]
+fn test_draw_fullscreen_orientation() {
+    const VERTEX: &str = "#version 330 core
+layout(location = 0) in vec2 a_Pos;
+layout(location = 1) in vec2 a_UV;
+out vec2 v_TexCoord;
+void main() {
+    v_TexCoord = a_UV;
+    gl_Position = vec4(a_Pos, 0.0, 1.0);
+}";
+    const FRAGMENT: &str = "#version 330 core
+uniform sampler2D u_Texture;
+in vec2 v_TexCoord;
+out vec4 Color;
+void main() {
+    Color = texture(u_Texture, v_TexCoord);
+}";
+    let (_context, device) = headless_device!(16, 16);
+    let shader = Shader::try_from_source(&device, VERTEX, FRAGMENT).unwrap();
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let mut texture = Texture::new(&device, 1, 2).unwrap();
+    texture.update_data(&device, &[red, blue].concat()).unwrap();
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    device.draw_fullscreen(&texture, &shader).unwrap();
+
+    assert_eq!(common::pixel(&device, 8, 0), red);
+    assert_eq!(common::pixel(&device, 8, 7), red);
+    assert_eq!(common::pixel(&device, 8, 8), blue);
+    assert_eq!(common::pixel(&device, 8, 15), blue);
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// A device built from a context, with extension logging off, still
+/// knows the extensions, and logs none of them.
+#[test]
+fn test_build_without_extension_logging() {
+    #[cfg(feature = "log")]
+    common::logger::install();
+    let context = headless_context!(16, 16);
+    let gl = unsafe { glow::Context::from_loader_function(|s| context.get_proc_address(s)) };
+
+    let device = GraphicDevice::builder().log_extensions(false).build(gl);
+    #[cfg(feature = "log")]
+    assert!(common::logger::take()
+        .iter()
+        .all(|(_, message)| message != "Extensions:"));
+
+    let extension = unsafe {
+        device
+            .gl_context()
+            .get_parameter_indexed_string(glow::EXTENSIONS, 0)
+    };
+    assert!(device.has_extension(&extension));
+    assert!(!device.has_extension("GL_GROK_not_an_extension"));
+}
@@ -0,0 +1,42 @@
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{errors::Error, texture::Texture};
+
+/// An upload outside the texture raises a GL error, labelled with the
+/// call that raised it.
+#[test]
+fn test_labelled_gl_error() {
+    let (_context, device) = headless_device!(16, 16);
+    let mut texture = Texture::new(&device, 4, 4).unwrap();
+
+    let err = texture
+        .update_sub_data(&device, [8, 8], [1, 1], &[0; 4])
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::OpenGlAt {
+            code: glow::INVALID_VALUE,
+            op: "tex_sub_image_2d"
+        }
+    ));
+    assert_eq!(err.to_string(), "OpenGL Error: 0x501 in tex_sub_image_2d");
+}
+
+/// An error raised through the raw context is taken once, after which
+/// the error flag reads `NO_ERROR`.
+#[test]
+fn test_take_gl_error() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+
+    unsafe { gl.enable(0xFFFF) };
+    assert_eq!(device.take_gl_error(), Some(glow::INVALID_ENUM));
+    assert_eq!(device.take_gl_error(), None);
+    assert_eq!(unsafe { gl.get_error() }, glow::NO_ERROR);
+
+    unsafe { gl.enable(0xFFFF) };
+    assert_eq!(device.drain_gl_errors(), vec![glow::INVALID_ENUM]);
+    assert!(device.drain_gl_errors().is_empty());
+}
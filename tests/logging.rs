@@ -0,0 +1,84 @@
+//! Messages logged through the `log` crate.
+#![cfg(feature = "log")]
+
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{
+    device::GraphicDevice,
+    sprite_batch::{Sprite, SpriteBatch},
+    texture::Texture,
+};
+use log::Level;
+
+/// Every extension is listed at debug level when enabled.
+#[test]
+fn test_log_extensions() {
+    common::logger::install();
+    let context = headless_context!(16, 16);
+
+    let device = unsafe {
+        GraphicDevice::builder()
+            .log_extensions(true)
+            .build_with_loader(|s| context.get_proc_address(s))
+    };
+    let messages = common::logger::take();
+
+    let debug = |text: &str| {
+        messages
+            .iter()
+            .any(|(level, message)| *level == Level::Debug && message == text)
+    };
+    assert!(debug("Extensions:"));
+
+    let extension = unsafe {
+        device
+            .gl_context()
+            .get_parameter_indexed_string(glow::EXTENSIONS, 0)
+    };
+    assert!(device.has_extension(&extension));
+    assert!(debug(&format!("  {}", extension)));
+}
+
+/// A draw with more flushes than the threshold warns with the count.
+#[test]
+fn test_flush_warn_threshold() {
+    common::logger::install();
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+
+    // Every texture change flushes the batch.
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    for _ in 0..3 {
+        let texture = Texture::new(&device, 4, 4).unwrap();
+        batch.add(&Sprite::with_texture([0, 0], texture));
+    }
+    batch.set_flush_warn_threshold(Some(2));
+    common::logger::take();
+    batch.draw(&device, &shader);
+
+    assert_eq!(batch.stats().flushes, 3);
+    let warnings: Vec<String> = common::logger::take()
+        .into_iter()
+        .filter(|(level, _)| *level == Level::Warn)
+        .map(|(_, message)| message)
+        .collect();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("Sprite batch flushed 3 times"));
+}
+
+/// Dropping the device with a queued destroy warns about the leak.
+#[test]
+#[cfg(debug_assertions)]
+fn test_drop_warns_of_leaks() {
+    common::logger::install();
+    let (_context, device) = headless_device!(16, 16);
+    drop(Texture::new(&device, 4, 4).unwrap());
+
+    common::logger::take();
+    drop(device);
+    let messages = common::logger::take();
+    assert!(messages.iter().any(|(level, message)| *level == Level::Warn
+        && message.starts_with("Graphic device dropped with 1 resources not deallocated")));
+}
@@ -0,0 +1,40 @@
+#[macro_use]
+mod common;
+
+use grok_glow::{
+    post::PostProcess,
+    render_target::RenderTarget,
+    sprite_batch::{Sprite, SpriteBatch},
+};
+
+/// A mid-gray target with a red top strip is drawn unchanged, and
+/// the right way up, through the passthrough settings.
+#[test]
+fn test_passthrough() {
+    let (_context, device) = headless_device!(32, 32);
+    let shader = common::sprite_shader(&device);
+    let target = RenderTarget::new(&device, 32, 32).unwrap();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    target.bind(&device);
+    device.clear_screen([0.5, 0.5, 0.5, 1.0]);
+    batch.add(
+        &Sprite::builder()
+            .size([32, 8])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+    batch.draw_to_target(&device, &shader, target.size());
+    RenderTarget::unbind(&device);
+
+    let post = PostProcess::new(&device).unwrap();
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    post.draw(&device, target.color_attachment(0).unwrap());
+
+    assert_eq!(common::pixel(&device, 16, 4), [255, 0, 0, 255]);
+    let gray = common::pixel(&device, 16, 24);
+    for channel in &gray[..3] {
+        assert!((*channel as i32 - 128).abs() <= 1, "{:?}", gray);
+    }
+    assert_eq!(device.take_gl_error(), None);
+}
@@ -0,0 +1,173 @@
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{
+    rect::Rect,
+    render_target::{DepthStencil, RenderTarget},
+    shader::Shader,
+    sprite_batch::{Sprite, SpriteBatch},
+    texture::Texture,
+};
+
+#[test]
+fn test_depth_stencil_complete() {
+    let (_context, device) = headless_device!(64, 64);
+
+    // Construction fails with `IncompleteFramebuffer` otherwise.
+    let target = RenderTarget::with_depth_stencil(&device, 64, 32, 1, DepthStencil::BOTH).unwrap();
+    assert_eq!(target.depth_stencil(), DepthStencil::BOTH);
+
+    drop(target);
+    device.maintain().unwrap();
+    device.debug_assert_no_leaks();
+}
+
+/// Reading back a bound target gives the rows of its texture, even
+/// when the target is smaller than the window.
+#[test]
+fn test_read_framebuffer_rows() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let target = RenderTarget::new(&device, 32, 16).unwrap();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    target.bind(&device);
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(
+        &Sprite::builder()
+            .size([32, 4])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+    batch.draw_to_target(&device, &shader, target.size());
+
+    let rows = device
+        .read_framebuffer(Rect {
+            pos: [0, 0],
+            size: [32, 16],
+        })
+        .unwrap();
+    let bottom = device
+        .read_framebuffer(Rect {
+            pos: [0, 12],
+            size: [32, 4],
+        })
+        .unwrap();
+    RenderTarget::unbind(&device);
+
+    let texels = target
+        .color_attachment(0)
+        .unwrap()
+        .read_pixels(&device)
+        .unwrap();
+    assert_eq!(rows, texels);
+    assert_eq!(&rows[..4], [255, 0, 0, 255]);
+    assert_eq!(&bottom[..], &texels[12 * 32 * 4..]);
+}
+
+/// A retained batch drawn to two targets has the resolution of each,
+/// and covers the same pixels of both.
+#[test]
+fn test_draw_to_targets_of_different_sizes() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_retained(true);
+    batch.add(
+        &Sprite::builder()
+            .size([16, 16])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+
+    for size in &[32, 64] {
+        let target = RenderTarget::new(&device, *size, *size).unwrap();
+        target.bind(&device);
+        device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+        batch.draw_to_target(&device, &shader, target.size());
+        RenderTarget::unbind(&device);
+
+        // `u_Resolution` is at location 0.
+        let mut resolution = [0.0; 2];
+        unsafe {
+            device
+                .gl_context()
+                .get_uniform_f32(shader.raw_handle(), &0, &mut resolution)
+        };
+        assert_eq!(resolution, [*size as f32; 2]);
+
+        let texels = target
+            .color_attachment(0)
+            .unwrap()
+            .read_pixels(&device)
+            .unwrap();
+        let texel = |x: u32, y: u32| {
+            let i = ((y * size + x) * 4) as usize;
+            texels[i..i + 4].to_vec()
+        };
+        assert_eq!(texel(15, 15), [255, 0, 0, 255], "{}x{}", size, size);
+        assert_eq!(texel(16, 16), [0, 0, 0, 255], "{}x{}", size, size);
+    }
+}
+
+/// Both attachments of a target are complete and routed to the fragment
+/// outputs of `sprite_mrt.frag`, which writes colour and normals at once.
+#[test]
+fn test_multiple_color_attachments() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+    let shader = Shader::try_from_source(
+        &device,
+        include_str!("../src/sprite.vert"),
+        include_str!("../src/sprite_mrt.frag"),
+    )
+    .unwrap();
+    let mut normal = Texture::new(&device, 1, 1).unwrap();
+    normal.update_data(&device, &[128, 128, 255, 255]).unwrap();
+
+    let target = RenderTarget::with_color_attachments(&device, 8, 8, 2).unwrap();
+    assert_eq!(target.color_attachments().len(), 2);
+
+    target.bind(&device);
+    unsafe {
+        assert_eq!(
+            gl.check_framebuffer_status(glow::FRAMEBUFFER),
+            glow::FRAMEBUFFER_COMPLETE
+        );
+        assert_eq!(
+            gl.get_parameter_i32(glow::DRAW_BUFFER0) as u32,
+            glow::COLOR_ATTACHMENT0
+        );
+        assert_eq!(
+            gl.get_parameter_i32(glow::DRAW_BUFFER1) as u32,
+            glow::COLOR_ATTACHMENT1
+        );
+    }
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch
+        .set_auxiliary_texture(&device, 1, "u_Normal", normal)
+        .unwrap();
+    batch.add(
+        &Sprite::builder()
+            .size([8, 8])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+    batch.draw_to_target(&device, &shader, target.size());
+    RenderTarget::unbind(&device);
+
+    for (index, expected) in [[255, 0, 0, 255], [128, 128, 255, 255]].iter().enumerate() {
+        let texels = target
+            .color_attachment(index)
+            .unwrap()
+            .read_pixels(&device)
+            .unwrap();
+        assert_eq!(texels.len(), 8 * 8 * 4);
+        for texel in texels.chunks(4) {
+            assert_eq!(texel, expected, "attachment {}", index);
+        }
+    }
+    assert_eq!(device.take_gl_error(), None);
+}
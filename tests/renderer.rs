@@ -0,0 +1,38 @@
+#[macro_use]
+mod common;
+
+use grok_glow::{rect::Rect, renderer::Renderer, sprite_batch::Sprite};
+
+/// Draws a red sprite through the renderer into a headless context,
+/// and reads it back.
+#[test]
+fn test_renderer_draws_sprite() {
+    let (_context, device) = headless_device!(64, 64);
+
+    let mut renderer = Renderer::new(device).unwrap();
+    let sprite = Sprite::builder()
+        .pos([16, 16])
+        .size([32, 32])
+        .color([1.0, 0.0, 0.0, 1.0])
+        .build();
+
+    renderer.begin([0.0, 0.0, 1.0, 1.0]).unwrap();
+    renderer.draw_sprite(&sprite);
+    renderer.end().unwrap();
+
+    let device = renderer.device();
+    let inside = device
+        .read_framebuffer(Rect {
+            pos: [32, 32],
+            size: [1, 1],
+        })
+        .unwrap();
+    let outside = device
+        .read_framebuffer(Rect {
+            pos: [4, 4],
+            size: [1, 1],
+        })
+        .unwrap();
+    assert_eq!(inside, [255, 0, 0, 255]);
+    assert_eq!(outside, [0, 0, 255, 255]);
+}
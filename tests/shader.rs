@@ -0,0 +1,77 @@
+#[macro_use]
+mod common;
+
+use grok_glow::{
+    errors::Error,
+    shader::{glsl_type_name, Shader, ShaderStage},
+};
+
+/// Errors in both stages are reported together, each with its stage's
+/// info log, and the program is not linked.
+#[test]
+fn test_compile_errors_in_both_stages() {
+    let (_context, device) = headless_device!(16, 16);
+    const VERTEX: &str = "#version 330 core
+void main() {
+    gl_Position = vertex_undeclared;
+}";
+    const FRAGMENT: &str = "#version 330 core
+out vec4 Color;
+void main() {
+    Color = fragment_undeclared;
+}";
+
+    let logs = match Shader::try_from_source(&device, VERTEX, FRAGMENT) {
+        Err(Error::ShaderCompile(logs)) => logs,
+        Err(err) => panic!("unexpected error: {}", err),
+        Ok(_) => panic!("shader compiled"),
+    };
+    let stages = logs.iter().map(|log| log.stage).collect::<Vec<_>>();
+    assert_eq!(stages, [ShaderStage::Vertex, ShaderStage::Fragment]);
+    assert!(logs[0].log.contains("vertex_undeclared"), "{}", logs[0].log);
+    assert!(
+        logs[1].log.contains("fragment_undeclared"),
+        "{}",
+        logs[1].log
+    );
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// The sprite shader's uniforms and attributes are listed with the
+/// locations and types declared in its source.
+#[test]
+fn test_sprite_shader_symbols() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+
+    let uniforms = shader.active_uniforms(&device);
+    let resolution = uniforms
+        .iter()
+        .find(|uniform| uniform.name == "u_Resolution")
+        .expect("u_Resolution");
+    assert_eq!(resolution.location, Some(0));
+    assert_eq!(resolution.gl_type, glow::FLOAT_VEC2);
+    assert_eq!(glsl_type_name(resolution.gl_type), Some("vec2"));
+    assert_eq!(resolution.size, 1);
+    let albedo = uniforms
+        .iter()
+        .find(|uniform| uniform.name == "u_Albedo")
+        .expect("u_Albedo");
+    assert_eq!(albedo.location, Some(1));
+    assert_eq!(albedo.gl_type, glow::SAMPLER_2D);
+
+    let attributes = shader.active_attributes(&device);
+    let position = attributes
+        .iter()
+        .find(|attribute| attribute.name == "a_Pos")
+        .expect("a_Pos");
+    assert_eq!(position.location, 0);
+    assert_eq!(position.gl_type, glow::FLOAT_VEC2);
+    assert_eq!(position.components, 2);
+    let color = attributes
+        .iter()
+        .find(|attribute| attribute.name == "a_Color")
+        .expect("a_Color");
+    assert_eq!(color.location, 2);
+    assert_eq!(color.components, 4);
+}
@@ -0,0 +1,697 @@
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{
+    color,
+    primitives::Primitive,
+    rect::Rect,
+    render_target::RenderTarget,
+    sampler::{Filter, Sampler, Wrap},
+    shader::Shader,
+    sprite_batch::{Origin, Sprite, SpriteBatch},
+    texture::{PixelFormat, Texture},
+    texture_array::TextureArray,
+    vertex::Vertex,
+};
+use std::rc::Rc;
+
+/// Draws sprites 2..5 of ten red sprites in a row, and reads back
+/// which were drawn.
+#[test]
+fn test_draw_range() {
+    let (_context, device) = headless_device!(100, 10);
+    let shader = common::sprite_shader(&device);
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_retained(true);
+    for i in 0..10 {
+        let sprite = Sprite::builder()
+            .pos([i * 10, 0])
+            .size([10, 10])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build();
+        batch.add(&sprite);
+    }
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw_range(&device, &shader, 2..5);
+
+    let row = device
+        .read_framebuffer(Rect {
+            pos: [0, 5],
+            size: [100, 1],
+        })
+        .unwrap();
+    let drawn: Vec<u32> = (0..10)
+        .filter(|i| row[(*i as usize * 10 + 5) * 4] == 255)
+        .collect();
+    assert_eq!(drawn, [2, 3, 4]);
+}
+
+/// Sorting a retained batch only changes the drawing order, so ranges
+/// still index the sprites in the order they were added.
+#[test]
+fn test_draw_range_after_sorted_draw() {
+    let (_context, device) = headless_device!(100, 10);
+    let shader = common::sprite_shader(&device);
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_retained(true);
+    for i in 0..10 {
+        let sprite = Sprite::builder()
+            .pos([i * 10, 0])
+            .size([10, 10])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build();
+        batch.add(&sprite);
+    }
+    // Reverse submission order.
+    batch.set_sort_key(|item| u64::MAX - item.corners()[0][0] as u64);
+    batch.draw(&device, &shader);
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw_range(&device, &shader, 2..5);
+
+    let row = device
+        .read_framebuffer(Rect {
+            pos: [0, 5],
+            size: [100, 1],
+        })
+        .unwrap();
+    let drawn: Vec<u32> = (0..10)
+        .filter(|i| row[(*i as usize * 10 + 5) * 4] == 255)
+        .collect();
+    assert_eq!(drawn, [2, 3, 4]);
+}
+
+/// Records the texture bound before each group of sprites is drawn.
+#[test]
+fn test_draw_with_callback() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let first = Texture::new(&device, 4, 4).unwrap();
+    let second = Texture::new(&device, 4, 4).unwrap();
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    for texture in &[&first, &first, &second] {
+        batch.add(&Sprite::with_texture([0, 0], (*texture).clone()));
+    }
+
+    let mut bound = vec![];
+    batch.draw_with_callback(&device, &shader, &mut |_device, _shader, texture| {
+        bound.push(texture.raw_handle());
+    });
+    assert_eq!(bound, [first.raw_handle(), second.raw_handle()]);
+}
+
+/// A sprite in the top-left corner is at the top of the window, and in
+/// the first rows of a render target's texture, like uploaded images.
+#[test]
+fn test_flip_y_orientation() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let sprite = Sprite::builder()
+        .pos([0, 0])
+        .size([16, 16])
+        .color([1.0, 0.0, 0.0, 1.0])
+        .build();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    assert_eq!(common::pixel(&device, 4, 4), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 4, 60), [0, 0, 0, 255]);
+
+    let target = RenderTarget::new(&device, 64, 64).unwrap();
+    target.bind(&device);
+    assert!(!device.get_flip_y());
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(&sprite);
+    batch.draw_to_target(&device, &shader, target.size());
+    RenderTarget::unbind(&device);
+    assert!(device.get_flip_y());
+
+    let texels = target
+        .color_attachment(0)
+        .unwrap()
+        .read_pixels(&device)
+        .unwrap();
+    let texel = |x: usize, y: usize| &texels[(y * 64 + x) * 4..][..4];
+    assert_eq!(texel(4, 4), [255, 0, 0, 255]);
+    assert_eq!(texel(4, 60), [0, 0, 0, 255]);
+}
+
+/// With a bottom-left origin, a sprite at the origin is at the bottom
+/// of the window, and in the last rows of a render target's texture.
+#[test]
+fn test_bottom_left_origin() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let sprite = Sprite::builder()
+        .pos([0, 0])
+        .size([16, 16])
+        .color([1.0, 0.0, 0.0, 1.0])
+        .build();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_origin(Origin::BottomLeft);
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    assert_eq!(common::pixel(&device, 4, 60), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 4, 4), [0, 0, 0, 255]);
+
+    let target = RenderTarget::new(&device, 64, 64).unwrap();
+    target.bind(&device);
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(&sprite);
+    batch.draw_to_target(&device, &shader, target.size());
+    let bottom = common::pixel(&device, 4, 60);
+    RenderTarget::unbind(&device);
+
+    let texels = target
+        .color_attachment(0)
+        .unwrap()
+        .read_pixels(&device)
+        .unwrap();
+    let texel = |x: usize, y: usize| &texels[(y * 64 + x) * 4..][..4];
+    assert_eq!(texel(4, 60), [255, 0, 0, 255]);
+    assert_eq!(texel(4, 4), [0, 0, 0, 255]);
+    assert_eq!(bottom, [255, 0, 0, 255]);
+}
+
+/// A trapezoid, narrow at the top, covers the bottom corners
+/// but not the top ones.
+#[test]
+fn test_add_quad_trapezoid() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let white = device.white_texture().unwrap();
+    let full = Rect {
+        pos: [0.0, 0.0],
+        size: [1.0, 1.0],
+    };
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add_quad(
+        [[24.0, 8.0], [40.0, 8.0], [56.0, 56.0], [8.0, 56.0]],
+        &white,
+        full,
+        [1.0, 0.0, 0.0, 1.0],
+    );
+    batch.draw(&device, &shader);
+
+    assert_eq!(common::pixel(&device, 32, 12), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 12, 52), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 51, 52), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 12, 12), [0, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 51, 12), [0, 0, 0, 255]);
+}
+
+/// A linear sampler blends the texels of a texture that filters with
+/// nearest itself, without changing the texture.
+#[test]
+fn test_sampler_overrides_texture_filter() {
+    let (_context, device) = headless_device!(64, 8);
+    let shader = common::sprite_shader(&device);
+    let mut texture = Texture::new(&device, 2, 1).unwrap();
+    texture
+        .update_data(&device, &[255, 0, 0, 255, 0, 0, 255, 255])
+        .unwrap();
+    let sprite = Sprite::builder()
+        .size([64, 8])
+        .texture(texture.clone())
+        .build();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    // Left of the seam between the two texels.
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    assert_eq!(common::pixel(&device, 30, 4), [255, 0, 0, 255]);
+
+    let sampler = Sampler::new(&device, Filter::Linear, Wrap::ClampToEdge).unwrap();
+    batch.set_sampler(Some(Rc::new(sampler)));
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    let [red, _, blue, _] = common::pixel(&device, 30, 4);
+    assert!(red > 64 && red < 192, "red {}", red);
+    assert!(blue > 64 && blue < 192, "blue {}", blue);
+
+    batch.set_sampler(None);
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    assert_eq!(common::pixel(&device, 30, 4), [255, 0, 0, 255]);
+}
+
+/// Outside the texture, a border clamping sampler returns its border colour.
+#[test]
+fn test_sampler_border_color() {
+    let (_context, device) = headless_device!(64, 8);
+    let shader = common::sprite_shader(&device);
+    let mut texture = Texture::new(&device, 1, 1).unwrap();
+    texture.update_data(&device, &[255, 0, 0, 255]).unwrap();
+
+    let mut sampler = Sampler::new(&device, Filter::Nearest, Wrap::ClampToBorder).unwrap();
+    sampler
+        .set_border_color(&device, [0.0, 1.0, 0.0, 1.0])
+        .unwrap();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_sampler(Some(Rc::new(sampler)));
+
+    // The left half of the quad samples left of the texture.
+    let uv_rect = Rect {
+        pos: [-1.0, 0.0],
+        size: [2.0, 1.0],
+    };
+    let corners = [[0.0, 0.0], [64.0, 0.0], [64.0, 8.0], [0.0, 8.0]];
+    batch.add_quad(corners, &texture, uv_rect, [1.0; 4]);
+    batch.draw(&device, &shader);
+
+    assert_eq!(common::pixel(&device, 16, 4), [0, 255, 0, 255]);
+    assert_eq!(common::pixel(&device, 48, 4), [255, 0, 0, 255]);
+}
+
+/// The auxiliary texture is bound to its unit while drawing, and
+/// unbound afterwards, leaving the default unit active.
+#[test]
+fn test_auxiliary_texture_binding() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+    let texture = Texture::new(&device, 4, 4).unwrap();
+    let aux = Texture::new(&device, 4, 4).unwrap();
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch
+        .set_auxiliary_texture(&device, 1, "u_Normal", aux.clone())
+        .unwrap();
+    batch.add(&Sprite::with_texture([0, 0], texture));
+
+    let mut bound = None;
+    batch.draw_with_callback(&device, &shader, &mut |device, _shader, _texture| unsafe {
+        let gl = device.gl_context();
+        gl.active_texture(glow::TEXTURE1);
+        bound = Some(gl.get_parameter_i32(glow::TEXTURE_BINDING_2D) as u32);
+        gl.active_texture(glow::TEXTURE0);
+    });
+    assert_eq!(bound, Some(aux.raw_handle()));
+
+    unsafe {
+        let gl = device.gl_context();
+        assert_eq!(
+            gl.get_parameter_i32(glow::ACTIVE_TEXTURE) as u32,
+            glow::TEXTURE0
+        );
+        gl.active_texture(glow::TEXTURE1);
+        assert_eq!(gl.get_parameter_i32(glow::TEXTURE_BINDING_2D), 0);
+        gl.active_texture(glow::TEXTURE0);
+    }
+}
+
+/// Sprites outside the cull rectangle are counted, and not drawn.
+#[test]
+fn test_cull_rect() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let red = |pos| {
+        Sprite::builder()
+            .pos(pos)
+            .size([16, 16])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build()
+    };
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_cull_rect(Some(Rect {
+        pos: [0.0, 0.0],
+        size: [64.0, 64.0],
+    }));
+    batch.add(&red([8, 8]));
+    batch.add(&red([100, 8]));
+    batch.add(&red([8, -40]));
+    assert_eq!(batch.len(), 1);
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw(&device, &shader);
+    assert_eq!(batch.stats().culled, 2);
+    assert_eq!(batch.stats().flushes, 1);
+    assert_eq!(common::pixel(&device, 16, 16), [255, 0, 0, 255]);
+}
+
+/// Shapes are drawn as solid triangles of their colour.
+#[test]
+fn test_draw_shapes() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::sprite_shader(&device);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw_circle_filled([16.0, 16.0], 12.0, 32, [1.0, 0.0, 0.0, 1.0]);
+    batch.draw_circle([40.5, 40.5], 20.0, 64, [0.0, 0.0, 1.0, 1.0]);
+    batch.draw_line_aa([4.0, 60.0], [30.0, 60.0], 4.0, [0.0, 1.0, 0.0, 1.0]);
+    batch.draw(&device, &shader);
+
+    // Filled to the rim.
+    assert_eq!(common::pixel(&device, 16, 16), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 26, 16), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 30, 4), [0, 0, 0, 255]);
+
+    // Only the outline.
+    assert_eq!(common::pixel(&device, 60, 40), [0, 0, 255, 255]);
+    assert_eq!(common::pixel(&device, 40, 40), [0, 0, 0, 255]);
+
+    // Solid core of the line.
+    assert_eq!(common::pixel(&device, 16, 60), [0, 255, 0, 255]);
+    assert_eq!(common::pixel(&device, 16, 54), [0, 0, 0, 255]);
+}
+
+/// A retained batch draws the same image again without re-adding sprites.
+#[test]
+fn test_retained_draw_twice() {
+    let (_context, device) = headless_device!(32, 32);
+    let shader = common::sprite_shader(&device);
+    let whole = Rect {
+        pos: [0, 0],
+        size: [32, 32],
+    };
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_retained(true);
+    batch.add(
+        &Sprite::builder()
+            .pos([4, 4])
+            .size([8, 16])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+    batch.add(
+        &Sprite::builder()
+            .pos([16, 20])
+            .size([12, 8])
+            .color([0.0, 1.0, 0.0, 1.0])
+            .build(),
+    );
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw(&device, &shader);
+    let first = device.read_framebuffer(whole).unwrap();
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw(&device, &shader);
+    let second = device.read_framebuffer(whole).unwrap();
+
+    assert_eq!(batch.len(), 2);
+    assert_eq!(common::pixel(&device, 8, 8), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 20, 24), [0, 255, 0, 255]);
+    assert!(first == second);
+}
+
+/// Indices of an R8 texture select colours from the palette.
+#[test]
+fn test_palette_lookup() {
+    let (_context, device) = headless_device!(32, 8);
+    let shader = Shader::try_from_source(
+        &device,
+        include_str!("../src/sprite.vert"),
+        include_str!("../src/sprite_palette.frag"),
+    )
+    .unwrap();
+
+    let mut indices = Texture::with_format(&device, 2, 1, PixelFormat::R8).unwrap();
+    indices.update_data(&device, &[1, 2]).unwrap();
+    let mut palette = Texture::new(&device, 256, 1).unwrap();
+    let mut colors = vec![0; 256 * 4];
+    colors[4..12].copy_from_slice(&[255, 0, 0, 255, 0, 255, 0, 255]);
+    palette.update_data(&device, &colors).unwrap();
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_palette(palette);
+    batch.add(&Sprite::builder().size([32, 8]).texture(indices).build());
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw(&device, &shader);
+
+    assert_eq!(common::pixel(&device, 4, 4), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 28, 4), [0, 255, 0, 255]);
+}
+
+/// Textureless sprites are drawn as solid rectangles of their colour,
+/// sharing the device's white texture in a single flush.
+#[test]
+fn test_solid_color_sprites() {
+    let (_context, device) = headless_device!(32, 16);
+    let shader = common::sprite_shader(&device);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(
+        &Sprite::builder()
+            .size([16, 16])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+    batch.add(
+        &Sprite::builder()
+            .pos([16, 0])
+            .size([16, 16])
+            .color([0.0, 0.0, 1.0, 1.0])
+            .build(),
+    );
+    batch.draw(&device, &shader);
+
+    assert_eq!(batch.stats().flushes, 1);
+    assert_eq!(common::pixel(&device, 8, 8), [255, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 24, 8), [0, 0, 255, 255]);
+}
+
+/// A sprite whose texture was cleared is skipped, and drawn again
+/// once a texture is set.
+#[test]
+fn test_cleared_texture_skipped() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::sprite_shader(&device);
+    let mut texture = Texture::new(&device, 1, 1).unwrap();
+    texture.update_data(&device, &[255, 0, 0, 255]).unwrap();
+    let mut batch = SpriteBatch::new(&device).unwrap();
+
+    let mut sprite = Sprite::builder()
+        .size([16, 16])
+        .texture(texture.clone())
+        .build();
+    sprite.clear_texture();
+    device.clear_screen([0.0, 0.0, 1.0, 1.0]);
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    assert_eq!(common::pixel(&device, 8, 8), [0, 0, 255, 255]);
+
+    sprite.set_texture(texture);
+    batch.add(&sprite);
+    batch.draw(&device, &shader);
+    assert_eq!(common::pixel(&device, 8, 8), [255, 0, 0, 255]);
+}
+
+/// Each flush submits only the sprites added since the previous one,
+/// in order with drawing done in between.
+#[test]
+fn test_flush_now_submissions() {
+    let (_context, device) = headless_device!(32, 16);
+    let shader = common::sprite_shader(&device);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_retained(true);
+    let sprite = |x, color| {
+        Sprite::builder()
+            .pos([x, 0])
+            .size([16, 16])
+            .color(color)
+            .build()
+    };
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(&sprite(0, [1.0, 0.0, 0.0, 1.0]));
+    batch.flush_now(&device, &shader);
+    assert_eq!(batch.stats().flushes, 1);
+    assert!(batch.is_empty());
+    assert_eq!(common::pixel(&device, 8, 8), [255, 0, 0, 255]);
+
+    // The first sprite is not submitted again.
+    device.clear_screen([0.0, 1.0, 0.0, 1.0]);
+    batch.add(&sprite(16, [0.0, 0.0, 1.0, 1.0]));
+    batch.flush_now(&device, &shader);
+    assert_eq!(batch.stats().flushes, 1);
+    assert_eq!(common::pixel(&device, 8, 8), [0, 255, 0, 255]);
+    assert_eq!(common::pixel(&device, 24, 8), [0, 0, 255, 255]);
+}
+
+/// With `Lines`, a sprite is drawn as its outline.
+#[test]
+fn test_lines_primitive() {
+    let (_context, device) = headless_device!(32, 32);
+    let shader = common::sprite_shader(&device);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_primitive(Primitive::Lines);
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.add(
+        &Sprite::builder()
+            .pos([8, 8])
+            .size([16, 16])
+            .color([1.0, 0.0, 0.0, 1.0])
+            .build(),
+    );
+    batch.draw(&device, &shader);
+
+    // The top edge lies between two rows of pixels.
+    let column = device
+        .read_framebuffer(Rect {
+            pos: [16, 0],
+            size: [1, 32],
+        })
+        .unwrap();
+    let red_rows: Vec<usize> = (0..32).filter(|y| column[y * 4] == 255).collect();
+    assert!(
+        red_rows.iter().any(|y| (7..=8).contains(y)),
+        "{:?}",
+        red_rows
+    );
+    assert!(
+        red_rows.iter().any(|y| (23..=24).contains(y)),
+        "{:?}",
+        red_rows
+    );
+    assert_eq!(common::pixel(&device, 16, 16), [0, 0, 0, 255]);
+}
+
+/// Sprites of a batch with a texture array sample the layer they select,
+/// through the `u_Layer` uniform of `sprite_array.frag`.
+#[test]
+fn test_texture_array_layers() {
+    let (_context, device) = headless_device!(20, 10);
+    let shader = Shader::try_from_source(
+        &device,
+        include_str!("../src/sprite.vert"),
+        include_str!("../src/sprite_array.frag"),
+    )
+    .unwrap();
+    let location = unsafe {
+        device
+            .gl_context()
+            .get_uniform_location(shader.raw_handle(), "u_Layer")
+    };
+    assert_eq!(location, Some(3));
+
+    let red = [255, 0, 0, 255];
+    let blue = [0, 0, 255, 255];
+    let array = TextureArray::from_frames(&device, 1, 1, &[&red, &blue]).unwrap();
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_texture_array(Some(Rc::new(array)));
+    for layer in 0..2 {
+        let mut sprite = Sprite::builder()
+            .pos([layer as i32 * 10, 0])
+            .size([10, 10])
+            .build();
+        sprite.set_layer(layer);
+        batch.add(&sprite);
+    }
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw(&device, &shader);
+
+    assert_eq!(common::pixel(&device, 5, 5), red);
+    assert_eq!(common::pixel(&device, 15, 5), blue);
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// With the sprite texture on unit 2, the texture and sampler are bound
+/// to that unit, `u_Albedo` samples it, and unit 0 is left untouched.
+#[test]
+fn test_texture_unit() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+    let shader = common::sprite_shader(&device);
+    let mut red = Texture::new(&device, 1, 1).unwrap();
+    red.update_data(&device, &[255, 0, 0, 255]).unwrap();
+    let mut blue = Texture::new(&device, 1, 1).unwrap();
+    blue.update_data(&device, &[0, 0, 255, 255]).unwrap();
+    let sampler = Rc::new(Sampler::new(&device, Filter::Nearest, Wrap::ClampToEdge).unwrap());
+
+    // A texture left on unit 0 must not be sampled.
+    unsafe { gl.bind_texture(glow::TEXTURE_2D, Some(blue.raw_handle())) };
+
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_texture_unit(2);
+    batch.set_sampler(Some(sampler));
+    batch.add(
+        &Sprite::builder()
+            .size([16, 16])
+            .texture(red.clone())
+            .build(),
+    );
+
+    let mut bound = None;
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    batch.draw_with_callback(&device, &shader, &mut |device, shader, _texture| unsafe {
+        let gl = device.gl_context();
+        let mut albedo = [-1];
+        gl.get_uniform_i32(shader.raw_handle(), &1, &mut albedo);
+        bound = Some((
+            gl.get_parameter_i32(glow::ACTIVE_TEXTURE) as u32,
+            gl.get_parameter_i32(glow::TEXTURE_BINDING_2D) as u32,
+            gl.get_parameter_i32(glow::SAMPLER_BINDING) != 0,
+            albedo[0],
+        ));
+    });
+    assert_eq!(bound, Some((glow::TEXTURE2, red.raw_handle(), true, 2)));
+    assert_eq!(common::pixel(&device, 8, 8), [255, 0, 0, 255]);
+
+    unsafe {
+        assert_eq!(
+            gl.get_parameter_i32(glow::ACTIVE_TEXTURE) as u32,
+            glow::TEXTURE0
+        );
+        assert_eq!(
+            gl.get_parameter_i32(glow::TEXTURE_BINDING_2D) as u32,
+            blue.raw_handle()
+        );
+        gl.bind_texture(glow::TEXTURE_2D, None);
+    }
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// The middle of a gradient between half red and half green is darker
+/// when the colours are interpolated in linear space.
+#[test]
+fn test_linear_color_gradient() {
+    let (_context, device) = headless_device!(15, 1);
+    let shader = common::sprite_shader(&device);
+    let mut batch = SpriteBatch::new(&device).unwrap();
+    batch.set_retained(true);
+    batch.set_corner_callback(|_item, corner, vertex: &mut Vertex| {
+        // Left corners are the first and last.
+        vertex.color = match corner {
+            0 | 3 => [0.5, 0.0, 0.0, 1.0],
+            _ => [0.0, 0.5, 0.0, 1.0],
+        };
+    });
+    batch.add(&Sprite::builder().size([15, 1]).build());
+
+    let mut middle = |linear_color| {
+        batch.set_linear_color(linear_color);
+        device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+        batch.draw(&device, &shader);
+        common::pixel(&device, 7, 0)
+    };
+    let srgb = middle(false);
+    let linear = middle(true);
+
+    // The centre of the middle pixel is half way along the gradient.
+    let channel = |value: f32| (value * 255.0).round() as i32;
+    let expected_srgb = channel(0.25);
+    let expected_linear = channel(color::srgb_to_linear(0.5) / 2.0);
+    for (pixel, expected) in &[(srgb, expected_srgb), (linear, expected_linear)] {
+        assert!((pixel[0] as i32 - expected).abs() <= 1, "{:?}", pixel);
+        assert!((pixel[1] as i32 - expected).abs() <= 1, "{:?}", pixel);
+    }
+    assert!(linear[0] < srgb[0]);
+    assert_eq!(device.take_gl_error(), None);
+}
@@ -0,0 +1,333 @@
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{
+    errors::Error,
+    rect::Rect,
+    texture::{CompressedFormat, ImageView, PixelFormat, Texture, TextureParams},
+};
+
+#[test]
+fn test_lod_params() {
+    let (context, device) = headless_device!(64, 64);
+    let params = TextureParams {
+        lod_bias: 1.5,
+        ..Default::default()
+    };
+
+    let texture = Texture::with_params(&device, 16, 16, params).unwrap();
+    assert_eq!(texture.params().lod_bias, 1.5);
+    let [min_lod, max_lod, lod_bias] = unsafe {
+        let gl = device.gl_context();
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+        let lod = |parameter| common::get_tex_parameter_floats(&context, parameter, 1)[0];
+        let lod = [
+            lod(glow::TEXTURE_MIN_LOD),
+            lod(glow::TEXTURE_MAX_LOD),
+            lod(glow::TEXTURE_LOD_BIAS),
+        ];
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        lod
+    };
+    // The defaults are OpenGL's.
+    assert_eq!([min_lod, max_lod], [-1000.0, 1000.0]);
+    assert_eq!(lod_bias, 1.5);
+
+    // Shared by sub-views.
+    let clamped = TextureParams {
+        min_lod: 1.0,
+        max_lod: 2.0,
+        lod_bias: -1.0,
+        ..Default::default()
+    };
+    let sub_texture = texture.new_sub([0, 0], [8, 8]).unwrap();
+    sub_texture.set_params(&device, clamped).unwrap();
+    assert_eq!(texture.params(), clamped);
+}
+
+/// The anisotropy is set on the texture, clamped to the device's maximum.
+#[test]
+fn test_anisotropy_param() {
+    let (_context, device) = headless_device!(16, 16);
+    let max = match device.max_anisotropy() {
+        Some(max) => max,
+        None => {
+            eprintln!("skipped: no anisotropic filtering");
+            return;
+        }
+    };
+    let params = TextureParams {
+        anisotropy: max + 1.0,
+        ..Default::default()
+    };
+
+    let texture = Texture::with_params(&device, 16, 16, params).unwrap();
+    assert_eq!(texture.params().anisotropy, max);
+    let anisotropy = unsafe {
+        let gl = device.gl_context();
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+        // Drivers report whole numbers, like `max_anisotropy`.
+        let anisotropy = gl.get_tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAX_ANISOTROPY);
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        anisotropy
+    };
+    assert_eq!(anisotropy as f32, max);
+}
+
+/// A single DXT1 block of red texels, decoded by the driver.
+#[test]
+fn test_dxt1_upload() {
+    let (_context, device) = headless_device!(16, 16);
+    if !device.has_extension(CompressedFormat::Dxt1.extension()) {
+        eprintln!("skipped: no S3TC support");
+        return;
+    }
+
+    // Both endpoints red in RGB565, and every index selecting the first.
+    let block = [0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0];
+    assert!(matches!(
+        Texture::from_compressed(&device, CompressedFormat::Dxt1, 4, 4, &block[..7]),
+        Err(Error::InvalidImageData {
+            expected: 8,
+            actual: 7
+        })
+    ));
+
+    let texture = Texture::from_compressed(&device, CompressedFormat::Dxt1, 4, 4, &block).unwrap();
+    assert_eq!(device.take_gl_error(), None);
+    let texels = texture.read_pixels(&device).unwrap();
+    for texel in texels.chunks(4) {
+        assert_eq!(texel, [255, 0, 0, 255]);
+    }
+}
+
+/// Both filters follow `set_smooth`, and the previous binding is restored.
+#[test]
+fn test_set_smooth() {
+    let (_context, device) = headless_device!(16, 16);
+    let texture = Texture::new(&device, 4, 4).unwrap();
+    let bound = Texture::new(&device, 4, 4).unwrap();
+
+    let filters = || unsafe {
+        let gl = device.gl_context();
+        gl.bind_texture(glow::TEXTURE_2D, Some(texture.raw_handle()));
+        let filters = [
+            gl.get_tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER) as u32,
+            gl.get_tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER) as u32,
+        ];
+        gl.bind_texture(glow::TEXTURE_2D, Some(bound.raw_handle()));
+        filters
+    };
+
+    assert_eq!(filters(), [glow::NEAREST, glow::NEAREST]);
+    texture.set_smooth(&device, true);
+    assert_eq!(filters(), [glow::LINEAR, glow::LINEAR]);
+    texture.set_smooth(&device, false);
+    assert_eq!(filters(), [glow::NEAREST, glow::NEAREST]);
+
+    let binding = unsafe {
+        device
+            .gl_context()
+            .get_parameter_i32(glow::TEXTURE_BINDING_2D)
+    };
+    assert_eq!(binding as u32, bound.raw_handle());
+}
+
+/// The center of a larger image lands at a position relative to the
+/// sub-view, and a region that doesn't fit the view is refused.
+#[test]
+fn test_update_sub_from_region() {
+    let (_context, device) = headless_device!(16, 16);
+    let mut texture = Texture::new(&device, 8, 8).unwrap();
+    texture.update_data(&device, &[0; 8 * 8 * 4]).unwrap();
+    let mut view = texture.new_sub([4, 4], [4, 4]).unwrap();
+
+    // Red 2x2 center in a blue 6x6 image.
+    let mut image = vec![];
+    for y in 0..6 {
+        for x in 0..6 {
+            let center = (2..4).contains(&x) && (2..4).contains(&y);
+            image.extend_from_slice(if center {
+                &[255, 0, 0, 255]
+            } else {
+                &[0, 0, 255, 255]
+            });
+        }
+    }
+    let center = Rect {
+        pos: [2, 2],
+        size: [2, 2],
+    };
+    let src = ImageView::new(&image, 6, 6, center).unwrap();
+    view.update_sub_from_region(&device, [1, 1], &src).unwrap();
+
+    let texels = texture.read_pixels(&device).unwrap();
+    for y in 0..8 {
+        for x in 0..8 {
+            let expected = if (5..7).contains(&x) && (5..7).contains(&y) {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 0, 0]
+            };
+            let i = (y * 8 + x) * 4;
+            assert_eq!(texels[i..i + 4], expected, "texel {}, {}", x, y);
+        }
+    }
+
+    assert!(matches!(
+        view.update_sub_from_region(&device, [3, 3], &src),
+        Err(Error::InvalidSubTexture { .. })
+    ));
+
+    let mut single = Texture::with_format(&device, 8, 8, PixelFormat::R8).unwrap();
+    assert!(matches!(
+        single.update_sub_from_region(&device, [0, 0], &src),
+        Err(Error::InvalidPixelFormat {
+            expected: PixelFormat::Rgba8,
+            actual: PixelFormat::R8
+        })
+    ));
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// Single channel data streamed through a pixel buffer fills the red
+/// channel, including rows that aren't 4-byte aligned, and data sized
+/// for another format is refused.
+#[test]
+fn test_update_data_async_r8() {
+    let (_context, device) = headless_device!(16, 16);
+    let mut texture = Texture::with_format(&device, 3, 2, PixelFormat::R8).unwrap();
+    let values = [10, 20, 30, 40, 50, 60];
+
+    texture.update_data_async(&device, &values).unwrap();
+    let texels = texture.read_pixels(&device).unwrap();
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(texels[i * 4..i * 4 + 4], [*value, 0, 0, 255], "texel {}", i);
+    }
+
+    assert!(matches!(
+        texture.update_data_async(&device, &[0; 3 * 2 * 4]),
+        Err(Error::InvalidImageData {
+            expected: 6,
+            actual: 24
+        })
+    ));
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// The copied region of the destination matches the source region,
+/// and the rest of the destination is untouched.
+#[test]
+fn test_copy_region() {
+    let (_context, device) = headless_device!(16, 16);
+    let mut src = Texture::new(&device, 4, 4).unwrap();
+    let src_texels: Vec<u8> = (0..16)
+        .flat_map(|i| vec![i * 16, 255 - i, 0, 255])
+        .collect();
+    src.update_data(&device, &src_texels).unwrap();
+    let mut dst = Texture::new(&device, 4, 4).unwrap();
+    dst.update_data(&device, &[0; 4 * 4 * 4]).unwrap();
+
+    let src_rect = Rect {
+        pos: [1, 1],
+        size: [2, 2],
+    };
+    dst.copy_region(&device, &src, src_rect, [2, 0]).unwrap();
+
+    let texels = dst.read_pixels(&device).unwrap();
+    for y in 0..4 {
+        for x in 0..4 {
+            let i = (y * 4 + x) * 4;
+            let expected = if x >= 2 && y < 2 {
+                let j = ((y + 1) * 4 + x - 1) * 4;
+                &src_texels[j..j + 4]
+            } else {
+                &[0; 4][..]
+            };
+            assert_eq!(&texels[i..i + 4], expected, "texel {}, {}", x, y);
+        }
+    }
+
+    let outside = Rect {
+        pos: [3, 3],
+        size: [2, 2],
+    };
+    assert!(dst.copy_region(&device, &src, outside, [0, 0]).is_err());
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// A path that doesn't exist loads the shared checkerboard instead.
+#[test]
+#[cfg(feature = "image")]
+fn test_missing_path_fallback() {
+    let (_context, device) = headless_device!(16, 16);
+
+    assert!(Texture::from_path(&device, "does/not/exist.png").is_err());
+    let texture = Texture::from_path_or_missing(&device, "does/not/exist.png").unwrap();
+    assert_eq!(
+        texture.raw_handle(),
+        device.missing_texture().unwrap().raw_handle()
+    );
+    assert_eq!(texture.rect().size, [Texture::MISSING_SIZE; 2]);
+
+    // Magenta and black cells of 4 texels.
+    let texels = texture.read_pixels(&device).unwrap();
+    let texel = |x: usize, y: usize| {
+        let i = (y * Texture::MISSING_SIZE as usize + x) * 4;
+        texels[i..i + 4].to_vec()
+    };
+    assert_eq!(texel(0, 0), [255, 0, 255, 255]);
+    assert_eq!(texel(4, 0), [0, 0, 0, 255]);
+    assert_eq!(texel(4, 4), [255, 0, 255, 255]);
+    assert_eq!(texel(15, 8), [0, 0, 0, 255]);
+}
+
+/// An uploaded pattern is saved and reloaded unchanged, with single
+/// channel textures saved as greyscale.
+#[test]
+#[cfg(feature = "image")]
+fn test_save_png_round_trip() {
+    let (_context, device) = headless_device!(16, 16);
+    let path = std::env::temp_dir().join("grok_glow_test_save_png_round_trip.png");
+
+    let mut texture = Texture::new(&device, 4, 2).unwrap();
+    let rgba: Vec<u8> = (0..4 * 2 * 4).map(|i| (i * 8) as u8).collect();
+    texture.update_data(&device, &rgba).unwrap();
+    texture.save_png(&device, &path).unwrap();
+    let reloaded = image::open(&path).unwrap();
+    assert_eq!(reloaded.color(), image::ColorType::Rgba8);
+    assert_eq!(reloaded.to_rgba8().into_raw(), rgba);
+
+    let mut texture = Texture::with_format(&device, 4, 2, PixelFormat::R8).unwrap();
+    let grey: Vec<u8> = (0..4 * 2).map(|i| (i * 32) as u8).collect();
+    texture.update_data(&device, &grey).unwrap();
+    texture.save_png(&device, &path).unwrap();
+    let reloaded = image::open(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert_eq!(reloaded.color(), image::ColorType::L8);
+    assert_eq!(reloaded.to_luma8().into_raw(), grey);
+}
+
+/// Creating a texture leaves the active unit's binding as it was, since
+/// textures like the white texture are created in the middle of a draw.
+#[test]
+fn test_new_keeps_binding() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+    let bound = Texture::new(&device, 1, 1).unwrap();
+
+    unsafe {
+        gl.active_texture(glow::TEXTURE1);
+        gl.bind_texture(glow::TEXTURE_2D, Some(bound.raw_handle()));
+    }
+    let _texture = Texture::new(&device, 4, 4).unwrap();
+    unsafe {
+        assert_eq!(
+            gl.get_parameter_i32(glow::TEXTURE_BINDING_2D) as u32,
+            bound.raw_handle()
+        );
+        gl.bind_texture(glow::TEXTURE_2D, None);
+        gl.active_texture(glow::TEXTURE0);
+    }
+}
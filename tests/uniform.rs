@@ -0,0 +1,62 @@
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{
+    shader::Shader,
+    uniform::UniformBuffer,
+    vertex::{Vertex, VertexBuffer},
+};
+
+const VERTEX: &str = "#version 330 core
+layout(location = 0) in vec2 a_Pos;
+void main() {
+    gl_Position = vec4(a_Pos, 0.0, 1.0);
+}";
+/// Block of a single colour, laid out the same in `std140`.
+const FRAGMENT: &str = "#version 330 core
+layout(std140) uniform Tint {
+    vec4 u_Tint;
+};
+out vec4 Color;
+void main() {
+    Color = u_Tint;
+}";
+
+/// The shader reads the colour from the buffer bound to its block's
+/// binding point, before and after an update.
+#[test]
+fn test_uniform_block_draw() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = Shader::try_from_source(&device, VERTEX, FRAGMENT).unwrap();
+    let vertex = |position| Vertex {
+        position,
+        uv: [0.0, 0.0],
+        color: [1.0; 4],
+    };
+    let quad = [
+        vertex([-1.0, -1.0]),
+        vertex([1.0, -1.0]),
+        vertex([1.0, 1.0]),
+        vertex([-1.0, 1.0]),
+    ];
+    let vertex_buffer = VertexBuffer::new_static(&device, &quad, &[0, 1, 2, 0, 2, 3]).unwrap();
+
+    let tint = UniformBuffer::new(&device, 2, &[1.0f32, 0.0, 0.0, 1.0]).unwrap();
+    assert_eq!(tint.binding(), 2);
+    shader.bind_uniform_block(&device, "Tint", 2).unwrap();
+
+    let draw = || unsafe {
+        device.gl_context().use_program(Some(shader.raw_handle()));
+        vertex_buffer.draw(&device, 0, 6);
+        device.gl_context().use_program(None);
+    };
+
+    draw();
+    assert_eq!(common::pixel(&device, 8, 8), [255, 0, 0, 255]);
+
+    tint.update(&device, &[0.0, 0.0, 1.0, 1.0]).unwrap();
+    draw();
+    assert_eq!(common::pixel(&device, 8, 8), [0, 0, 255, 255]);
+    assert_eq!(device.take_gl_error(), None);
+}
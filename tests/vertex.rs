@@ -0,0 +1,183 @@
+#[macro_use]
+mod common;
+
+use glow::HasContext;
+use grok_glow::{
+    color::Color,
+    primitives::{self, Primitive},
+    vertex::{BatchVertex, IndexType, Vertex, VertexAttribute, VertexBuffer, VertexFormat},
+};
+
+/// Corners of a quad in clip space, counter-clockwise from the bottom-left.
+fn quad(left: f32, right: f32, color: Color) -> [Vertex; 4] {
+    let vertex = |position, uv| Vertex {
+        position,
+        uv,
+        color,
+    };
+    [
+        vertex([left, -1.0], [0.0, 0.0]),
+        vertex([right, -1.0], [1.0, 0.0]),
+        vertex([right, 1.0], [1.0, 1.0]),
+        vertex([left, 1.0], [0.0, 1.0]),
+    ]
+}
+
+/// Two quads share a buffer, and the second is drawn with the indices
+/// of the first, offset by a base vertex.
+#[test]
+fn test_draw_range_base_vertex() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::color_shader(&device);
+
+    let mut vertices = quad(-1.0, 0.0, [1.0, 0.0, 0.0, 1.0]).to_vec();
+    vertices.extend_from_slice(&quad(0.0, 1.0, [0.0, 1.0, 0.0, 1.0]));
+    let vertex_buffer = VertexBuffer::new_static(&device, &vertices, &[0, 1, 2, 0, 2, 3]).unwrap();
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    unsafe { device.gl_context().use_program(Some(shader.raw_handle())) };
+    vertex_buffer.draw_range(&device, 0, 6, 4);
+
+    assert_eq!(common::pixel(&device, 16, 32), [0, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 48, 32), [0, 255, 0, 255]);
+}
+
+/// A buffer of 32-bit indices draws with `UNSIGNED_INT`, and offsets
+/// into its indices by 4 bytes per index.
+#[test]
+fn test_draw_u32_indices() {
+    let (_context, device) = headless_device!(64, 64);
+    let shader = common::color_shader(&device);
+
+    let mut vertices = quad(-1.0, 0.0, [1.0, 0.0, 0.0, 1.0]).to_vec();
+    vertices.extend_from_slice(&quad(0.0, 1.0, [0.0, 1.0, 0.0, 1.0]));
+    let indices: [u32; 12] = [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7];
+    let vertex_buffer = VertexBuffer::new_static_u32(&device, &vertices, &indices).unwrap();
+    assert_eq!(vertex_buffer.index_type(), IndexType::U32);
+
+    device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+    unsafe { device.gl_context().use_program(Some(shader.raw_handle())) };
+    vertex_buffer.draw(&device, 6, 6);
+
+    assert_eq!(common::pixel(&device, 16, 32), [0, 0, 0, 255]);
+    assert_eq!(common::pixel(&device, 48, 32), [0, 255, 0, 255]);
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// Stub vertex whose extra attribute is at a location past
+/// `GL_MAX_VERTEX_ATTRIBS`, so describing the layout fails.
+#[derive(Debug, Clone)]
+#[repr(C)]
+struct BrokenVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+    extra: f32,
+}
+
+unsafe impl BatchVertex for BrokenVertex {
+    fn format() -> VertexFormat {
+        VertexFormat::Custom {
+            stride: std::mem::size_of::<BrokenVertex>(),
+            offsets: [0, 8, 16],
+        }
+    }
+
+    fn extra_attributes() -> Vec<VertexAttribute> {
+        vec![VertexAttribute {
+            location: 4096,
+            components: 1,
+            offset: 32,
+        }]
+    }
+
+    fn from_vertex(vertex: Vertex) -> Self {
+        BrokenVertex {
+            position: vertex.position,
+            uv: vertex.uv,
+            color: vertex.color,
+            extra: 0.0,
+        }
+    }
+}
+
+/// A buffer that fails to initialise returns an error, and deletes
+/// the vertex array and buffers it already created.
+#[test]
+fn test_failed_creation_releases_objects() {
+    let (_context, device) = headless_device!(16, 16);
+    let gl = unsafe { device.gl_context() };
+
+    // The failed creation takes the lowest free buffer names.
+    let first = unsafe { gl.create_buffer().unwrap() };
+    unsafe { gl.delete_buffer(first) };
+
+    let vertices = [BrokenVertex::from_vertex(
+        quad(-1.0, 1.0, [1.0; 4])[0].clone(),
+    )];
+    assert!(VertexBuffer::new_static_custom(&device, &vertices, &[0]).is_err());
+
+    for name in first..first + 4 {
+        assert!(unsafe { !gl.is_buffer(name) }, "buffer {} leaked", name);
+    }
+    assert_eq!(
+        unsafe { gl.get_parameter_i32(glow::VERTEX_ARRAY_BINDING) },
+        0
+    );
+    assert_eq!(device.take_gl_error(), None);
+}
+
+/// A two-row strip separated by the restart index draws both rows, but
+/// not the triangles joining the end of the first to the start of the
+/// second, which cover the middle of the window when drawn.
+#[test]
+fn test_primitive_restart_grid() {
+    let (_context, device) = headless_device!(16, 16);
+    let shader = common::color_shader(&device);
+    let vertex = |position| Vertex {
+        position,
+        uv: [0.0, 0.0],
+        color: [1.0, 0.0, 0.0, 1.0],
+    };
+    // Rows of the grid from the top. The first row is slanted, so the
+    // joining triangles reach outside the rows.
+    let vertices = [
+        vertex([-1.0, 1.0]),
+        vertex([1.0, 1.0]),
+        vertex([-1.0, 0.0]),
+        vertex([-0.5, 0.0]),
+        vertex([-1.0, -1.0]),
+        vertex([-0.5, -1.0]),
+    ];
+    let indices = primitives::grid_strip_indices(1, 2);
+    assert!(indices.contains(&primitives::RESTART_INDEX));
+    let joined: Vec<u16> = indices
+        .iter()
+        .copied()
+        .filter(|index| *index != primitives::RESTART_INDEX)
+        .collect();
+
+    let draw = |indices: &[u16]| {
+        let vertex_buffer = VertexBuffer::new_static(&device, &vertices, indices).unwrap();
+        device.clear_screen([0.0, 0.0, 0.0, 1.0]);
+        unsafe { device.gl_context().use_program(Some(shader.raw_handle())) };
+        vertex_buffer.draw_primitives(&device, Primitive::TriangleStrip, 0, indices.len(), 0);
+        unsafe { device.gl_context().use_program(None) };
+    };
+    let red = [255, 0, 0, 255];
+    let black = [0, 0, 0, 255];
+
+    device
+        .set_primitive_restart(Some(primitives::RESTART_INDEX as u32))
+        .unwrap();
+    draw(&indices);
+    assert_eq!(common::pixel(&device, 1, 1), red);
+    assert_eq!(common::pixel(&device, 1, 14), red);
+    assert_eq!(common::pixel(&device, 8, 6), black);
+
+    // The same rows joined into a single strip.
+    device.set_primitive_restart(None).unwrap();
+    draw(&joined);
+    assert_eq!(common::pixel(&device, 8, 6), red);
+    assert_eq!(device.take_gl_error(), None);
+}